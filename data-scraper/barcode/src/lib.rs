@@ -0,0 +1,100 @@
+//! Barcodes as the scraper stores them and the web app looks them up, shared so the
+//! two always agree on which codes are the same product.
+
+/// Longest code in the GTIN family (GTIN-14)
+const MAX_DIGITS: usize = 14;
+
+/// Reduces a barcode to the form it's stored and looked up by: digits only, without
+/// leading zeros, so EAN-13, UPC-A and GTIN-14 spellings of the same product all
+/// match. Codes that aren't GTINs are kept as they are, trimmed.
+pub fn normalize(code: &str) -> Option<String> {
+    let code = code.trim();
+    let digits = code
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>();
+
+    if !digits.is_empty() && digits.len() <= MAX_DIGITS && digits.chars().all(|c| c.is_ascii_digit()) {
+        let trimmed = digits.trim_start_matches('0');
+        return match trimmed.is_empty() {
+            true => None,
+            false => Some(trimmed.to_owned()),
+        };
+    }
+
+    match code.is_empty() {
+        true => None,
+        false => Some(code.to_owned()),
+    }
+}
+
+/// Whether a normalized code is a GTIN rather than some other kind of code
+pub fn is_gtin(code: &str) -> bool {
+    !code.is_empty() && code.len() <= MAX_DIGITS && code.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Whether the last digit is the correct GTIN check digit for the rest of the code.
+/// A failure usually means the code was typed wrong. Leading zeros don't change the
+/// check digit, so normalized codes can be checked.
+pub fn has_valid_check_digit(code: &str) -> bool {
+    let Some(digits) = code
+        .chars()
+        .map(|c| c.to_digit(10))
+        .collect::<Option<Vec<u32>>>()
+    else {
+        return false;
+    };
+
+    let Some((check, rest)) = digits.split_last() else {
+        return false;
+    };
+
+    // Weights alternate 3, 1, 3, ... starting from the digit next to the check digit
+    let sum: u32 = rest
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, digit)| if i % 2 == 0 { digit * 3 } else { *digit })
+        .sum();
+
+    (10 - sum % 10) % 10 == *check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spellings_of_one_gtin_match() {
+        let upc = normalize("041234567893");
+        assert_eq!(upc.as_deref(), Some("41234567893"));
+        assert_eq!(normalize("0041234567893"), upc);
+        assert_eq!(normalize("00041234567893"), upc);
+        assert_eq!(normalize(" 0 41234-56789 3 "), upc);
+    }
+
+    #[test]
+    fn other_codes_are_kept_trimmed() {
+        assert_eq!(normalize(" ABC-123 ").as_deref(), Some("ABC-123"));
+        assert_eq!(normalize("123456789012345").as_deref(), Some("123456789012345"));
+        assert!(!is_gtin("ABC-123"));
+        assert!(!is_gtin("123456789012345"));
+        assert!(is_gtin("41234567893"));
+    }
+
+    #[test]
+    fn blank_codes_are_nothing() {
+        assert_eq!(normalize(""), None);
+        assert_eq!(normalize("   "), None);
+        assert_eq!(normalize("0000"), None);
+    }
+
+    #[test]
+    fn check_digits() {
+        assert!(has_valid_check_digit("9415767004351"));
+        assert!(has_valid_check_digit("36000291452"));
+        assert!(!has_valid_check_digit("9415767004353"));
+        assert!(!has_valid_check_digit("ABC"));
+        assert!(!has_valid_check_digit(""));
+    }
+}
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.4
 
+use money::Money;
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
@@ -7,14 +8,18 @@ use sea_orm::entity::prelude::*;
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
-    pub timestamp: DateTime,
+    pub timestamp: DateTimeWithTimeZone,
     pub supermarket_id: i32,
     pub product_id: i32,
-    #[sea_orm(column_type = "Float")]
-    pub price: f32,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))")]
+    pub price: Money,
     pub on_special: Option<bool>,
-    #[sea_orm(column_type = "Float", nullable)]
-    pub original_price: Option<f32>,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))", nullable)]
+    pub original_price: Option<Money>,
+    pub multibuy_quantity: Option<i32>,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))", nullable)]
+    pub multibuy_price: Option<Money>,
+    pub flagged: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
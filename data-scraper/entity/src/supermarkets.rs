@@ -15,10 +15,26 @@ pub struct Model {
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::product_detail::Entity")]
+    ProductDetail,
+    #[sea_orm(has_many = "super::scrape_run::Entity")]
+    ScrapeRun,
     #[sea_orm(has_many = "super::supermarket_price::Entity")]
     SupermarketPrice,
 }
 
+impl Related<super::product_detail::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ProductDetail.def()
+    }
+}
+
+impl Related<super::scrape_run::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ScrapeRun.def()
+    }
+}
+
 impl Related<super::supermarket_price::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::SupermarketPrice.def()
@@ -0,0 +1,75 @@
+//! A stand-in for the Countdown product API that serves the canned responses in
+//! `fixtures/countdown`, so the fetcher can be run end to end without hitting
+//! countdown.co.nz. Start it, then point the scraper at it:
+//!
+//! ```text
+//! cargo run --example mock_countdown -- --page-size 2 --fail-every 3
+//! COUNTDOWN_BASE_URL=http://127.0.0.1:8089 cargo run
+//! ```
+//!
+//! With the `sqlite` feature the scraper doesn't need a Postgres server either. Each
+//! run starts from an empty, freshly migrated database:
+//!
+//! ```text
+//! COUNTDOWN_BASE_URL=http://127.0.0.1:8089 DATABASE_URL=sqlite::memory: cargo run --features sqlite
+//! ```
+//!
+//! `target=specials` only serves the items on special, and `target=search` looks for
+//! the `search` text in every department's barcodes, brands and names. Product
+//! details, `/api/v1/products/{sku}`, are served from `products/{sku}.json`.
+//!
+//! Options:
+//!  - `--port <n>`: port to listen on (default 8089)
+//!  - `--fixtures <dir>`: where the fixture files live (default `fixtures/countdown`)
+//!  - `--page-size <n>`: most items returned per page, to force pagination
+//!  - `--fail-every <n>`: answer every nth request with a 500
+//!  - `--rate-limit <n>`: answer with a 429 and `Retry-After` once more than n requests arrive in a second
+
+mod mock;
+
+use std::{net::SocketAddr, path::PathBuf};
+
+use mock::{MockState, Options};
+
+/// The port to listen on, and how the mock should behave
+fn parse_args() -> Result<(u16, Options), String> {
+    let mut port = 8089;
+    let mut options = Options::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(format!("{flag} needs a value"))?;
+        let number = || value.parse::<usize>().map_err(|_| format!("{flag} must be a number"));
+
+        match flag.as_str() {
+            "--port" => port = value.parse().map_err(|_| "--port must be a port number".to_owned())?,
+            "--fixtures" => options.fixtures = PathBuf::from(&value),
+            "--page-size" => options.page_size = Some(number()?),
+            "--fail-every" => options.fail_every = Some(number()?),
+            "--rate-limit" => options.rate_limit = Some(number()?),
+            _ => return Err(format!("Unknown option {flag}")),
+        }
+    }
+
+    Ok((port, options))
+}
+
+#[tokio::main]
+async fn main() {
+    let (port, options) = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let app = mock::router(MockState::new(options));
+
+    println!("Mock Countdown API listening on http://{addr}");
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .await
+        .unwrap();
+}
@@ -0,0 +1,217 @@
+//! The mock Countdown API's routes, shared by the `mock_countdown` example and the
+//! scraper's tests
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde_json::Value;
+
+pub struct Options {
+    pub fixtures: PathBuf,
+    /// Most items returned per page, to force pagination
+    pub page_size: Option<usize>,
+    /// Answer every nth request with a 500
+    pub fail_every: Option<usize>,
+    /// Answer with a 429 and `Retry-After` once more than n requests arrive in a second
+    pub rate_limit: Option<usize>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            fixtures: PathBuf::from("fixtures/countdown"),
+            page_size: None,
+            fail_every: None,
+            rate_limit: None,
+        }
+    }
+}
+
+pub struct MockState {
+    options: Options,
+    /// Every request seen, including the ones answered with a failure
+    pub requests: AtomicUsize,
+    /// Requests answered with a 429
+    pub rate_limited: AtomicUsize,
+    /// Start of the current one second rate limit window, and requests seen in it
+    window: Mutex<(Instant, usize)>,
+}
+
+impl MockState {
+    pub fn new(options: Options) -> Arc<Self> {
+        Arc::new(MockState {
+            options,
+            requests: AtomicUsize::new(0),
+            rate_limited: AtomicUsize::new(0),
+            window: Mutex::new((Instant::now(), 0)),
+        })
+    }
+
+    fn is_rate_limited(&self) -> bool {
+        let Some(limit) = self.options.rate_limit else {
+            return false;
+        };
+
+        let mut window = self.window.lock().unwrap();
+        if window.0.elapsed() >= Duration::from_secs(1) {
+            *window = (Instant::now(), 0);
+        }
+        window.1 += 1;
+
+        window.1 > limit
+    }
+}
+
+/// Which fixture answers the request: the department named in `dasFilter`, or the
+/// department listing when there is no filter
+fn fixture_name(query: &HashMap<String, String>) -> Option<String> {
+    let Some(filter) = query.get("dasFilter") else {
+        return Some("departments".to_owned());
+    };
+
+    // e.g. "Department;;fruit-veg;false"
+    let department = filter.split(";;").nth(1)?.split(';').next()?;
+    if department.is_empty() || !department.chars().all(|c| c.is_ascii_lowercase() || c == '-') {
+        return None;
+    }
+
+    Some(department.to_owned())
+}
+
+/// Whether a product matches a search: the same barcode, or every word of the search
+/// in its brand or name
+fn matches_search(item: &Value, search: &str) -> bool {
+    if item["barcode"].as_str() == Some(search) {
+        return true;
+    }
+
+    let text = format!("{} {}", item["brand"].as_str().unwrap_or(""), item["name"].as_str().unwrap_or("")).to_lowercase();
+    search.split_whitespace().all(|word| text.contains(&word.to_lowercase()))
+}
+
+/// Every product in any department fixture matching the search
+async fn search_fixtures(fixtures: &PathBuf, search: &str) -> Result<Value, String> {
+    let mut entries = tokio::fs::read_dir(fixtures).await.map_err(|e| e.to_string())?;
+    let mut found = Vec::new();
+    while let Some(entry) = entries.next_entry().await.map_err(|e| e.to_string())? {
+        let path = entry.path();
+        if entry.file_name() == "departments.json" || path.extension().is_none_or(|x| x != "json") {
+            continue;
+        }
+
+        let contents = tokio::fs::read_to_string(path).await.map_err(|e| e.to_string())?;
+        let root = serde_json::from_str::<Value>(&contents).map_err(|e| e.to_string())?;
+        if let Some(items) = root["products"]["items"].as_array() {
+            found.extend(items.iter().filter(|x| x["type"] == "Product" && matches_search(x, search)).cloned());
+        }
+    }
+
+    Ok(serde_json::json!({
+        "products": { "items": found, "totalItems": found.len() },
+        "isSuccessful": true,
+        "dasFacets": [],
+    }))
+}
+
+/// Counts the request, and answers it with a failure if it's time for one
+fn simulate_trouble(state: &MockState, request: impl std::fmt::Debug) -> Option<Response> {
+    let request_number = state.requests.fetch_add(1, Ordering::SeqCst) + 1;
+    println!("#{request_number} {request:?}");
+
+    if state.is_rate_limited() {
+        state.rate_limited.fetch_add(1, Ordering::SeqCst);
+        return Some((StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, "1")], "Too many requests").into_response());
+    }
+    if state.options.fail_every.is_some_and(|n| n > 0 && request_number % n == 0) {
+        return Some((StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response());
+    }
+
+    None
+}
+
+async fn product_detail(State(state): State<Arc<MockState>>, Path(sku): Path<String>) -> Response {
+    if let Some(response) = simulate_trouble(&state, format!("detail {sku}")) {
+        return response;
+    }
+
+    if sku.is_empty() || !sku.chars().all(|c| c.is_ascii_digit()) {
+        return (StatusCode::NOT_FOUND, "No such product").into_response();
+    }
+    let Ok(contents) = tokio::fs::read_to_string(state.options.fixtures.join("products").join(format!("{sku}.json"))).await else {
+        return (StatusCode::NOT_FOUND, "No such product").into_response();
+    };
+
+    ([(header::CONTENT_TYPE, "application/json")], contents).into_response()
+}
+
+async fn products(State(state): State<Arc<MockState>>, Query(query): Query<HashMap<String, String>>) -> Response {
+    if let Some(response) = simulate_trouble(&state, &query) {
+        return response;
+    }
+
+    let target = query.get("target").map(|x| x.as_str()).unwrap_or("browse");
+    let mut root = if target == "search" {
+        let Some(search) = query.get("search") else {
+            return (StatusCode::BAD_REQUEST, "Search without search text").into_response();
+        };
+        match search_fixtures(&state.options.fixtures, search).await {
+            Ok(root) => root,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Couldn't search fixtures: {e}")).into_response(),
+        }
+    } else {
+        let Some(name) = fixture_name(&query) else {
+            return (StatusCode::BAD_REQUEST, "Bad dasFilter").into_response();
+        };
+        let Ok(contents) = tokio::fs::read_to_string(state.options.fixtures.join(format!("{name}.json"))).await else {
+            return (StatusCode::NOT_FOUND, format!("No fixture for {name}")).into_response();
+        };
+        match serde_json::from_str::<Value>(&contents) {
+            Ok(root) => root,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Broken fixture {name}: {e}")).into_response(),
+        }
+    };
+
+    if target == "specials" {
+        if let Some(items) = root["products"]["items"].as_array_mut() {
+            items.retain(|x| x["price"]["isSpecial"] == true);
+            root["products"]["totalItems"] = items.len().into();
+        }
+    }
+
+    let page = query.get("page").and_then(|x| x.parse::<usize>().ok()).unwrap_or(1).max(1);
+    let mut size = query.get("size").and_then(|x| x.parse::<usize>().ok()).unwrap_or(24);
+    if let Some(page_size) = state.options.page_size {
+        size = size.min(page_size);
+    }
+
+    // Serve one page of the fixture's items, like the real API
+    if let Some(items) = root["products"]["items"].as_array_mut() {
+        let page_items = items
+            .iter()
+            .skip((page - 1) * size)
+            .take(size)
+            .cloned()
+            .collect::<Vec<Value>>();
+        *items = page_items;
+    }
+
+    Json(root).into_response()
+}
+
+pub fn router(state: Arc<MockState>) -> Router {
+    Router::new()
+        .route("/api/v1/products", get(products))
+        .route("/api/v1/products/:sku", get(product_detail))
+        .with_state(state)
+}
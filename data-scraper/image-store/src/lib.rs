@@ -0,0 +1,64 @@
+//! Where mirrored product images are kept, shared by the scraper that writes them
+//! and the web app that serves them. Images are keyed by the SHA-256 of the
+//! downloaded file, so the same picture behind two URLs is only stored once.
+
+use std::{fs, sync::Arc};
+
+use object_store::{aws::AmazonS3Builder, local::LocalFileSystem, path::Path, ObjectStore, PutPayload};
+
+pub use object_store::Error;
+
+/// Widths and heights, in pixels, that every image is shrunk to fit
+pub const THUMBNAIL_SIZES: [u32; 2] = [160, 480];
+
+/// Where the thumbnail of an image at one of `THUMBNAIL_SIZES` lives
+pub fn thumbnail_key(hash: &str, size: u32) -> String {
+    format!("thumbnails/{size}/{hash}.jpg")
+}
+
+/// Where the image as it was downloaded lives, kept so thumbnails can be made again
+/// without going back to the supermarket
+pub fn original_key(hash: &str, extension: &str) -> String {
+    format!("originals/{hash}.{extension}")
+}
+
+#[derive(Clone, Debug)]
+pub struct ImageStore {
+    store: Arc<dyn ObjectStore>,
+}
+
+impl ImageStore {
+    /// `location` is either a local directory, created if it's missing, or an
+    /// `s3://bucket/prefix` URL. S3 credentials, region and endpoint come from the
+    /// usual `AWS_` environment variables, e.g. `AWS_ENDPOINT` for MinIO or R2.
+    pub fn open(location: &str) -> Result<Self, Error> {
+        let store: Arc<dyn ObjectStore> = match location.starts_with("s3://") {
+            true => Arc::new(AmazonS3Builder::from_env().with_url(location).build()?),
+            false => {
+                fs::create_dir_all(location).map_err(|e| Error::Generic {
+                    store: "LocalFileSystem",
+                    source: Box::new(e),
+                })?;
+                Arc::new(LocalFileSystem::new_with_prefix(location)?)
+            }
+        };
+
+        Ok(ImageStore { store })
+    }
+
+    pub async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), Error> {
+        self.store.put(&Path::from(key), PutPayload::from(bytes)).await?;
+        Ok(())
+    }
+
+    /// The stored file, or `None` if there's nothing at `key`
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let result = match self.store.get(&Path::from(key)).await {
+            Ok(result) => result,
+            Err(Error::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Some(result.bytes().await?.to_vec()))
+    }
+}
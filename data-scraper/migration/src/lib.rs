@@ -1,12 +1,42 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
+mod m20240115_000001_add_product_department;
+mod m20240122_000001_create_price_index;
+mod m20240129_000001_create_shopping_list;
+mod m20240205_000001_create_users;
+mod m20240212_000001_create_scrape_run;
+mod m20240219_000001_create_schema_drift;
+mod m20240226_000001_add_price_quality;
+mod m20240304_000001_create_product_revision;
+mod m20240311_000001_harden_schema;
+mod m20240318_000001_partition_prices;
+mod m20240325_000001_create_product_detail;
+mod m20240401_000001_create_product_image;
+mod m20240408_000001_login_token_email;
+mod m20240415_000001_product_updated_at;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20240115_000001_add_product_department::Migration),
+            Box::new(m20240122_000001_create_price_index::Migration),
+            Box::new(m20240129_000001_create_shopping_list::Migration),
+            Box::new(m20240205_000001_create_users::Migration),
+            Box::new(m20240212_000001_create_scrape_run::Migration),
+            Box::new(m20240219_000001_create_schema_drift::Migration),
+            Box::new(m20240226_000001_add_price_quality::Migration),
+            Box::new(m20240304_000001_create_product_revision::Migration),
+            Box::new(m20240311_000001_harden_schema::Migration),
+            Box::new(m20240318_000001_partition_prices::Migration),
+            Box::new(m20240325_000001_create_product_detail::Migration),
+            Box::new(m20240401_000001_create_product_image::Migration),
+            Box::new(m20240408_000001_login_token_email::Migration),
+            Box::new(m20240415_000001_product_updated_at::Migration),
+        ]
     }
 }
@@ -0,0 +1,107 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PriceIndex::PriceIndex)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PriceIndex::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(PriceIndex::PeriodType)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PriceIndex::PeriodStart)
+                            .date()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PriceIndex::Department)
+                            .string()
+                    )
+                    .col(
+                        ColumnDef::new(PriceIndex::BrandName)
+                            .string()
+                    )
+                    .col(
+                        ColumnDef::new(PriceIndex::Laspeyres)
+                            .double()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PriceIndex::Jevons)
+                            .double()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PriceIndex::Fisher)
+                            .double()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PriceIndex::BasketSize)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PriceIndex::MatchedItems)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PriceIndex::ComputedAt)
+                            .date_time()
+                            .default(Expr::current_timestamp())
+                            .not_null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_PriceIndex_Period")
+                    .table(PriceIndex::PriceIndex)
+                    .col(PriceIndex::PeriodType)
+                    .col(PriceIndex::PeriodStart)
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PriceIndex::PriceIndex).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum PriceIndex {
+    PriceIndex,
+    Id,
+    PeriodType,
+    PeriodStart,
+    Department,
+    BrandName,
+    Laspeyres,
+    Jevons,
+    Fisher,
+    BasketSize,
+    MatchedItems,
+    ComputedAt
+}
@@ -0,0 +1,180 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SupermarketPrice::SupermarketPrice)
+                    .add_column(
+                        ColumnDef::new(SupermarketPrice::MultibuyQuantity)
+                            .integer()
+                    )
+                    .to_owned()
+            ).await?;
+
+        // One column per statement, SQLite can't alter more at once
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SupermarketPrice::SupermarketPrice)
+                    .add_column(
+                        ColumnDef::new(SupermarketPrice::MultibuyPrice)
+                            .float()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ShoppingList::ShoppingList)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ShoppingList::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ShoppingList::Owner)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShoppingList::Name)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShoppingList::CreatedAt)
+                            .date_time()
+                            .default(Expr::current_timestamp())
+                            .not_null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_ShoppingList_Owner")
+                    .table(ShoppingList::ShoppingList)
+                    .col(ShoppingList::Owner)
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ShoppingListItem::ShoppingListItem)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ShoppingListItem::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ShoppingListItem::ListId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_ShoppingListItem_ListId")
+                            .from(ShoppingListItem::ShoppingListItem, ShoppingListItem::ListId)
+                            .to(ShoppingList::ShoppingList, ShoppingList::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(
+                        ColumnDef::new(ShoppingListItem::ProductId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_ShoppingListItem_ProductId")
+                            .from(ShoppingListItem::ShoppingListItem, ShoppingListItem::ProductId)
+                            .to(ProductDB::ProductDB, ProductDB::ProductID)
+                    )
+                    .col(
+                        ColumnDef::new(ShoppingListItem::Quantity)
+                            .integer()
+                            .not_null()
+                            .default(1)
+                    )
+                    .col(
+                        ColumnDef::new(ShoppingListItem::AllowSubstitute)
+                            .boolean()
+                            .not_null()
+                            .default(true)
+                    )
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ShoppingListItem::ShoppingListItem).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(ShoppingList::ShoppingList).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SupermarketPrice::SupermarketPrice)
+                    .drop_column(SupermarketPrice::MultibuyQuantity)
+                    .to_owned()
+            ).await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SupermarketPrice::SupermarketPrice)
+                    .drop_column(SupermarketPrice::MultibuyPrice)
+                    .to_owned()
+            ).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SupermarketPrice {
+    SupermarketPrice,
+    MultibuyQuantity,
+    MultibuyPrice
+}
+
+#[derive(DeriveIden)]
+enum ShoppingList {
+    ShoppingList,
+    Id,
+    Owner,
+    Name,
+    CreatedAt
+}
+
+#[derive(DeriveIden)]
+enum ShoppingListItem {
+    ShoppingListItem,
+    Id,
+    ListId,
+    ProductId,
+    Quantity,
+    AllowSubstitute
+}
+
+#[derive(DeriveIden)]
+enum ProductDB {
+    ProductDB,
+    ProductID
+}
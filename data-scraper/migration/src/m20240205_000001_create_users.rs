@@ -0,0 +1,268 @@
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Users::Users)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Users::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Users::Email)
+                            .string()
+                            .not_null()
+                            .unique_key()
+                    )
+                    .col(
+                        ColumnDef::new(Users::PasswordHash)
+                            .string()
+                    )
+                    .col(
+                        ColumnDef::new(Users::CreatedAt)
+                            .date_time()
+                            .default(Expr::current_timestamp())
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Users::LastVisitAt)
+                            .date_time()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserSession::UserSession)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UserSession::Token)
+                            .string()
+                            .not_null()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(UserSession::UserId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_UserSession_UserId")
+                            .from(UserSession::UserSession, UserSession::UserId)
+                            .to(Users::Users, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(
+                        ColumnDef::new(UserSession::CreatedAt)
+                            .date_time()
+                            .default(Expr::current_timestamp())
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(UserSession::ExpiresAt)
+                            .date_time()
+                            .not_null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(LoginToken::LoginToken)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LoginToken::Token)
+                            .string()
+                            .not_null()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(LoginToken::UserId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_LoginToken_UserId")
+                            .from(LoginToken::LoginToken, LoginToken::UserId)
+                            .to(Users::Users, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(
+                        ColumnDef::new(LoginToken::ExpiresAt)
+                            .date_time()
+                            .not_null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Watchlist::Watchlist)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Watchlist::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Watchlist::UserId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_Watchlist_UserId")
+                            .from(Watchlist::Watchlist, Watchlist::UserId)
+                            .to(Users::Users, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(
+                        ColumnDef::new(Watchlist::ProductId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_Watchlist_ProductId")
+                            .from(Watchlist::Watchlist, Watchlist::ProductId)
+                            .to(ProductDB::ProductDB, ProductDB::ProductID)
+                    )
+                    .col(
+                        ColumnDef::new(Watchlist::CreatedAt)
+                            .date_time()
+                            .default(Expr::current_timestamp())
+                            .not_null()
+                    )
+                    .index(
+                        Index::create()
+                            .name("IDX_Watchlist_UserProduct")
+                            .col(Watchlist::UserId)
+                            .col(Watchlist::ProductId)
+                            .unique()
+                    )
+                    .to_owned()
+            ).await?;
+
+        let mut add_user = Table::alter()
+            .table(ShoppingList::ShoppingList)
+            .add_column(
+                ColumnDef::new(ShoppingList::UserId)
+                    .integer()
+            )
+            .to_owned();
+
+        // SQLite can't add a foreign key to a table that already exists
+        if manager.get_database_backend() != DbBackend::Sqlite {
+            add_user.add_foreign_key(
+                TableForeignKey::new()
+                    .name("FK_ShoppingList_UserId")
+                    .from_tbl(ShoppingList::ShoppingList)
+                    .from_col(ShoppingList::UserId)
+                    .to_tbl(Users::Users)
+                    .to_col(Users::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+            );
+        }
+
+        manager.alter_table(add_user).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::Sqlite {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(ShoppingList::ShoppingList)
+                        .drop_foreign_key(Alias::new("FK_ShoppingList_UserId"))
+                        .to_owned()
+                ).await?;
+        }
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ShoppingList::ShoppingList)
+                    .drop_column(ShoppingList::UserId)
+                    .to_owned()
+            ).await?;
+        manager
+            .drop_table(Table::drop().table(Watchlist::Watchlist).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(LoginToken::LoginToken).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(UserSession::UserSession).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Users::Users).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Users,
+    Id,
+    Email,
+    PasswordHash,
+    CreatedAt,
+    LastVisitAt
+}
+
+#[derive(DeriveIden)]
+enum UserSession {
+    UserSession,
+    Token,
+    UserId,
+    CreatedAt,
+    ExpiresAt
+}
+
+#[derive(DeriveIden)]
+enum LoginToken {
+    LoginToken,
+    Token,
+    UserId,
+    ExpiresAt
+}
+
+#[derive(DeriveIden)]
+enum Watchlist {
+    Watchlist,
+    Id,
+    UserId,
+    ProductId,
+    CreatedAt
+}
+
+#[derive(DeriveIden)]
+enum ShoppingList {
+    ShoppingList,
+    UserId
+}
+
+#[derive(DeriveIden)]
+enum ProductDB {
+    ProductDB,
+    ProductID
+}
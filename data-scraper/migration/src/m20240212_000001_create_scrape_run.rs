@@ -0,0 +1,161 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ScrapeRun::ScrapeRun)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ScrapeRun::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRun::SupermarketId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_ScrapeRun_SupermarketId")
+                            .from(ScrapeRun::ScrapeRun, ScrapeRun::SupermarketId)
+                            .to(Supermarkets::Supermarkets, Supermarkets::SupermarketId)
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRun::Status)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRun::ProductsFetched)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRun::StartedAt)
+                            .date_time()
+                            .default(Expr::current_timestamp())
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRun::FinishedAt)
+                            .date_time()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_ScrapeRun_SupermarketStarted")
+                    .table(ScrapeRun::ScrapeRun)
+                    .col(ScrapeRun::SupermarketId)
+                    .col(ScrapeRun::StartedAt)
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ScrapeRunError::ScrapeRunError)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ScrapeRunError::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRunError::RunId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_ScrapeRunError_RunId")
+                            .from(ScrapeRunError::ScrapeRunError, ScrapeRunError::RunId)
+                            .to(ScrapeRun::ScrapeRun, ScrapeRun::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRunError::Kind)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRunError::Fatal)
+                            .boolean()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRunError::Department)
+                            .string()
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRunError::Page)
+                            .integer()
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRunError::Url)
+                            .string()
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRunError::Message)
+                            .text()
+                            .not_null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ScrapeRunError::ScrapeRunError).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(ScrapeRun::ScrapeRun).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Supermarkets {
+    Supermarkets,
+    SupermarketId
+}
+
+#[derive(DeriveIden)]
+enum ScrapeRun {
+    ScrapeRun,
+    Id,
+    SupermarketId,
+    Status,
+    ProductsFetched,
+    StartedAt,
+    FinishedAt
+}
+
+#[derive(DeriveIden)]
+enum ScrapeRunError {
+    ScrapeRunError,
+    Id,
+    RunId,
+    Kind,
+    Fatal,
+    Department,
+    Page,
+    Url,
+    Message
+}
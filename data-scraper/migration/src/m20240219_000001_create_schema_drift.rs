@@ -0,0 +1,145 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ScrapeRunDrift::ScrapeRunDrift)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ScrapeRunDrift::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRunDrift::RunId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_ScrapeRunDrift_RunId")
+                            .from(ScrapeRunDrift::ScrapeRunDrift, ScrapeRunDrift::RunId)
+                            .to(ScrapeRun::ScrapeRun, ScrapeRun::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRunDrift::Field)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRunDrift::Change)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRunDrift::Occurrences)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ScrapeRunDrift::Example)
+                            .text()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(QuarantinedProduct::QuarantinedProduct)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(QuarantinedProduct::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(QuarantinedProduct::RunId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_QuarantinedProduct_RunId")
+                            .from(QuarantinedProduct::QuarantinedProduct, QuarantinedProduct::RunId)
+                            .to(ScrapeRun::ScrapeRun, ScrapeRun::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(
+                        ColumnDef::new(QuarantinedProduct::Department)
+                            .string()
+                    )
+                    .col(
+                        ColumnDef::new(QuarantinedProduct::Page)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(QuarantinedProduct::Sku)
+                            .string()
+                    )
+                    .col(
+                        ColumnDef::new(QuarantinedProduct::Error)
+                            .text()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(QuarantinedProduct::Raw)
+                            .text()
+                            .not_null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(QuarantinedProduct::QuarantinedProduct).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(ScrapeRunDrift::ScrapeRunDrift).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ScrapeRun {
+    ScrapeRun,
+    Id
+}
+
+#[derive(DeriveIden)]
+enum ScrapeRunDrift {
+    ScrapeRunDrift,
+    Id,
+    RunId,
+    Field,
+    Change,
+    Occurrences,
+    Example
+}
+
+#[derive(DeriveIden)]
+enum QuarantinedProduct {
+    QuarantinedProduct,
+    Id,
+    RunId,
+    Department,
+    Page,
+    Sku,
+    Error,
+    Raw
+}
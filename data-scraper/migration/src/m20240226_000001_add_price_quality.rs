@@ -0,0 +1,155 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SupermarketPrice::SupermarketPrice)
+                    .add_column(
+                        ColumnDef::new(SupermarketPrice::Flagged)
+                            .boolean()
+                            .not_null()
+                            .default(false)
+                    )
+                    .to_owned()
+            ).await?;
+
+        // One column per statement, SQLite can't alter more at once
+        for column in [ScrapeRun::PricesAccepted, ScrapeRun::PricesFlagged, ScrapeRun::PricesQuarantined] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(ScrapeRun::ScrapeRun)
+                        .add_column(
+                            ColumnDef::new(column)
+                                .integer()
+                                .not_null()
+                                .default(0)
+                        )
+                        .to_owned()
+                ).await?;
+        }
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(PriceAnomaly::PriceAnomaly)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PriceAnomaly::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(PriceAnomaly::RunId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_PriceAnomaly_RunId")
+                            .from(PriceAnomaly::PriceAnomaly, PriceAnomaly::RunId)
+                            .to(ScrapeRun::ScrapeRun, ScrapeRun::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(
+                        ColumnDef::new(PriceAnomaly::ProductId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_PriceAnomaly_ProductId")
+                            .from(PriceAnomaly::PriceAnomaly, PriceAnomaly::ProductId)
+                            .to(ProductDB::ProductDB, ProductDB::ProductId)
+                    )
+                    .col(
+                        ColumnDef::new(PriceAnomaly::Price)
+                            .float()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PriceAnomaly::ReferencePrice)
+                            .float()
+                    )
+                    .col(
+                        ColumnDef::new(PriceAnomaly::Reason)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PriceAnomaly::Quarantined)
+                            .boolean()
+                            .not_null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PriceAnomaly::PriceAnomaly).to_owned())
+            .await?;
+
+        for column in [ScrapeRun::PricesAccepted, ScrapeRun::PricesFlagged, ScrapeRun::PricesQuarantined] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(ScrapeRun::ScrapeRun)
+                        .drop_column(column)
+                        .to_owned()
+                ).await?;
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SupermarketPrice::SupermarketPrice)
+                    .drop_column(SupermarketPrice::Flagged)
+                    .to_owned()
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum SupermarketPrice {
+    SupermarketPrice,
+    Flagged
+}
+
+#[derive(DeriveIden)]
+enum ProductDB {
+    ProductDB,
+    ProductId
+}
+
+#[derive(DeriveIden)]
+enum ScrapeRun {
+    ScrapeRun,
+    Id,
+    PricesAccepted,
+    PricesFlagged,
+    PricesQuarantined
+}
+
+#[derive(DeriveIden)]
+enum PriceAnomaly {
+    PriceAnomaly,
+    Id,
+    RunId,
+    ProductId,
+    Price,
+    ReferencePrice,
+    Reason,
+    Quarantined
+}
@@ -0,0 +1,130 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProductRevision::ProductRevision)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProductRevision::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ProductRevision::ProductId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_ProductRevision_ProductId")
+                            .from(ProductRevision::ProductRevision, ProductRevision::ProductId)
+                            .to(ProductDB::ProductDB, ProductDB::ProductId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(
+                        ColumnDef::new(ProductRevision::RevisedAt)
+                            .date_time()
+                            .default(Expr::current_timestamp())
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductRevision::ProductTitle)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductRevision::ImageUrl)
+                            .string()
+                    )
+                    .col(
+                        ColumnDef::new(ProductRevision::Size)
+                            .float()
+                    )
+                    .col(
+                        ColumnDef::new(ProductRevision::Unit)
+                            .string()
+                    )
+                    .col(
+                        ColumnDef::new(ProductRevision::Quantity)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductRevision::Price)
+                            .float()
+                    )
+                    .col(
+                        ColumnDef::new(ProductRevision::Changes)
+                            .string()
+                    )
+                    .col(
+                        ColumnDef::new(ProductRevision::Shrinkflation)
+                            .boolean()
+                            .not_null()
+                            .default(false)
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_ProductRevision_ProductRevised")
+                    .table(ProductRevision::ProductRevision)
+                    .col(ProductRevision::ProductId)
+                    .col(ProductRevision::RevisedAt)
+                    .to_owned()
+            ).await?;
+
+        // Every existing product starts with its first-seen details and first price
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                INSERT INTO product_revision (product_id, revised_at, product_title, image_url, size, unit, quantity, price)
+                SELECT p.product_id, p.first_index_timestamp, p.product_title, p.image_url, p.size, p.unit, p.quantity,
+                    (SELECT sp.price FROM supermarket_price sp WHERE sp.product_id = p.product_id ORDER BY sp.timestamp LIMIT 1)
+                FROM product_db p
+                "#
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ProductRevision::ProductRevision).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ProductDB {
+    ProductDB,
+    ProductId
+}
+
+#[derive(DeriveIden)]
+enum ProductRevision {
+    ProductRevision,
+    Id,
+    ProductId,
+    RevisedAt,
+    ProductTitle,
+    ImageUrl,
+    Size,
+    Unit,
+    Quantity,
+    Price,
+    Changes,
+    Shrinkflation
+}
@@ -0,0 +1,267 @@
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+/// Money columns, stored exactly to the cent instead of as `real`
+const MONEY_COLUMNS: [(&str, &str); 6] = [
+    ("supermarket_price", "price"),
+    ("supermarket_price", "original_price"),
+    ("supermarket_price", "multibuy_price"),
+    ("price_anomaly", "price"),
+    ("price_anomaly", "reference_price"),
+    ("product_revision", "price"),
+];
+
+/// Timestamp columns. Everything so far has been written in UTC.
+const TIMESTAMP_COLUMNS: [(&str, &str); 13] = [
+    ("login_token", "expires_at"),
+    ("price_index", "computed_at"),
+    ("product_db", "first_index_timestamp"),
+    ("product_revision", "revised_at"),
+    ("scrape_run", "started_at"),
+    ("scrape_run", "finished_at"),
+    ("shopping_list", "created_at"),
+    ("supermarket_price", "timestamp"),
+    ("user_session", "created_at"),
+    ("user_session", "expires_at"),
+    ("users", "created_at"),
+    ("users", "last_visit_at"),
+    ("watchlist", "created_at"),
+];
+
+/// Stores each product's barcode in the form it's matched by, merges duplicate
+/// products and supermarkets and drops repeated price index rows so they can be made
+/// unique, adds the indexes the scraper and web app query by, and moves money to
+/// `numeric` and timestamps to `timestamptz`. Going down restores the old types and
+/// drops the indexes and normalized barcodes, but merged duplicates stay merged.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // SQLite databases are only ever new, so there's nothing to merge, and SQLite
+        // doesn't have column types to tighten
+        let postgres = manager.get_database_backend() == DbBackend::Postgres;
+
+        // Written by the scraper with `barcode::normalize`, so spellings of one GTIN
+        // with and without leading zeros are the same product
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ProductDB::ProductDB)
+                    .add_column(ColumnDef::new(ProductDB::NormalizedBarcode).string().null())
+                    .to_owned()
+            ).await?;
+
+        if postgres {
+            // Every reference to a duplicate product is moved to the oldest copy. Products
+            // without a barcode were stored with an empty one, which isn't a barcode. The
+            // normalizing has to match `barcode::normalize`.
+            db.execute_unprepared(
+                r#"
+                UPDATE product_db SET barcode = NULL WHERE trim(barcode) = '';
+
+                UPDATE product_db SET normalized_barcode = CASE
+                    WHEN regexp_replace(barcode, '[[:space:]-]', '', 'g') ~ '^[0-9]{1,14}$'
+                        THEN nullif(ltrim(regexp_replace(barcode, '[[:space:]-]', '', 'g'), '0'), '')
+                    ELSE regexp_replace(barcode, '^[[:space:]]+|[[:space:]]+$', '', 'g')
+                END;
+
+                CREATE TEMPORARY TABLE product_merge AS
+                    SELECT product_id, min(product_id) OVER (PARTITION BY normalized_barcode) AS keep_id
+                    FROM product_db
+                    WHERE normalized_barcode IS NOT NULL;
+                DELETE FROM product_merge WHERE product_id = keep_id;
+
+                UPDATE supermarket_price t SET product_id = m.keep_id FROM product_merge m WHERE t.product_id = m.product_id;
+                UPDATE shopping_list_item t SET product_id = m.keep_id FROM product_merge m WHERE t.product_id = m.product_id;
+                UPDATE price_anomaly t SET product_id = m.keep_id FROM product_merge m WHERE t.product_id = m.product_id;
+                UPDATE product_revision t SET product_id = m.keep_id FROM product_merge m WHERE t.product_id = m.product_id;
+                DELETE FROM watchlist t USING product_merge m
+                    WHERE t.product_id = m.product_id
+                        AND EXISTS (SELECT 1 FROM watchlist k WHERE k.user_id = t.user_id AND k.product_id = m.keep_id);
+                UPDATE watchlist t SET product_id = m.keep_id FROM product_merge m WHERE t.product_id = m.product_id;
+                DELETE FROM product_db t USING product_merge m WHERE t.product_id = m.product_id;
+
+                DROP TABLE product_merge;
+
+                CREATE TEMPORARY TABLE supermarket_merge AS
+                    SELECT supermarket_id, min(supermarket_id) OVER (PARTITION BY brand_name, location_id) AS keep_id
+                    FROM supermarkets;
+                DELETE FROM supermarket_merge WHERE supermarket_id = keep_id;
+
+                UPDATE supermarket_price t SET supermarket_id = m.keep_id FROM supermarket_merge m WHERE t.supermarket_id = m.supermarket_id;
+                UPDATE scrape_run t SET supermarket_id = m.keep_id FROM supermarket_merge m WHERE t.supermarket_id = m.supermarket_id;
+                DELETE FROM supermarkets t USING supermarket_merge m WHERE t.supermarket_id = m.supermarket_id;
+
+                DROP TABLE supermarket_merge;
+                "#
+            ).await?;
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_ProductDB_NormalizedBarcode")
+                    .table(ProductDB::ProductDB)
+                    .col(ProductDB::NormalizedBarcode)
+                    .unique()
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_ProductDB_Department")
+                    .table(ProductDB::ProductDB)
+                    .col(ProductDB::Department)
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_Supermarkets_BrandLocation")
+                    .table(Supermarkets::Supermarkets)
+                    .col(Supermarkets::BrandName)
+                    .col(Supermarkets::LocationId)
+                    .unique()
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_SupermarketPrice_ProductTimestamp")
+                    .table(SupermarketPrice::SupermarketPrice)
+                    .col(SupermarketPrice::ProductId)
+                    .col(SupermarketPrice::Timestamp)
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_SupermarketPrice_SupermarketTimestamp")
+                    .table(SupermarketPrice::SupermarketPrice)
+                    .col(SupermarketPrice::SupermarketId)
+                    .col(SupermarketPrice::Timestamp)
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_ShoppingListItem_List")
+                    .table(ShoppingListItem::ShoppingListItem)
+                    .col(ShoppingListItem::ListId)
+                    .to_owned()
+            ).await?;
+
+        // A scope is all departments or chains when they're null, so nulls have to
+        // clash with each other like any other value
+        if postgres {
+            db.execute_unprepared(
+                r#"
+                DELETE FROM price_index t USING price_index k
+                    WHERE k.period_type = t.period_type
+                        AND k.period_start = t.period_start
+                        AND k.department IS NOT DISTINCT FROM t.department
+                        AND k.brand_name IS NOT DISTINCT FROM t.brand_name
+                        AND k.id > t.id;
+                "#
+            ).await?;
+        }
+        db.execute_unprepared(
+            r#"CREATE UNIQUE INDEX "IDX_PriceIndex_Scope" ON price_index (period_type, period_start, COALESCE(department, ''), COALESCE(brand_name, ''))"#
+        ).await?;
+
+        if postgres {
+            for (table, column) in MONEY_COLUMNS {
+                db.execute_unprepared(&format!(
+                    r#"ALTER TABLE "{table}" ALTER COLUMN "{column}" TYPE numeric(12, 2) USING round("{column}"::numeric, 2)"#
+                )).await?;
+            }
+
+            for (table, column) in TIMESTAMP_COLUMNS {
+                db.execute_unprepared(&format!(
+                    r#"ALTER TABLE "{table}" ALTER COLUMN "{column}" TYPE timestamptz USING "{column}" AT TIME ZONE 'UTC'"#
+                )).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let postgres = manager.get_database_backend() == DbBackend::Postgres;
+
+        if postgres {
+            for (table, column) in TIMESTAMP_COLUMNS {
+                db.execute_unprepared(&format!(
+                    r#"ALTER TABLE "{table}" ALTER COLUMN "{column}" TYPE timestamp USING "{column}" AT TIME ZONE 'UTC'"#
+                )).await?;
+            }
+
+            for (table, column) in MONEY_COLUMNS {
+                db.execute_unprepared(&format!(
+                    r#"ALTER TABLE "{table}" ALTER COLUMN "{column}" TYPE real"#
+                )).await?;
+            }
+        }
+
+        for name in [
+            "IDX_PriceIndex_Scope",
+            "IDX_ShoppingListItem_List",
+            "IDX_SupermarketPrice_SupermarketTimestamp",
+            "IDX_SupermarketPrice_ProductTimestamp",
+            "IDX_Supermarkets_BrandLocation",
+            "IDX_ProductDB_Department",
+            "IDX_ProductDB_NormalizedBarcode",
+        ] {
+            manager
+                .drop_index(Index::drop().name(name).to_owned())
+                .await?;
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ProductDB::ProductDB)
+                    .drop_column(ProductDB::NormalizedBarcode)
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ProductDB {
+    ProductDB,
+    NormalizedBarcode,
+    Department
+}
+
+#[derive(DeriveIden)]
+enum Supermarkets {
+    Supermarkets,
+    BrandName,
+    LocationId
+}
+
+#[derive(DeriveIden)]
+enum SupermarketPrice {
+    SupermarketPrice,
+    ProductId,
+    SupermarketId,
+    Timestamp
+}
+
+#[derive(DeriveIden)]
+enum ShoppingListItem {
+    ShoppingListItem,
+    ListId
+}
@@ -0,0 +1,372 @@
+use sea_orm_migration::{prelude::*, sea_orm::{ConnectionTrait, DbBackend, Statement}};
+
+/// Months of partitions made ahead of the newest price, so the scraper has
+/// somewhere to write until it next adds partitions itself
+const MONTHS_AHEAD: i32 = 2;
+
+/// Splits `supermarket_price` up by time and adds the daily and weekly price
+/// aggregates (`price_daily`, `price_weekly`) that charts and the price index read
+/// instead of the raw rows.
+///
+/// If the server has TimescaleDB loaded the table becomes a hypertable and the
+/// aggregates are continuous aggregates. Otherwise it becomes a natively
+/// partitioned table with one partition per month, and the aggregates are tables
+/// kept up to date by `refresh_price_aggregates()`. Either way the partitioning
+/// column has to be part of the primary key, so it becomes (id, timestamp).
+///
+/// SQLite just gets the aggregate tables, which the scraper fills itself after
+/// each scrape.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return create_aggregate_tables(manager).await;
+        }
+
+        let timescale = db
+            .query_one(Statement::from_string(
+                DbBackend::Postgres,
+                r#"
+                SELECT EXISTS (SELECT 1 FROM pg_available_extensions WHERE name = 'timescaledb')
+                    AND current_setting('shared_preload_libraries') LIKE '%timescaledb%' AS available
+                "#,
+            ))
+            .await?
+            .map(|x| x.try_get::<bool>("", "available"))
+            .transpose()?
+            .unwrap_or(false);
+
+        match timescale {
+            true => create_hypertable(manager).await,
+            false => create_partitions(manager).await,
+        }
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            manager
+                .drop_table(Table::drop().table(PriceWeekly::PriceWeekly).to_owned())
+                .await?;
+            return manager
+                .drop_table(Table::drop().table(PriceDaily::PriceDaily).to_owned())
+                .await;
+        }
+
+        // Continuous aggregates are views, the native ones are tables
+        db.execute_unprepared(
+            r#"
+            DO $$
+            BEGIN
+                IF EXISTS (SELECT 1 FROM pg_class WHERE relname = 'price_daily' AND relkind = 'v') THEN
+                    DROP MATERIALIZED VIEW price_weekly;
+                    DROP MATERIALIZED VIEW price_daily;
+                ELSE
+                    DROP TABLE price_weekly;
+                    DROP TABLE price_daily;
+                END IF;
+            END $$;
+
+            DROP FUNCTION IF EXISTS refresh_price_aggregates();
+            DROP FUNCTION IF EXISTS add_price_partition(date);
+            "#
+        ).await?;
+
+        // Copy everything back into a plain table, which works the same for a
+        // hypertable or a partitioned table
+        db.execute_unprepared(
+            r#"
+            CREATE TABLE supermarket_price_plain (LIKE supermarket_price INCLUDING DEFAULTS);
+            INSERT INTO supermarket_price_plain SELECT * FROM supermarket_price;
+
+            ALTER SEQUENCE supermarket_price_id_seq OWNED BY NONE;
+            DROP TABLE supermarket_price CASCADE;
+            ALTER TABLE supermarket_price_plain RENAME TO supermarket_price;
+            ALTER SEQUENCE supermarket_price_id_seq OWNED BY supermarket_price.id;
+
+            ALTER TABLE supermarket_price ADD CONSTRAINT supermarket_price_pkey PRIMARY KEY (id);
+            "#
+        ).await?;
+
+        add_keys_and_indexes(manager).await
+    }
+}
+
+async fn create_hypertable(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+
+    // Continuous aggregates are made empty because filling them can't happen inside
+    // the migration's transaction. The scraper refreshes them after each run, and
+    // the policies catch anything it misses.
+    db.execute_unprepared(
+        r#"
+        CREATE EXTENSION IF NOT EXISTS timescaledb;
+
+        ALTER TABLE supermarket_price DROP CONSTRAINT supermarket_price_pkey;
+        ALTER TABLE supermarket_price ADD CONSTRAINT supermarket_price_pkey PRIMARY KEY (id, "timestamp");
+        SELECT create_hypertable('supermarket_price', 'timestamp', chunk_time_interval => INTERVAL '1 month', migrate_data => true);
+
+        CREATE MATERIALIZED VIEW price_daily WITH (timescaledb.continuous) AS
+            SELECT
+                product_id, supermarket_id, time_bucket(INTERVAL '1 day', "timestamp") AS bucket,
+                min(price) AS min_price, max(price) AS max_price, avg(price)::numeric(12, 2) AS avg_price,
+                last(price, "timestamp") AS close_price
+            FROM supermarket_price
+            WHERE NOT flagged AND price > 0
+            GROUP BY product_id, supermarket_id, bucket
+            WITH NO DATA;
+
+        CREATE MATERIALIZED VIEW price_weekly WITH (timescaledb.continuous) AS
+            SELECT
+                product_id, supermarket_id, time_bucket(INTERVAL '1 week', "timestamp") AS bucket,
+                min(price) AS min_price, max(price) AS max_price, avg(price)::numeric(12, 2) AS avg_price,
+                last(price, "timestamp") AS close_price
+            FROM supermarket_price
+            WHERE NOT flagged AND price > 0
+            GROUP BY product_id, supermarket_id, bucket
+            WITH NO DATA;
+
+        SELECT add_continuous_aggregate_policy('price_daily',
+            start_offset => INTERVAL '3 days', end_offset => NULL, schedule_interval => INTERVAL '1 hour');
+        SELECT add_continuous_aggregate_policy('price_weekly',
+            start_offset => INTERVAL '3 weeks', end_offset => NULL, schedule_interval => INTERVAL '1 hour');
+        "#
+    ).await?;
+
+    Ok(())
+}
+
+async fn create_partitions(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+
+    // Partitions are named by month, e.g. supermarket_price_2024_03. Rows outside
+    // every partition land in supermarket_price_default rather than failing.
+    db.execute_unprepared(&format!(
+        r#"
+        ALTER TABLE supermarket_price RENAME TO supermarket_price_plain;
+        ALTER TABLE supermarket_price_plain RENAME CONSTRAINT supermarket_price_pkey TO supermarket_price_plain_pkey;
+
+        CREATE TABLE supermarket_price (LIKE supermarket_price_plain INCLUDING DEFAULTS)
+            PARTITION BY RANGE ("timestamp");
+        ALTER TABLE supermarket_price ADD CONSTRAINT supermarket_price_pkey PRIMARY KEY (id, "timestamp");
+        CREATE TABLE supermarket_price_default PARTITION OF supermarket_price DEFAULT;
+
+        CREATE FUNCTION add_price_partition(month date) RETURNS void LANGUAGE plpgsql AS $$
+        DECLARE
+            start date := date_trunc('month', month);
+        BEGIN
+            EXECUTE format(
+                'CREATE TABLE IF NOT EXISTS %I PARTITION OF supermarket_price FOR VALUES FROM (%L) TO (%L)',
+                'supermarket_price_' || to_char(start, 'YYYY_MM'),
+                start::timestamp AT TIME ZONE 'UTC',
+                (start + INTERVAL '1 month')::timestamp AT TIME ZONE 'UTC'
+            );
+        END $$;
+
+        SELECT add_price_partition(month::date)
+        FROM generate_series(
+            (SELECT date_trunc('month', coalesce(min("timestamp"), now()) AT TIME ZONE 'UTC') FROM supermarket_price_plain),
+            (SELECT date_trunc('month', greatest(max("timestamp"), now()) AT TIME ZONE 'UTC') FROM supermarket_price_plain)
+                + INTERVAL '{MONTHS_AHEAD} months',
+            INTERVAL '1 month'
+        ) AS month;
+
+        INSERT INTO supermarket_price SELECT * FROM supermarket_price_plain;
+
+        ALTER SEQUENCE supermarket_price_id_seq OWNED BY NONE;
+        DROP TABLE supermarket_price_plain;
+        ALTER SEQUENCE supermarket_price_id_seq OWNED BY supermarket_price.id;
+        "#
+    )).await?;
+
+    add_keys_and_indexes(manager).await?;
+
+    create_aggregate_tables(manager).await?;
+
+    // Aggregates are recomputed from the start of the newest bucket they already
+    // have, so each refresh only reads the rows added since the last one
+    db.execute_unprepared(
+        r#"
+        CREATE FUNCTION refresh_price_aggregates() RETURNS void LANGUAGE plpgsql AS $$
+        DECLARE
+            since timestamptz;
+        BEGIN
+            SELECT coalesce(max(bucket), '-infinity') INTO since FROM price_daily;
+            INSERT INTO price_daily
+                SELECT
+                    product_id, supermarket_id, date_trunc('day', "timestamp", 'UTC') AS bucket,
+                    min(price), max(price), avg(price), (array_agg(price ORDER BY "timestamp" DESC))[1]
+                FROM supermarket_price
+                WHERE "timestamp" >= since AND NOT flagged AND price > 0
+                GROUP BY product_id, supermarket_id, bucket
+            ON CONFLICT (product_id, supermarket_id, bucket) DO UPDATE SET
+                min_price = excluded.min_price,
+                max_price = excluded.max_price,
+                avg_price = excluded.avg_price,
+                close_price = excluded.close_price;
+
+            SELECT coalesce(max(bucket), '-infinity') INTO since FROM price_weekly;
+            INSERT INTO price_weekly
+                SELECT
+                    product_id, supermarket_id, date_trunc('week', "timestamp", 'UTC') AS bucket,
+                    min(price), max(price), avg(price), (array_agg(price ORDER BY "timestamp" DESC))[1]
+                FROM supermarket_price
+                WHERE "timestamp" >= since AND NOT flagged AND price > 0
+                GROUP BY product_id, supermarket_id, bucket
+            ON CONFLICT (product_id, supermarket_id, bucket) DO UPDATE SET
+                min_price = excluded.min_price,
+                max_price = excluded.max_price,
+                avg_price = excluded.avg_price,
+                close_price = excluded.close_price;
+        END $$;
+
+        SELECT refresh_price_aggregates();
+        "#
+    ).await?;
+
+    Ok(())
+}
+
+async fn create_aggregate_tables(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    manager
+        .create_table(aggregate_table(PriceDaily::PriceDaily))
+        .await?;
+
+    manager
+        .create_table(aggregate_table(PriceWeekly::PriceWeekly))
+        .await
+}
+
+fn aggregate_table(table: impl IntoIden) -> TableCreateStatement {
+    let table = table.into_iden();
+
+    Table::create()
+        .table(table.clone())
+        .col(
+            ColumnDef::new(PriceAggregate::ProductId)
+                .integer()
+                .not_null()
+        )
+        .foreign_key(
+            ForeignKeyCreateStatement::new()
+                .from(table.clone(), PriceAggregate::ProductId)
+                .to(ProductDB::ProductDB, ProductDB::ProductId)
+        )
+        .col(
+            ColumnDef::new(PriceAggregate::SupermarketId)
+                .integer()
+                .not_null()
+        )
+        .foreign_key(
+            ForeignKeyCreateStatement::new()
+                .from(table, PriceAggregate::SupermarketId)
+                .to(Supermarkets::Supermarkets, Supermarkets::SupermarketId)
+        )
+        .col(
+            ColumnDef::new(PriceAggregate::Bucket)
+                .timestamp_with_time_zone()
+                .not_null()
+        )
+        .col(ColumnDef::new(PriceAggregate::MinPrice).decimal_len(12, 2).not_null())
+        .col(ColumnDef::new(PriceAggregate::MaxPrice).decimal_len(12, 2).not_null())
+        .col(ColumnDef::new(PriceAggregate::AvgPrice).decimal_len(12, 2).not_null())
+        .col(ColumnDef::new(PriceAggregate::ClosePrice).decimal_len(12, 2).not_null())
+        .primary_key(
+            Index::create()
+                .col(PriceAggregate::ProductId)
+                .col(PriceAggregate::SupermarketId)
+                .col(PriceAggregate::Bucket)
+        )
+        .to_owned()
+}
+
+/// Foreign keys and indexes that are lost when `supermarket_price` is rebuilt
+async fn add_keys_and_indexes(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    manager
+        .create_foreign_key(
+            ForeignKey::create()
+                .name("FK_SupermarketPrice_ProductId")
+                .from(SupermarketPrice::SupermarketPrice, SupermarketPrice::ProductId)
+                .to(ProductDB::ProductDB, ProductDB::ProductId)
+                .to_owned()
+        ).await?;
+
+    manager
+        .create_foreign_key(
+            ForeignKey::create()
+                .name("FK_SupermarketPrice_SupermarketId")
+                .from(SupermarketPrice::SupermarketPrice, SupermarketPrice::SupermarketId)
+                .to(Supermarkets::Supermarkets, Supermarkets::SupermarketId)
+                .to_owned()
+        ).await?;
+
+    manager
+        .create_index(
+            Index::create()
+                .name("IDX_SupermarketPrice_ProductTimestamp")
+                .table(SupermarketPrice::SupermarketPrice)
+                .col(SupermarketPrice::ProductId)
+                .col(SupermarketPrice::Timestamp)
+                .to_owned()
+        ).await?;
+
+    manager
+        .create_index(
+            Index::create()
+                .name("IDX_SupermarketPrice_SupermarketTimestamp")
+                .table(SupermarketPrice::SupermarketPrice)
+                .col(SupermarketPrice::SupermarketId)
+                .col(SupermarketPrice::Timestamp)
+                .to_owned()
+        ).await?;
+
+    Ok(())
+}
+
+#[derive(DeriveIden)]
+enum SupermarketPrice {
+    SupermarketPrice,
+    ProductId,
+    SupermarketId,
+    Timestamp,
+}
+
+#[derive(DeriveIden)]
+enum ProductDB {
+    ProductDB,
+    ProductId,
+}
+
+#[derive(DeriveIden)]
+enum Supermarkets {
+    Supermarkets,
+    SupermarketId,
+}
+
+#[derive(DeriveIden)]
+enum PriceDaily {
+    PriceDaily,
+}
+
+#[derive(DeriveIden)]
+enum PriceWeekly {
+    PriceWeekly,
+}
+
+/// Columns shared by `price_daily` and `price_weekly`
+#[derive(DeriveIden)]
+enum PriceAggregate {
+    ProductId,
+    SupermarketId,
+    Bucket,
+    MinPrice,
+    MaxPrice,
+    AvgPrice,
+    ClosePrice,
+}
@@ -0,0 +1,195 @@
+use sea_orm_migration::prelude::*;
+
+/// What a supermarket's product page says beyond the price: ingredients, nutrition
+/// and where it's from
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProductDetail::ProductDetail)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProductDetail::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ProductDetail::ProductId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_ProductDetail_ProductId")
+                            .from(ProductDetail::ProductDetail, ProductDetail::ProductId)
+                            .to(ProductDB::ProductDB, ProductDB::ProductId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(
+                        ColumnDef::new(ProductDetail::SupermarketId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_ProductDetail_SupermarketId")
+                            .from(ProductDetail::ProductDetail, ProductDetail::SupermarketId)
+                            .to(Supermarkets::Supermarkets, Supermarkets::SupermarketId)
+                    )
+                    .col(
+                        ColumnDef::new(ProductDetail::Sku)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductDetail::Description)
+                            .text()
+                    )
+                    .col(
+                        ColumnDef::new(ProductDetail::Ingredients)
+                            .text()
+                    )
+                    .col(
+                        ColumnDef::new(ProductDetail::Allergens)
+                            .text()
+                    )
+                    .col(
+                        ColumnDef::new(ProductDetail::CountryOfOrigin)
+                            .string()
+                    )
+                    .col(
+                        ColumnDef::new(ProductDetail::ServingSize)
+                            .string()
+                    )
+                    .col(
+                        ColumnDef::new(ProductDetail::ServingsPerPack)
+                            .string()
+                    )
+                    .col(
+                        ColumnDef::new(ProductDetail::FetchedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_ProductDetail_ProductSupermarket")
+                    .table(ProductDetail::ProductDetail)
+                    .col(ProductDetail::ProductId)
+                    .col(ProductDetail::SupermarketId)
+                    .unique()
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProductNutrition::ProductNutrition)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProductNutrition::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ProductNutrition::DetailId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_ProductNutrition_DetailId")
+                            .from(ProductNutrition::ProductNutrition, ProductNutrition::DetailId)
+                            .to(ProductDetail::ProductDetail, ProductDetail::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(
+                        ColumnDef::new(ProductNutrition::Nutrient)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductNutrition::PerServing)
+                            .string()
+                    )
+                    .col(
+                        ColumnDef::new(ProductNutrition::Per100)
+                            .string()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_ProductNutrition_Detail")
+                    .table(ProductNutrition::ProductNutrition)
+                    .col(ProductNutrition::DetailId)
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ProductNutrition::ProductNutrition).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(ProductDetail::ProductDetail).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ProductDB {
+    ProductDB,
+    ProductId
+}
+
+#[derive(DeriveIden)]
+enum Supermarkets {
+    Supermarkets,
+    SupermarketId
+}
+
+#[derive(DeriveIden)]
+enum ProductDetail {
+    ProductDetail,
+    Id,
+    ProductId,
+    SupermarketId,
+    Sku,
+    Description,
+    Ingredients,
+    Allergens,
+    CountryOfOrigin,
+    ServingSize,
+    ServingsPerPack,
+    FetchedAt
+}
+
+#[derive(DeriveIden)]
+enum ProductNutrition {
+    ProductNutrition,
+    Id,
+    DetailId,
+    Nutrient,
+    PerServing,
+    #[sea_orm(iden = "per_100")]
+    Per100
+}
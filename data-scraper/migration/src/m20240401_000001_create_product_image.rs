@@ -0,0 +1,138 @@
+use sea_orm_migration::prelude::*;
+
+/// Product images copied from the supermarkets' CDNs, one row per distinct file, and
+/// which of them each image URL turned out to be
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProductImage::ProductImage)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProductImage::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ProductImage::ContentHash)
+                            .string_len(64)
+                            .not_null()
+                            .unique_key()
+                    )
+                    .col(
+                        ColumnDef::new(ProductImage::Format)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductImage::Width)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductImage::Height)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductImage::ByteSize)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductImage::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProductImageSource::ProductImageSource)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProductImageSource::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ProductImageSource::Url)
+                            .text()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductImageSource::ImageId)
+                            .integer()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_ProductImageSource_ImageId")
+                            .from(ProductImageSource::ProductImageSource, ProductImageSource::ImageId)
+                            .to(ProductImage::ProductImage, ProductImage::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                    )
+                    .col(
+                        ColumnDef::new(ProductImageSource::FetchedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp())
+                            .not_null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_ProductImageSource_Url")
+                    .table(ProductImageSource::ProductImageSource)
+                    .col(ProductImageSource::Url)
+                    .unique()
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ProductImageSource::ProductImageSource).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(ProductImage::ProductImage).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ProductImage {
+    ProductImage,
+    Id,
+    ContentHash,
+    Format,
+    Width,
+    Height,
+    ByteSize,
+    CreatedAt
+}
+
+#[derive(DeriveIden)]
+enum ProductImageSource {
+    ProductImageSource,
+    Id,
+    Url,
+    ImageId,
+    FetchedAt
+}
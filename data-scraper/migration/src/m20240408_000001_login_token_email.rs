@@ -0,0 +1,90 @@
+use sea_orm_migration::prelude::*;
+
+/// Login links are issued for an email address rather than a user, so no account is
+/// created until someone proves they own the address by following one. Links only
+/// last a few minutes, so outstanding ones are dropped rather than carried over.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LoginToken::LoginToken).to_owned())
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(LoginToken::LoginToken)
+                    .col(
+                        ColumnDef::new(LoginToken::Token)
+                            .string()
+                            .not_null()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(LoginToken::Email)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(LoginToken::ExpiresAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                    )
+                    .to_owned()
+            ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LoginToken::LoginToken).to_owned())
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(LoginToken::LoginToken)
+                    .col(
+                        ColumnDef::new(LoginToken::Token)
+                            .string()
+                            .not_null()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(LoginToken::UserId)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("FK_LoginToken_UserId")
+                            .from(LoginToken::LoginToken, LoginToken::UserId)
+                            .to(Users::Users, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(
+                        ColumnDef::new(LoginToken::ExpiresAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                    )
+                    .to_owned()
+            ).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Users,
+    Id
+}
+
+#[derive(DeriveIden)]
+enum LoginToken {
+    LoginToken,
+    Token,
+    UserId,
+    Email,
+    ExpiresAt
+}
@@ -0,0 +1,55 @@
+use sea_orm_migration::prelude::*;
+
+/// When each product was last saved, so the scraper can reload just the products
+/// another process has changed instead of all of them. Existing products count as
+/// changed when the migration runs.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ProductDB::ProductDB)
+                    .add_column(
+                        ColumnDef::new(ProductDB::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("IDX_ProductDB_UpdatedAt")
+                    .table(ProductDB::ProductDB)
+                    .col(ProductDB::UpdatedAt)
+                    .to_owned()
+            ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("IDX_ProductDB_UpdatedAt").to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ProductDB::ProductDB)
+                    .drop_column(ProductDB::UpdatedAt)
+                    .to_owned()
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ProductDB {
+    ProductDB,
+    UpdatedAt
+}
@@ -0,0 +1,63 @@
+use sea_orm::{
+    prelude::Decimal,
+    sea_query::{ArrayType, ColumnType, Nullable, Value, ValueType, ValueTypeErr},
+    ColIdx, QueryResult, TryGetError, TryGetable,
+};
+
+use super::Money;
+
+// Stored as numeric(12, 2)
+
+impl From<Money> for Decimal {
+    fn from(money: Money) -> Decimal {
+        Decimal::new(money.cents(), 2)
+    }
+}
+
+impl From<Decimal> for Money {
+    /// Rounds to the cent
+    fn from(decimal: Decimal) -> Money {
+        let mut cents = decimal.round_dp(2);
+        cents.rescale(2);
+        Money::from_cents(cents.mantissa() as i64)
+    }
+}
+
+impl From<Money> for Value {
+    fn from(money: Money) -> Value {
+        Value::Decimal(Some(Box::new(money.into())))
+    }
+}
+
+impl Nullable for Money {
+    fn null() -> Value {
+        Value::Decimal(None)
+    }
+}
+
+impl ValueType for Money {
+    fn try_from(v: Value) -> Result<Self, ValueTypeErr> {
+        match v {
+            Value::Decimal(Some(x)) => Ok((*x).into()),
+            _ => Err(ValueTypeErr),
+        }
+    }
+
+    fn type_name() -> String {
+        "Money".to_owned()
+    }
+
+    fn array_type() -> ArrayType {
+        ArrayType::Decimal
+    }
+
+    fn column_type() -> ColumnType {
+        ColumnType::Decimal(Some((12, 2)))
+    }
+}
+
+impl TryGetable for Money {
+    fn try_get_by<I: ColIdx>(res: &QueryResult, index: I) -> Result<Self, TryGetError> {
+        Decimal::try_get_by(res, index).map(Money::from)
+    }
+}
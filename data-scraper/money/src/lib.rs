@@ -0,0 +1,281 @@
+//! An exact amount of money, counted in cents, shared by the scraper, the entities
+//! and the web app.
+
+use std::{fmt, iter::Sum, ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign}, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "sea-orm")]
+mod db;
+
+/// An amount of money in cents. Serialized as a whole number of cents.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub const fn from_cents(cents: i64) -> Self {
+        Money(cents)
+    }
+
+    /// Rounds to the nearest cent, for amounts that arrive as floats
+    pub fn from_dollars(dollars: f64) -> Self {
+        Money((dollars * 100.0).round() as i64)
+    }
+
+    pub const fn cents(self) -> i64 {
+        self.0
+    }
+
+    pub fn dollars(self) -> f64 {
+        self.0 as f64 / 100.0
+    }
+
+    pub const fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn abs(self) -> Self {
+        Money(self.0.abs())
+    }
+
+    /// What one unit costs when `amount` units cost this much, in dollars. Unit
+    /// prices aren't whole cents, so they aren't `Money`.
+    pub fn per_unit(self, amount: f64) -> Option<f64> {
+        (amount > 0.0).then(|| self.dollars() / amount)
+    }
+
+    /// How big this is compared to `other`, e.g. 0.5 for half. `None` if `other` is zero.
+    pub fn ratio(self, other: Money) -> Option<f64> {
+        (other.0 != 0).then(|| self.0 as f64 / other.0 as f64)
+    }
+}
+
+/// e.g. "$2.40", or "-$0.50"
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        write!(f, "{}${}.{:02}", sign, self.0.abs() / 100, self.0.abs() % 100)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMoneyError(String);
+
+impl fmt::Display for ParseMoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not an amount of money: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseMoneyError {}
+
+/// Reads dollars like "2.4", "$2.40" or "-0.5". Prices don't go past the cent, so
+/// more decimal places than that are an error rather than rounded.
+impl FromStr for Money {
+    type Err = ParseMoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseMoneyError(s.to_owned());
+
+        let trimmed = s.trim();
+        let (negative, unsigned) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed),
+        };
+        let unsigned = unsigned.strip_prefix('$').unwrap_or(unsigned);
+        let (dollars, cents) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+
+        let is_digits = |x: &str| x.chars().all(|c| c.is_ascii_digit());
+        if (dollars.is_empty() && cents.is_empty()) || !is_digits(dollars) || !is_digits(cents) || cents.len() > 2 {
+            return Err(error());
+        }
+
+        let dollars = match dollars {
+            "" => 0,
+            dollars => dollars.parse::<i64>().map_err(|_| error())?,
+        };
+        // "4" after the point is 40 cents
+        let cents = match cents.len() {
+            0 => 0,
+            1 => cents.parse::<i64>().map_err(|_| error())? * 10,
+            _ => cents.parse::<i64>().map_err(|_| error())?,
+        };
+
+        let money = dollars
+            .checked_mul(100)
+            .and_then(|x| x.checked_add(cents))
+            .map(Money)
+            .ok_or_else(error)?;
+        Ok(if negative { -money } else { money })
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, rhs: Money) -> Money {
+        Money(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, rhs: Money) {
+        self.0 += rhs.0;
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, rhs: Money) -> Money {
+        Money(self.0 - rhs.0)
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, rhs: Money) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+/// So many of the same thing
+impl Mul<i32> for Money {
+    type Output = Money;
+
+    fn mul(self, rhs: i32) -> Money {
+        Money(self.0 * rhs as i64)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item = &'a Money>>(iter: I) -> Money {
+        iter.copied().sum()
+    }
+}
+
+/// For reading prices that APIs send as numbers of dollars, with
+/// `#[serde(deserialize_with = "money::dollars::deserialize")]`
+pub mod dollars {
+    use serde::{Deserialize, Deserializer};
+
+    use super::Money;
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Money, D::Error> {
+        f64::deserialize(deserializer).map(Money::from_dollars)
+    }
+
+    /// Use with `#[serde(default)]` so a missing price is `None`
+    pub fn deserialize_option<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Money>, D::Error> {
+        Option::<f64>::deserialize(deserializer).map(|x| x.map(Money::from_dollars))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(s: &str) -> Money {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_dollars_and_cents() {
+        assert_eq!(money("2.4"), Money::from_cents(240));
+        assert_eq!(money("$2.40"), Money::from_cents(240));
+        assert_eq!(money(" 12 "), Money::from_cents(1200));
+        assert_eq!(money(".05"), Money::from_cents(5));
+        assert_eq!(money("3."), Money::from_cents(300));
+        assert_eq!(money("-0.5"), Money::from_cents(-50));
+        assert_eq!(money("-$1.99"), Money::from_cents(-199));
+    }
+
+    #[test]
+    fn refuses_what_isnt_money() {
+        for bad in ["", ".", "$", "2.405", "1.2.3", "abc", "1e3", "+1", "--1", "$-1", "1,50", "99999999999999999999"] {
+            assert_eq!(bad.parse::<Money>(), Err(ParseMoneyError(bad.to_owned())), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn displays_as_dollars() {
+        assert_eq!(Money::from_cents(240).to_string(), "$2.40");
+        assert_eq!(Money::from_cents(5).to_string(), "$0.05");
+        assert_eq!(Money::ZERO.to_string(), "$0.00");
+        assert_eq!(Money::from_cents(-50).to_string(), "-$0.50");
+        assert_eq!(Money::from_cents(-1234).to_string(), "-$12.34");
+    }
+
+    #[test]
+    fn display_and_parse_round_trip() {
+        for cents in [0, 1, 99, 100, 1999, -1, -250] {
+            assert_eq!(money(&Money::from_cents(cents).to_string()), Money::from_cents(cents));
+        }
+    }
+
+    #[test]
+    fn negative_amounts() {
+        let refund = -Money::from_cents(150);
+        assert_eq!(refund.cents(), -150);
+        assert_eq!(refund.abs(), Money::from_cents(150));
+        assert_eq!(Money::from_cents(100) - Money::from_cents(250), refund);
+        assert!(refund < Money::ZERO);
+    }
+
+    #[test]
+    fn from_dollars_rounds_to_the_cent() {
+        assert_eq!(Money::from_dollars(2.4), Money::from_cents(240));
+        assert_eq!(Money::from_dollars(19.99), Money::from_cents(1999));
+        assert_eq!(Money::from_dollars(0.1 + 0.2), Money::from_cents(30));
+        assert_eq!(Money::from_dollars(2.494), Money::from_cents(249));
+        assert_eq!(Money::from_dollars(2.496), Money::from_cents(250));
+        assert_eq!(Money::from_dollars(-0.5), Money::from_cents(-50));
+    }
+
+    #[test]
+    fn ratio() {
+        assert_eq!(Money::from_cents(150).ratio(Money::from_cents(300)), Some(0.5));
+        assert_eq!(Money::from_cents(300).ratio(Money::from_cents(150)), Some(2.0));
+        assert_eq!(Money::from_cents(150).ratio(Money::ZERO), None);
+    }
+
+    #[test]
+    fn per_unit() {
+        assert_eq!(Money::from_cents(450).per_unit(3.0), Some(1.5));
+        assert_eq!(Money::from_cents(250).per_unit(0.5), Some(5.0));
+        assert_eq!(Money::from_cents(450).per_unit(0.0), None);
+        assert_eq!(Money::from_cents(450).per_unit(-1.0), None);
+    }
+
+    #[test]
+    fn sums_and_multiplies() {
+        let prices = [Money::from_cents(199), Money::from_cents(250), Money::from_cents(-50)];
+        assert_eq!(prices.iter().sum::<Money>(), Money::from_cents(399));
+        assert_eq!(prices.into_iter().sum::<Money>(), Money::from_cents(399));
+        assert_eq!(Vec::<Money>::new().into_iter().sum::<Money>(), Money::ZERO);
+
+        assert_eq!(Money::from_cents(199) * 3, Money::from_cents(597));
+        assert_eq!(Money::from_cents(199) * -2, Money::from_cents(-398));
+
+        let mut total = Money::ZERO;
+        total += Money::from_cents(100);
+        total -= Money::from_cents(30);
+        assert_eq!(total, Money::from_cents(70));
+    }
+}
@@ -0,0 +1,47 @@
+//! How much product is in a pack, shared so the scraper's shrinkflation check and the
+//! web app's report always measure packs the same way.
+
+/// How much product there is in total, in grams, millilitres or whatever the unit is.
+/// Kilograms and litres are converted so packs labelled either way compare, and a
+/// pack with no size or unit counts as one item.
+pub fn amount(size: Option<f32>, unit: Option<&str>, quantity: i32) -> (f32, String) {
+    let size = size.unwrap_or(1.0) * quantity.max(1) as f32;
+    let unit = unit.unwrap_or("ea").to_lowercase();
+
+    match unit.as_str() {
+        "kg" => (size * 1000.0, "g".to_owned()),
+        "l" => (size * 1000.0, "ml".to_owned()),
+        _ => (size, unit),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kilograms_are_grams() {
+        assert_eq!(amount(Some(1.5), Some("kg"), 1), (1500.0, "g".to_owned()));
+        assert_eq!(amount(Some(500.0), Some("g"), 1), (500.0, "g".to_owned()));
+    }
+
+    #[test]
+    fn litres_are_millilitres() {
+        assert_eq!(amount(Some(2.0), Some("L"), 1), (2000.0, "ml".to_owned()));
+        assert_eq!(amount(Some(330.0), Some("mL"), 1), (330.0, "ml".to_owned()));
+    }
+
+    #[test]
+    fn multipacks_count_every_pack() {
+        assert_eq!(amount(Some(330.0), Some("ml"), 6), (1980.0, "ml".to_owned()));
+        // A quantity of zero is a single pack that wasn't labelled
+        assert_eq!(amount(Some(330.0), Some("ml"), 0), (330.0, "ml".to_owned()));
+    }
+
+    #[test]
+    fn unlabelled_packs_are_items() {
+        assert_eq!(amount(None, None, 1), (1.0, "ea".to_owned()));
+        assert_eq!(amount(None, None, 12), (12.0, "ea".to_owned()));
+        assert_eq!(amount(None, Some("EA"), 4), (4.0, "ea".to_owned()));
+    }
+}
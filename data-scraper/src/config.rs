@@ -1,29 +1,411 @@
-use std::env;
+use std::{collections::{BTreeMap, BTreeSet}, env, fmt::Display, fs, net::SocketAddr, path::{Path, PathBuf}, str::FromStr};
 
-use chrono::NaiveTime;
 use log::info;
-use once_cell::sync::Lazy;
+use once_cell::sync::OnceCell;
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::Deserialize;
+use url::Url;
 
+use crate::{error::ScraperError, rate_limit::RateLimits};
 
+/// Read when `SCRAPER_CONFIG` doesn't point somewhere else
+const DEFAULT_CONFIG_FILE: &str = "scraper.toml";
 
-pub static DATA_OUT_DIR: &str = "dataout_tmp";
+/// The most products the Countdown API returns in one page
+const MAX_PAGE_SIZE: usize = 120;
 
-pub struct EnvConfig {
-    pub db_connection_uri: String,
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+/// How much of each supermarket a round of scraping covers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrapeMode {
+    /// Every product in every department
+    #[default]
+    Full,
+    /// Only products on someone's watchlist, looked up one by one
+    Watched,
+    /// Only products on special
+    Specials,
+}
+
+impl FromStr for ScrapeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "full" => Ok(ScrapeMode::Full),
+            "watched" => Ok(ScrapeMode::Watched),
+            "specials" => Ok(ScrapeMode::Specials),
+            _ => Err(String::from("expected full, watched or specials")),
+        }
+    }
+}
+
+/// Settings for the whole scraper. Built from the defaults, then `scraper.toml` (see
+/// `scraper.example.toml`), then environment variables, each overriding the last.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database_url: String,
+    pub scrape_mode: ScrapeMode,
+    /// Most products fetched from one supermarket in a scrape, across all departments
     pub max_products_scrape: usize,
+    /// Where raw API responses are written
+    pub output_dir: PathBuf,
+    /// Pause between one round of scraping and the next
+    pub scrape_interval_secs: u64,
+    /// Where to serve `/metrics`, `/healthz` and `/readyz`
+    pub metrics_addr: SocketAddr,
+    /// The scraper is unhealthy if a supermarket hasn't been scraped for this long
+    pub scrape_health_window_secs: u64,
+    pub countdown: CountdownConfig,
+    pub images: ImageConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CountdownConfig {
+    /// Where the Countdown API lives, overridable to scrape a mock server
+    pub base_url: String,
+    /// Products asked for per page
+    pub page_size: usize,
+    /// Departments, or watched products, fetched at the same time
+    pub department_workers: usize,
+    pub departments: DepartmentFilter,
+    pub connect_timeout_secs: u64,
+    /// Limit on a whole request, from connecting to reading the body
+    pub request_timeout_secs: u64,
+    /// Sent with every request. Entries in the file are added to these, replacing
+    /// any with the same name.
+    pub headers: BTreeMap<String, String>,
+    pub rate_limits: RateLimits,
+    pub retry: RetryPolicy,
+    pub details: DetailConfig,
+}
+
+/// Which departments to scrape, by the names used in the API's filter, e.g. `fruit-veg`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DepartmentFilter {
+    /// Only these departments, or every department if empty
+    pub include: BTreeSet<String>,
+    /// Never these departments
+    pub exclude: BTreeSet<String>,
+}
+
+impl DepartmentFilter {
+    pub fn allows(&self, department: &str) -> bool {
+        (self.include.is_empty() || self.include.contains(department)) && !self.exclude.contains(department)
+    }
+}
+
+/// Fetching product pages for ingredients, nutrition and origin, after the prices
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DetailConfig {
+    pub enabled: bool,
+    /// Details older than this are fetched again
+    pub refresh_days: u32,
+    /// Most product pages fetched after each scrape, so a full catalogue is enriched
+    /// over many scrapes instead of all at once
+    pub max_products_per_run: usize,
+}
+
+impl Default for DetailConfig {
+    fn default() -> Self {
+        DetailConfig {
+            enabled: false,
+            refresh_days: 30,
+            max_products_per_run: 200,
+        }
+    }
+}
+
+/// Copying product images into our own storage after each round of scraping, so
+/// the web app doesn't have to link to the supermarkets' CDNs
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageConfig {
+    pub enabled: bool,
+    /// A directory, or an `s3://bucket/prefix` URL. The web app's `IMAGE_STORE`
+    /// must point at the same place.
+    pub store: String,
+    /// Most images downloaded after each round
+    pub max_per_run: u64,
+    /// Images downloaded at the same time
+    pub workers: usize,
+    pub request_timeout_secs: u64,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        ImageConfig {
+            enabled: false,
+            store: String::from("images"),
+            max_per_run: 500,
+            workers: 4,
+            request_timeout_secs: 20,
+        }
+    }
+}
+
+/// How failed requests are retried, backing off exponentially
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    pub initial_delay_secs: u64,
+    /// Each delay is this many times the last
+    pub factor: u32,
+    pub max_delay_secs: u64,
+    /// Retries of a request that failed for some transient reason
+    pub max_retries: usize,
+    /// How many times in a row a request can be rate limited before giving up on it
+    pub max_rate_limited_attempts: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            database_url: String::new(),
+            scrape_mode: ScrapeMode::default(),
+            max_products_scrape: 30000,
+            output_dir: PathBuf::from("dataout_tmp"),
+            scrape_interval_secs: 30,
+            metrics_addr: SocketAddr::from(([127, 0, 0, 1], 9464)),
+            scrape_health_window_secs: 2 * 60 * 60,
+            countdown: CountdownConfig::default(),
+            images: ImageConfig::default(),
+        }
+    }
+}
+
+impl Default for CountdownConfig {
+    fn default() -> Self {
+        CountdownConfig {
+            base_url: String::from("https://www.countdown.co.nz"),
+            page_size: MAX_PAGE_SIZE,
+            department_workers: 4,
+            departments: DepartmentFilter::default(),
+            connect_timeout_secs: 5,
+            request_timeout_secs: 10,
+            headers: default_countdown_headers(),
+            rate_limits: RateLimits::default(),
+            retry: RetryPolicy::default(),
+            details: DetailConfig::default(),
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            initial_delay_secs: 1,
+            factor: 2,
+            max_delay_secs: 20,
+            max_retries: 6,
+            max_rate_limited_attempts: 8,
+        }
+    }
 }
 
-pub static CONFIG: Lazy<EnvConfig> = Lazy::new(|| {
+/// The headers the Countdown web app sends
+fn default_countdown_headers() -> BTreeMap<String, String> {
+    return [
+        ("authority", "www.countdown.co.nz"),
+        ("accept", "application/json, text/plain, */*"),
+        ("accept-language", "en-GB,en-US;q=0.9,en;q=0.8"),
+        ("cache-control", "no-cache"),
+        ("user-agent", "Yes/1.0.0"),
+        ("x-requested-with", "OnlineShopping.WebApp"),
+        ("x-ui-ver", "7.30.266"),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_owned(), value.to_owned()))
+    .collect();
+}
+
+/// The loaded config. Only call after `load`.
+pub fn get() -> &'static Config {
+    CONFIG.get().expect("config::load is called at startup")
+}
+
+/// Reads, checks and stores the config, failing with what's wrong with it
+pub fn load() -> Result<&'static Config, ScraperError> {
     if dotenv::dotenv().is_err() {
         info!("No .env file found");
     }
 
-    EnvConfig {
-        db_connection_uri: env::var("DATABASE_URL")
-            .expect("Missing DATABASE_URL environment variable"),
-        max_products_scrape: env::var("MAX_PRODUCTS_SCRAPE")
-            .unwrap_or(String::from("30000"))
-            .parse::<usize>()
-            .expect("MAX_PRODUCTS_SCRAPE must be a number"),
+    let mut config = match env::var("SCRAPER_CONFIG") {
+        Ok(path) => read_file(Path::new(&path))?,
+        Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => read_file(Path::new(DEFAULT_CONFIG_FILE))?,
+        Err(_) => Config::default(),
+    };
+
+    config.apply_env()?;
+    config.validate()?;
+
+    return Ok(CONFIG.get_or_init(|| config));
+}
+
+/// The defaults, with responses written somewhere out of the way, for tests that
+/// reach code calling `get`
+#[cfg(test)]
+pub fn for_tests() -> &'static Config {
+    CONFIG.get_or_init(|| {
+        let output_dir = env::temp_dir().join("data-scraper-tests");
+        fs::create_dir_all(&output_dir).expect("Temp dir is writable");
+        Config { output_dir, ..Config::default() }
+    })
+}
+
+fn read_file(path: &Path) -> Result<Config, ScraperError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| ScraperError::Config(format!("Couldn't read {}: {e}", path.display())))?;
+    let mut config: Config = toml::from_str(&contents)
+        .map_err(|e| ScraperError::Config(format!("{} isn't valid: {e}", path.display())))?;
+
+    let mut headers = default_countdown_headers();
+    headers.extend(config.countdown.headers);
+    config.countdown.headers = headers;
+
+    info!("Loaded config from {}", path.display());
+    return Ok(config);
+}
+
+/// Replaces `list` with the comma separated environment variable `name`, if it's set
+fn env_list_override(list: &mut BTreeSet<String>, name: &str) {
+    if let Ok(raw) = env::var(name) {
+        *list = raw
+            .split(',')
+            .map(|x| x.trim().to_owned())
+            .filter(|x| !x.is_empty())
+            .collect();
+    }
+}
+
+/// Replaces `value` with the environment variable `name`, if it's set
+fn env_override<T>(value: &mut T, name: &str) -> Result<(), ScraperError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Ok(raw) = env::var(name) {
+        *value = raw
+            .parse()
+            .map_err(|e| ScraperError::Config(format!("{name}={raw:?} isn't valid: {e}")))?;
+    }
+
+    return Ok(());
+}
+
+impl Config {
+    fn apply_env(&mut self) -> Result<(), ScraperError> {
+        env_override(&mut self.database_url, "DATABASE_URL")?;
+        env_override(&mut self.scrape_mode, "SCRAPE_MODE")?;
+        env_override(&mut self.max_products_scrape, "MAX_PRODUCTS_SCRAPE")?;
+        env_override(&mut self.output_dir, "DATA_OUT_DIR")?;
+        env_override(&mut self.scrape_interval_secs, "SCRAPE_INTERVAL_SECS")?;
+        env_override(&mut self.metrics_addr, "METRICS_ADDR")?;
+        env_override(&mut self.scrape_health_window_secs, "SCRAPE_HEALTH_WINDOW_SECS")?;
+
+        let countdown = &mut self.countdown;
+        env_override(&mut countdown.base_url, "COUNTDOWN_BASE_URL")?;
+        env_override(&mut countdown.page_size, "COUNTDOWN_PAGE_SIZE")?;
+        env_override(&mut countdown.department_workers, "COUNTDOWN_DEPARTMENT_WORKERS")?;
+        env_override(&mut countdown.details.enabled, "COUNTDOWN_FETCH_DETAILS")?;
+        env_list_override(&mut countdown.departments.include, "COUNTDOWN_DEPARTMENTS");
+        env_list_override(&mut countdown.departments.exclude, "COUNTDOWN_EXCLUDE_DEPARTMENTS");
+        env_override(&mut countdown.rate_limits.requests_per_second, "COUNTDOWN_REQUESTS_PER_SECOND")?;
+        env_override(&mut countdown.rate_limits.min_requests_per_second, "COUNTDOWN_MIN_REQUESTS_PER_SECOND")?;
+        env_override(&mut countdown.rate_limits.burst, "COUNTDOWN_BURST")?;
+        env_override(&mut countdown.rate_limits.max_concurrent, "COUNTDOWN_MAX_CONCURRENT")?;
+        env_override(&mut countdown.rate_limits.max_retry_after_secs, "COUNTDOWN_MAX_RETRY_AFTER_SECS")?;
+
+        env_override(&mut self.images.enabled, "MIRROR_IMAGES")?;
+        env_override(&mut self.images.store, "IMAGE_STORE")?;
+
+        return Ok(());
     }
-});
\ No newline at end of file
+
+    /// Catches settings that would only go wrong later, in the middle of a scrape
+    fn validate(&self) -> Result<(), ScraperError> {
+        let invalid = |message: String| Err(ScraperError::Config(message));
+
+        if self.database_url.is_empty() {
+            return invalid(format!("No database set, set DATABASE_URL or database_url in {DEFAULT_CONFIG_FILE}"));
+        }
+        if self.max_products_scrape == 0 {
+            return invalid(String::from("max_products_scrape must be at least 1"));
+        }
+        if self.output_dir.as_os_str().is_empty() {
+            return invalid(String::from("output_dir can't be empty"));
+        }
+
+        let countdown = &self.countdown;
+        match Url::parse(&countdown.base_url) {
+            Ok(url) if url.host_str().is_some() => {}
+            Ok(_) => return invalid(format!("countdown.base_url {:?} has no host", countdown.base_url)),
+            Err(e) => return invalid(format!("countdown.base_url {:?} isn't valid: {e}", countdown.base_url)),
+        }
+        if !(1..=MAX_PAGE_SIZE).contains(&countdown.page_size) {
+            return invalid(format!("countdown.page_size must be between 1 and {MAX_PAGE_SIZE}, not {}", countdown.page_size));
+        }
+        if countdown.department_workers == 0 {
+            return invalid(String::from("countdown.department_workers must be at least 1"));
+        }
+        if let Some(department) = countdown.departments.include.intersection(&countdown.departments.exclude).next() {
+            return invalid(format!("countdown.departments can't both include and exclude {department:?}"));
+        }
+        if countdown.connect_timeout_secs == 0 || countdown.request_timeout_secs == 0 {
+            return invalid(String::from("countdown timeouts must be at least 1 second"));
+        }
+        for (name, value) in &countdown.headers {
+            if let Err(e) = HeaderName::from_str(name) {
+                return invalid(format!("countdown.headers has an invalid name {name:?}: {e}"));
+            }
+            if let Err(e) = HeaderValue::from_str(value) {
+                return invalid(format!("countdown.headers.{name} has an invalid value {value:?}: {e}"));
+            }
+        }
+
+        let limits = &countdown.rate_limits;
+        if !(limits.min_requests_per_second > 0.0 && limits.min_requests_per_second <= limits.requests_per_second) {
+            return invalid(format!(
+                "countdown.rate_limits needs 0 < min_requests_per_second <= requests_per_second, not {} and {}",
+                limits.min_requests_per_second, limits.requests_per_second,
+            ));
+        }
+        if limits.burst == 0 || limits.max_concurrent == 0 {
+            return invalid(String::from("countdown.rate_limits burst and max_concurrent must be at least 1"));
+        }
+
+        if countdown.details.refresh_days == 0 {
+            return invalid(String::from("countdown.details.refresh_days must be at least 1"));
+        }
+
+        let retry = &countdown.retry;
+        if retry.factor == 0 {
+            return invalid(String::from("countdown.retry.factor must be at least 1"));
+        }
+        if retry.initial_delay_secs > retry.max_delay_secs {
+            return invalid(String::from("countdown.retry.initial_delay_secs can't be more than max_delay_secs"));
+        }
+        if retry.max_rate_limited_attempts == 0 {
+            return invalid(String::from("countdown.retry.max_rate_limited_attempts must be at least 1"));
+        }
+
+        let images = &self.images;
+        if images.store.is_empty() {
+            return invalid(String::from("images.store can't be empty"));
+        }
+        if images.max_per_run == 0 || images.workers == 0 {
+            return invalid(String::from("images.max_per_run and images.workers must be at least 1"));
+        }
+        if images.request_timeout_secs == 0 {
+            return invalid(String::from("images.request_timeout_secs must be at least 1 second"));
+        }
+
+        return Ok(());
+    }
+}
@@ -1,18 +1,73 @@
+use std::time::Duration;
+
 use log::info;
 use migration::MigratorTrait;
 use sea_orm::{ConnectOptions, Database, DatabaseConnection};
 
-use crate::config::CONFIG;
+use crate::{config, error::ScraperError};
+
+/// Long enough never to come, but not so long that adding it to now overflows
+const FOREVER: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+pub async fn connect() -> Result<DatabaseConnection, ScraperError> {
+    connect_to(&config::get().database_url).await
+}
 
-pub async fn connect() -> DatabaseConnection {
-    let mut opt = ConnectOptions::new(&CONFIG.db_connection_uri);
+/// Connects to the database at `db_connection_uri` and brings its schema up to date
+pub async fn connect_to(db_connection_uri: &str) -> Result<DatabaseConnection, ScraperError> {
+    let mut opt = ConnectOptions::new(db_connection_uri);
     opt.sqlx_logging_level(log::LevelFilter::Debug);
-    
-    let db = Database::connect(opt).await.unwrap();
+
+    // Every connection to an in-memory SQLite database gets a database of its own,
+    // which is gone once the connection closes. So there's only ever the one
+    // connection, and it's never closed for being idle or old.
+    if db_connection_uri.contains(":memory:") {
+        opt.min_connections(1)
+            .max_connections(1)
+            .idle_timeout(FOREVER)
+            .max_lifetime(FOREVER);
+    }
+
+    let db = Database::connect(opt).await?;
 
     info!("Connected to DB, running migrations...");
-    migration::Migrator::up(&db, None).await.unwrap();
+    migration::Migrator::up(&db, None).await?;
     info!("Migrations complete");
 
-    db
+    Ok(db)
+}
+
+/// A freshly migrated, empty in-memory database, for tests
+#[cfg(test)]
+pub async fn connect_for_tests() -> DatabaseConnection {
+    connect_to("sqlite::memory:").await.expect("In-memory SQLite database can be migrated")
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{ActiveModelTrait, EntityTrait, PaginatorTrait, Set};
+
+    use crate::db::entities::{prelude::Supermarkets, supermarkets};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn in_memory_database_is_shared_by_every_query() {
+        let db = connect_for_tests().await;
+
+        supermarkets::ActiveModel {
+            name: Set(String::from("Countdown Online")),
+            brand_name: Set(String::from("Countdown")),
+            location: Set(String::from("Online")),
+            location_id: Set(String::from("online")),
+            ..Default::default()
+        }.insert(&db).await.unwrap();
+
+        // Queries at the same time would each get a new, empty database if the pool
+        // opened more connections
+        let counts = futures::future::join_all((0..4).map(|_| Supermarkets::find().count(&db))).await;
+        for count in counts {
+            assert_eq!(count.unwrap(), 1);
+        }
+    }
 }
@@ -1,7 +0,0 @@
-//! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.4
-
-pub mod prelude;
-
-pub mod product_db;
-pub mod supermarket_price;
-pub mod supermarkets;
@@ -0,0 +1,54 @@
+use chrono::Utc;
+use sea_orm::{sea_query::Query, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect, Set};
+
+use super::entities::{prelude::*, product_db, product_image, product_image_source};
+use crate::{error::ScraperError, metrics};
+
+/// Image URLs of products that haven't been downloaded yet, at most `limit` of them
+pub async fn get_unmirrored_image_urls(db: &DatabaseConnection, limit: u64) -> Result<Vec<String>, ScraperError> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["get_unmirrored_image_urls"]).start_timer();
+    let urls = ProductDb::find()
+        .select_only()
+        .column(product_db::Column::ImageUrl)
+        .distinct()
+        .filter(product_db::Column::ImageUrl.is_not_null())
+        .filter(product_db::Column::ImageUrl.not_in_subquery(
+            Query::select()
+                .column(product_image_source::Column::Url)
+                .from(ProductImageSource)
+                .to_owned()
+        ))
+        .limit(limit)
+        .into_tuple::<String>()
+        .all(db).await?;
+
+    return Ok(urls);
+}
+
+pub async fn get_image_by_hash(db: &DatabaseConnection, content_hash: &str) -> Result<Option<product_image::Model>, ScraperError> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["get_image_by_hash"]).start_timer();
+    let image = ProductImage::find()
+        .filter(product_image::Column::ContentHash.eq(content_hash))
+        .one(db).await?;
+
+    return Ok(image);
+}
+
+pub async fn save_product_image(db: &DatabaseConnection, image: product_image::ActiveModel) -> Result<product_image::Model, ScraperError> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["save_product_image"]).start_timer();
+    return Ok(image.insert(db).await?);
+}
+
+/// Records what an image URL led to, `None` if it wasn't an image we could use,
+/// so it isn't downloaded again
+pub async fn save_image_source(db: &DatabaseConnection, url: String, image_id: Option<i32>) -> Result<(), ScraperError> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["save_image_source"]).start_timer();
+    product_image_source::ActiveModel {
+        url: Set(url),
+        image_id: Set(image_id),
+        fetched_at: Set(Utc::now().into()),
+        ..Default::default()
+    }.insert(db).await?;
+
+    return Ok(());
+}
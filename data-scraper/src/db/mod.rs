@@ -7,4 +7,16 @@ pub use supermarkets::*;
 pub mod products;
 pub use products::*;
 
-pub mod entities;
+pub mod scrape_runs;
+pub use scrape_runs::*;
+
+pub mod product_details;
+pub use product_details::*;
+
+pub mod images;
+pub use images::*;
+
+pub mod price_storage;
+pub use price_storage::*;
+
+pub use entity as entities;
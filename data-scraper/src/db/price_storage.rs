@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use crate::{db::entities::{prelude::*, price_daily, price_weekly, supermarket_price}, error::ScraperError, metrics};
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc};
+use log::warn;
+use money::Money;
+use sea_orm::{
+    prelude::DateTimeWithTimeZone, sea_query::OnConflict, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend,
+    EntityTrait, QueryFilter, QuerySelect, Set, Statement,
+};
+
+/// Months of partitions kept ready ahead of now
+const PARTITIONS_AHEAD: i32 = 2;
+
+/// Aggregate rows written per statement, well under SQLite's limit on bound values
+const AGGREGATE_CHUNK_SIZE: usize = 1000;
+
+/// How `supermarket_price` is stored, which decides how its aggregates are kept up to date
+enum PriceStorage {
+    /// TimescaleDB hypertable with continuous aggregates
+    Hypertable,
+    /// Monthly partitions with aggregate tables
+    Partitioned,
+}
+
+async fn price_storage(db: &DatabaseConnection) -> Result<Option<PriceStorage>, ScraperError> {
+    let kind = db
+        .query_one(Statement::from_string(
+            DbBackend::Postgres,
+            "SELECT relkind::text AS kind FROM pg_class WHERE relname = 'price_daily'",
+        ))
+        .await?
+        .map(|x| x.try_get::<String>("", "kind"))
+        .transpose()?;
+
+    return Ok(match kind.as_deref() {
+        Some("v") => Some(PriceStorage::Hypertable),
+        Some(_) => Some(PriceStorage::Partitioned),
+        None => None,
+    });
+}
+
+/// Brings the daily and weekly price aggregates up to date with the prices just
+/// scraped, and makes sure there are partitions for the coming months
+pub async fn maintain_price_storage(db: &DatabaseConnection) -> Result<(), ScraperError> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["refresh_aggregates"]).start_timer();
+
+    if db.get_database_backend() != DbBackend::Postgres {
+        return refresh_aggregates_in_memory(db).await;
+    }
+
+    match price_storage(db).await? {
+        Some(PriceStorage::Hypertable) => {
+            // Timescale only recomputes the buckets that changed
+            db.execute_unprepared(
+                r#"
+                CALL refresh_continuous_aggregate('price_daily', NULL, NULL);
+                CALL refresh_continuous_aggregate('price_weekly', NULL, NULL);
+                "#
+            ).await?;
+        }
+        Some(PriceStorage::Partitioned) => {
+            // Fails if prices for the month already went into the default partition,
+            // which only happens if the scraper was stopped for months. Those stay
+            // where they are.
+            let partitions = db.execute(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                "SELECT add_price_partition((now() + make_interval(months => n))::date) FROM generate_series(0, $1) AS n",
+                [PARTITIONS_AHEAD.into()],
+            )).await;
+            if let Err(e) = partitions {
+                warn!("Couldn't add price partitions: {}", e);
+            }
+
+            db.execute_unprepared("SELECT refresh_price_aggregates()").await?;
+        }
+        None => warn!("No price aggregates, has the database been migrated?"),
+    }
+
+    return Ok(());
+}
+
+/// One product's prices at one store over a day or week
+struct Bucket {
+    min: Money,
+    max: Money,
+    total_cents: i64,
+    count: i64,
+    close: Money,
+    close_at: DateTime<Utc>,
+}
+
+impl Bucket {
+    fn new(price: Money, timestamp: DateTime<Utc>) -> Self {
+        Bucket { min: price, max: price, total_cents: price.cents(), count: 1, close: price, close_at: timestamp }
+    }
+
+    fn add(&mut self, price: Money, timestamp: DateTime<Utc>) {
+        self.min = self.min.min(price);
+        self.max = self.max.max(price);
+        self.total_cents += price.cents();
+        self.count += 1;
+        if timestamp >= self.close_at {
+            self.close = price;
+            self.close_at = timestamp;
+        }
+    }
+
+    /// Rounded half up to the cent, like casting Postgres' avg to numeric(12, 2)
+    fn average(&self) -> Money {
+        Money::from_cents((2 * self.total_cents + self.count) / (2 * self.count))
+    }
+}
+
+type Buckets = HashMap<(i32, i32, DateTime<Utc>), Bucket>;
+
+/// The same as `refresh_price_aggregates()`, bucketing the prices here for databases
+/// without `date_trunc`. Everything from the start of the newest week already
+/// aggregated is bucketed again, which covers the newest day too.
+async fn refresh_aggregates_in_memory(db: &DatabaseConnection) -> Result<(), ScraperError> {
+    let since: Option<DateTimeWithTimeZone> = PriceWeekly::find()
+        .select_only()
+        .column_as(price_weekly::Column::Bucket.max(), "since")
+        .into_tuple()
+        .one(db).await?
+        .flatten();
+
+    let mut query = SupermarketPrice::find()
+        .filter(supermarket_price::Column::Flagged.eq(false))
+        .filter(supermarket_price::Column::Price.gt(Money::ZERO));
+    if let Some(since) = since {
+        query = query.filter(supermarket_price::Column::Timestamp.gte(since));
+    }
+    let prices = query.all(db).await?;
+
+    let mut daily = Buckets::new();
+    let mut weekly = Buckets::new();
+    for price in prices {
+        let timestamp = price.timestamp.with_timezone(&Utc);
+        let day = timestamp.date_naive();
+        let week = day - Duration::days(day.weekday().num_days_from_monday() as i64);
+
+        for (buckets, start) in [(&mut daily, day), (&mut weekly, week)] {
+            let start = start.and_time(NaiveTime::MIN).and_utc();
+            buckets
+                .entry((price.product_id, price.supermarket_id, start))
+                .and_modify(|x| x.add(price.price, timestamp))
+                .or_insert_with(|| Bucket::new(price.price, timestamp));
+        }
+    }
+
+    let daily: Vec<_> = daily
+        .into_iter()
+        .map(|((product_id, supermarket_id, bucket), x)| price_daily::ActiveModel {
+            product_id: Set(product_id),
+            supermarket_id: Set(supermarket_id),
+            bucket: Set(bucket.into()),
+            min_price: Set(x.min),
+            max_price: Set(x.max),
+            avg_price: Set(x.average()),
+            close_price: Set(x.close),
+        })
+        .collect();
+    for chunk in daily.chunks(AGGREGATE_CHUNK_SIZE) {
+        PriceDaily::insert_many(chunk.to_vec())
+            .on_conflict(
+                OnConflict::columns([price_daily::Column::ProductId, price_daily::Column::SupermarketId, price_daily::Column::Bucket])
+                    .update_columns([
+                        price_daily::Column::MinPrice,
+                        price_daily::Column::MaxPrice,
+                        price_daily::Column::AvgPrice,
+                        price_daily::Column::ClosePrice,
+                    ])
+                    .to_owned()
+            )
+            .exec(db).await?;
+    }
+
+    let weekly: Vec<_> = weekly
+        .into_iter()
+        .map(|((product_id, supermarket_id, bucket), x)| price_weekly::ActiveModel {
+            product_id: Set(product_id),
+            supermarket_id: Set(supermarket_id),
+            bucket: Set(bucket.into()),
+            min_price: Set(x.min),
+            max_price: Set(x.max),
+            avg_price: Set(x.average()),
+            close_price: Set(x.close),
+        })
+        .collect();
+    for chunk in weekly.chunks(AGGREGATE_CHUNK_SIZE) {
+        PriceWeekly::insert_many(chunk.to_vec())
+            .on_conflict(
+                OnConflict::columns([price_weekly::Column::ProductId, price_weekly::Column::SupermarketId, price_weekly::Column::Bucket])
+                    .update_columns([
+                        price_weekly::Column::MinPrice,
+                        price_weekly::Column::MaxPrice,
+                        price_weekly::Column::AvgPrice,
+                        price_weekly::Column::ClosePrice,
+                    ])
+                    .to_owned()
+            )
+            .exec(db).await?;
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone};
+    use sea_orm::{ActiveModelTrait, PaginatorTrait, QueryOrder};
+
+    use crate::db::{connect_for_tests, entities::{price_index, product_db}, upsert_supermarket};
+    use crate::price_index::update_price_index;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn fills_the_aggregates_without_postgres() {
+        let mut db = connect_for_tests().await;
+        let store = upsert_supermarket(&db, "Countdown Online", "Countdown", "Online", "online").await.unwrap();
+        let product = product_db::ActiveModel {
+            product_title: Set(String::from("Milk")),
+            quantity: Set(1),
+            updated_at: Set(Utc::now().into()),
+            ..Default::default()
+        }.insert(&db).await.unwrap();
+
+        let add_price = async |day: u32, hour: u32, cents: i64, flagged: bool| {
+            supermarket_price::ActiveModel {
+                timestamp: Set(Utc.with_ymd_and_hms(2024, 3, day, hour, 0, 0).unwrap().into()),
+                supermarket_id: Set(store.supermarket_id),
+                product_id: Set(product.product_id),
+                price: Set(Money::from_cents(cents)),
+                flagged: Set(flagged),
+                ..Default::default()
+            }.insert(&db).await.unwrap();
+        };
+
+        // Monday the 18th, then the Wednesday after
+        add_price(18, 9, 300, false).await;
+        add_price(18, 15, 200, false).await;
+        add_price(18, 12, 5000, true).await;
+        add_price(20, 9, 205, false).await;
+        maintain_price_storage(&db).await.unwrap();
+
+        let daily = PriceDaily::find().order_by_asc(price_daily::Column::Bucket).all(&db).await.unwrap();
+        let prices: Vec<_> = daily.iter()
+            .map(|x| (x.bucket.date_naive(), x.min_price.cents(), x.max_price.cents(), x.avg_price.cents(), x.close_price.cents()))
+            .collect();
+        assert_eq!(prices, [
+            (NaiveDate::from_ymd_opt(2024, 3, 18).unwrap(), 200, 300, 250, 200),
+            (NaiveDate::from_ymd_opt(2024, 3, 20).unwrap(), 205, 205, 205, 205),
+        ]);
+
+        // A later scrape updates the buckets it adds to
+        add_price(20, 18, 100, false).await;
+        maintain_price_storage(&db).await.unwrap();
+
+        let wednesday = PriceDaily::find().order_by_desc(price_daily::Column::Bucket).one(&db).await.unwrap().unwrap();
+        assert_eq!((wednesday.min_price.cents(), wednesday.avg_price.cents(), wednesday.close_price.cents()), (100, 153, 100));
+        let week = PriceWeekly::find().all(&db).await.unwrap();
+        assert_eq!(week.len(), 1);
+        assert_eq!(week[0].bucket.date_naive(), NaiveDate::from_ymd_opt(2024, 3, 18).unwrap());
+        assert_eq!((week[0].min_price.cents(), week[0].max_price.cents(), week[0].avg_price.cents(), week[0].close_price.cents()), (100, 300, 201, 100));
+
+        // Which is enough for the price index to be worked out
+        update_price_index(&mut db).await.unwrap();
+        assert!(PriceIndex::find().filter(price_index::Column::PeriodType.eq("daily")).count(&db).await.unwrap() > 0);
+    }
+}
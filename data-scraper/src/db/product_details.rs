@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect, Set, TransactionTrait};
+use sea_orm::prelude::DateTimeWithTimeZone;
+
+use super::entities::{prelude::*, product_detail, product_nutrition};
+use crate::{error::ScraperError, metrics};
+
+/// When each product's details were last fetched from a supermarket, by product ID
+pub async fn get_detail_fetch_times(db: &DatabaseConnection, supermarket_id: i32) -> Result<HashMap<i32, DateTimeWithTimeZone>, ScraperError> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["get_detail_fetch_times"]).start_timer();
+    let fetched = ProductDetail::find()
+        .select_only()
+        .column(product_detail::Column::ProductId)
+        .column(product_detail::Column::FetchedAt)
+        .filter(product_detail::Column::SupermarketId.eq(supermarket_id))
+        .into_tuple::<(i32, DateTimeWithTimeZone)>()
+        .all(db).await?;
+
+    return Ok(fetched.into_iter().collect());
+}
+
+/// Stores a product's details and nutrition, replacing any fetched before
+pub async fn save_product_detail(
+    db: &DatabaseConnection,
+    mut detail: product_detail::ActiveModel,
+    nutrition: Vec<product_nutrition::ActiveModel>,
+) -> Result<(), ScraperError> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["save_product_detail"]).start_timer();
+    let txn = db.begin().await?;
+
+    let existing = ProductDetail::find()
+        .filter(product_detail::Column::ProductId.eq(detail.product_id.clone().unwrap()))
+        .filter(product_detail::Column::SupermarketId.eq(detail.supermarket_id.clone().unwrap()))
+        .one(&txn).await?;
+
+    let detail = match existing {
+        Some(existing) => {
+            ProductNutrition::delete_many()
+                .filter(product_nutrition::Column::DetailId.eq(existing.id))
+                .exec(&txn).await?;
+            detail.id = Set(existing.id);
+            detail.update(&txn).await?
+        }
+        None => detail.insert(&txn).await?,
+    };
+
+    if !nutrition.is_empty() {
+        ProductNutrition::insert_many(nutrition.into_iter().map(|mut x| {
+            x.detail_id = Set(detail.id);
+            x
+        })).exec(&txn).await?;
+    }
+
+    txn.commit().await?;
+    return Ok(());
+}
@@ -1,14 +1,74 @@
-use super::entities::{prelude::*, supermarkets, product_db};
-use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
-use sea_orm::{ColumnTrait, Condition, EntityTrait};
+use super::entities::{prelude::*, product_db, product_revision, watchlist};
+use crate::{error::ScraperError, metrics, product_revisions::ProductDetails};
+use money::Money;
+use sea_orm::{sea_query::Query, ActiveModelTrait, DatabaseConnection, Set};
+use sea_orm::{prelude::DateTimeWithTimeZone, ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
 
 
 
-pub async fn get_products(db: &mut DatabaseConnection) -> Result<Vec<product_db::ActiveModel>, Box<dyn std::error::Error + Send + Sync>> {
-    let query = ProductDb::find()
+pub async fn get_products(db: &mut DatabaseConnection) -> Result<Vec<product_db::Model>, ScraperError> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["get_products"]).start_timer();
+    let products = ProductDb::find()
+        .order_by_asc(product_db::Column::ProductId)
         .all(db).await?;
 
-    let active_models = query.into_iter().map(|x| x.into()).collect::<Vec<product_db::ActiveModel>>();
+    return Ok(products);
+}
 
-    return Ok(active_models);
-}
\ No newline at end of file
+/// Products added after `product_id`, or saved since `since`, oldest first
+pub async fn get_products_changed(db: &mut DatabaseConnection, product_id: i32, since: Option<DateTimeWithTimeZone>) -> Result<Vec<product_db::Model>, ScraperError> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["get_products_changed"]).start_timer();
+    let added = product_db::Column::ProductId.gt(product_id);
+    let changed = match since {
+        Some(since) => added.or(product_db::Column::UpdatedAt.gt(since)),
+        None => added,
+    };
+    let products = ProductDb::find()
+        .filter(changed)
+        .order_by_asc(product_db::Column::ProductId)
+        .all(db).await?;
+
+    return Ok(products);
+}
+
+/// Products on at least one user's watchlist
+pub async fn get_watched_products(db: &DatabaseConnection) -> Result<Vec<product_db::Model>, ScraperError> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["get_watched_products"]).start_timer();
+    let watched = ProductDb::find()
+        .filter(product_db::Column::ProductId.in_subquery(
+            Query::select()
+                .column(watchlist::Column::ProductId)
+                .from(Watchlist)
+                .to_owned()
+        ))
+        .order_by_asc(product_db::Column::ProductId)
+        .all(db).await?;
+
+    return Ok(watched);
+}
+
+/// Records a product's details as they are now. `changes` lists what changed since
+/// the last revision, and is `None` for a product's first.
+pub async fn record_revision(
+    db: &DatabaseConnection,
+    product_id: i32,
+    details: &ProductDetails,
+    price: Option<Money>,
+    changes: Option<&[&str]>,
+    shrinkflation: bool,
+) -> Result<(), ScraperError> {
+    product_revision::ActiveModel {
+        product_id: Set(product_id),
+        product_title: Set(details.title.clone()),
+        image_url: Set(details.image_url.clone()),
+        size: Set(details.size),
+        unit: Set(details.unit.clone()),
+        quantity: Set(details.quantity),
+        price: Set(price),
+        changes: Set(changes.map(|x| x.join(","))),
+        shrinkflation: Set(shrinkflation),
+        ..Default::default()
+    }.insert(db).await?;
+
+    return Ok(());
+}
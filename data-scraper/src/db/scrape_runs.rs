@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use super::entities::{prelude::*, price_anomaly, quarantined_product, scrape_run, scrape_run_drift, scrape_run_error, supermarkets};
+use crate::{error::ScraperError, price_validation::QualityReport, schema_drift::DriftReport};
+use chrono::{NaiveDateTime, Utc};
+use log::warn;
+use sea_orm::{prelude::DateTimeWithTimeZone, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect, Set};
+
+
+
+/// Records that a supermarket's scrape has started, returning the run's ID
+pub async fn start_scrape_run(db: &DatabaseConnection, supermarket_id: i32) -> Result<i32, ScraperError> {
+    let run = scrape_run::ActiveModel {
+        supermarket_id: Set(supermarket_id),
+        status: Set("running".to_owned()),
+        started_at: Set(Utc::now().into()),
+        ..Default::default()
+    };
+
+    return Ok(run.insert(db).await?.id);
+}
+
+/// Marks a run as finished, storing whatever went wrong along the way. The run failed
+/// if there's a fatal error, and only partly succeeded if anything else failed.
+/// Returns the run's status.
+pub async fn finish_scrape_run(
+    db: &DatabaseConnection,
+    run_id: i32,
+    products_fetched: usize,
+    failures: &[ScraperError],
+    fatal: Option<&ScraperError>,
+) -> Result<&'static str, ScraperError> {
+    let status = match (fatal, failures.is_empty()) {
+        (Some(_), _) => "failed",
+        (None, false) => "partial",
+        (None, true) => "succeeded",
+    };
+
+    let errors = failures.iter().map(|e| (e, false))
+        .chain(fatal.map(|e| (e, true)))
+        .map(|(e, fatal)| {
+            let context = e.context().cloned().unwrap_or_default();
+            scrape_run_error::ActiveModel {
+                run_id: Set(run_id),
+                kind: Set(e.kind().to_owned()),
+                fatal: Set(fatal),
+                department: Set(context.department),
+                page: Set(context.page.map(|x| x as i32)),
+                url: Set(context.url),
+                message: Set(e.to_string()),
+                ..Default::default()
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if !errors.is_empty() {
+        scrape_run_error::Entity::insert_many(errors).exec(db).await?;
+    }
+
+    scrape_run::ActiveModel {
+        id: Set(run_id),
+        status: Set(status.to_owned()),
+        products_fetched: Set(products_fetched as i32),
+        finished_at: Set(Some(Utc::now().into())),
+        ..Default::default()
+    }.update(db).await?;
+
+    return Ok(status);
+}
+
+/// Stores which fields changed shape during a run, and the products set aside because of it
+pub async fn record_drift(db: &DatabaseConnection, run_id: i32, report: &DriftReport) -> Result<(), ScraperError> {
+    if report.is_empty() {
+        return Ok(());
+    }
+
+    let fields = report.fields().map(|(field, change, drift)| {
+        warn!("Schema drift: {} is {} ({} times)", field, change.as_str(), drift.occurrences);
+        scrape_run_drift::ActiveModel {
+            run_id: Set(run_id),
+            field: Set(field.to_owned()),
+            change: Set(change.as_str().to_owned()),
+            occurrences: Set(drift.occurrences as i32),
+            example: Set(drift.example.clone()),
+            ..Default::default()
+        }
+    }).collect::<Vec<_>>();
+
+    if !fields.is_empty() {
+        scrape_run_drift::Entity::insert_many(fields).exec(db).await?;
+    }
+
+    let quarantined = report.quarantined.iter().map(|item| quarantined_product::ActiveModel {
+        run_id: Set(run_id),
+        department: Set(item.department.clone()),
+        page: Set(item.page as i32),
+        sku: Set(item.sku.clone()),
+        error: Set(item.error.clone()),
+        raw: Set(item.raw.to_string()),
+        ..Default::default()
+    }).collect::<Vec<_>>();
+
+    if !quarantined.is_empty() {
+        quarantined_product::Entity::insert_many(quarantined).exec(db).await?;
+    }
+
+    return Ok(());
+}
+
+/// Every supermarket, with when it last finished a scrape that got some data
+pub async fn last_scrapes(db: &DatabaseConnection) -> Result<Vec<(supermarkets::Model, Option<NaiveDateTime>)>, ScraperError> {
+    let finished: HashMap<i32, NaiveDateTime> = ScrapeRun::find()
+        .select_only()
+        .column(scrape_run::Column::SupermarketId)
+        .column_as(scrape_run::Column::FinishedAt.max(), "finished_at")
+        .filter(scrape_run::Column::Status.is_in(["succeeded", "partial"]))
+        .group_by(scrape_run::Column::SupermarketId)
+        .into_tuple::<(i32, Option<DateTimeWithTimeZone>)>()
+        .all(db).await?
+        .into_iter()
+        .filter_map(|(supermarket_id, finished_at)| Some((supermarket_id, finished_at?.naive_utc())))
+        .collect();
+
+    let supermarkets = Supermarkets::find().all(db).await?;
+
+    return Ok(supermarkets.into_iter().map(|x| {
+        let finished_at = finished.get(&x.supermarket_id).copied();
+        (x, finished_at)
+    }).collect());
+}
+
+/// Stores how a run's prices fared against their history
+pub async fn record_price_quality(db: &DatabaseConnection, run_id: i32, report: &QualityReport) -> Result<(), ScraperError> {
+    scrape_run::ActiveModel {
+        id: Set(run_id),
+        prices_accepted: Set(report.accepted as i32),
+        prices_flagged: Set(report.flagged as i32),
+        prices_quarantined: Set(report.quarantined as i32),
+        ..Default::default()
+    }.update(db).await?;
+
+    let anomalies = report.anomalies.iter().map(|x| price_anomaly::ActiveModel {
+        run_id: Set(run_id),
+        product_id: Set(x.product_id),
+        price: Set(x.price),
+        reference_price: Set(x.reference_price),
+        reason: Set(x.reason.to_owned()),
+        quarantined: Set(x.quarantined),
+        ..Default::default()
+    }).collect::<Vec<_>>();
+
+    if !anomalies.is_empty() {
+        price_anomaly::Entity::insert_many(anomalies).exec(db).await?;
+    }
+
+    return Ok(());
+}
@@ -1,28 +1,40 @@
 use super::entities::{prelude::*, supermarkets};
-use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
-use sea_orm::{ColumnTrait, Condition, EntityTrait, QueryFilter};
+use crate::{error::ScraperError, metrics};
+use sea_orm::{sea_query::OnConflict, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set};
 
 
 
-pub async fn check_add_supermarket_info(db: &mut DatabaseConnection, name: &str, brand: &str, location: &str, location_id: &str)-> Result<i32, Box<dyn std::error::Error + Send + Sync>> {
-    let query = Supermarkets::find()
-        .filter(
-            Condition::all()
-                .add(supermarkets::Column::LocationId.contains(location_id))
-                .add(supermarkets::Column::BrandName.contains(brand))
+pub async fn get_supermarkets(db: &DatabaseConnection) -> Result<Vec<supermarkets::Model>, ScraperError> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["get_supermarkets"]).start_timer();
+    return Ok(Supermarkets::find().all(db).await?);
+}
+
+/// Adds a supermarket, or updates the name and location of the one with the same
+/// brand and location ID. Returns it as stored.
+pub async fn upsert_supermarket(db: &DatabaseConnection, name: &str, brand: &str, location: &str, location_id: &str) -> Result<supermarkets::Model, ScraperError> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["upsert_supermarket"]).start_timer();
+    let supermarket = supermarkets::ActiveModel {
+        brand_name: Set(brand.to_owned()),
+        location: Set(location.to_owned()),
+        location_id: Set(location_id.to_owned()),
+        name: Set(name.to_owned()),
+        ..Default::default()
+    };
+
+    Supermarkets::insert(supermarket)
+        .on_conflict(
+            OnConflict::columns([supermarkets::Column::BrandName, supermarkets::Column::LocationId])
+                .update_columns([supermarkets::Column::Name, supermarkets::Column::Location])
+                .to_owned()
         )
-        .one(db).await?;
+        .exec_without_returning(db).await?;
+
+    // Not every backend says which row an upsert touched, so it's looked up again
+    let stored = Supermarkets::find()
+        .filter(supermarkets::Column::BrandName.eq(brand))
+        .filter(supermarkets::Column::LocationId.eq(location_id))
+        .one(db).await?
+        .ok_or_else(|| DbErr::RecordNotFound(format!("supermarket {brand} {location_id}")))?;
 
-    if let Some(found_record) = query {
-        return Ok(found_record.supermarket_id);
-    } else {
-        let new_supermarket = supermarkets::ActiveModel {
-            brand_name: Set(brand.to_owned()),
-            location: Set(location.to_owned()),
-            location_id: Set(location_id.to_owned()),
-            name: Set(name.to_owned()),
-            ..Default::default()
-        };
-        return Ok(new_supermarket.insert(db).await?.supermarket_id);
-    }
-}
\ No newline at end of file
+    return Ok(stored);
+}
@@ -0,0 +1,137 @@
+use std::fmt;
+
+use reqwest::StatusCode;
+use sea_orm::DbErr;
+use thiserror::Error;
+
+/// Where in a scrape something went wrong
+#[derive(Clone, Debug, Default)]
+pub struct ErrorContext {
+    pub department: Option<String>,
+    pub page: Option<usize>,
+    pub url: Option<String>,
+}
+
+impl ErrorContext {
+    pub fn request(department: Option<&str>, page: usize, url: &str) -> Self {
+        ErrorContext {
+            department: department.map(|x| x.to_owned()),
+            page: Some(page),
+            url: Some(url.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(department) = &self.department {
+            write!(f, " [department {department}]")?;
+        }
+        if let Some(page) = self.page {
+            write!(f, " [page {page}]")?;
+        }
+        if let Some(url) = &self.url {
+            write!(f, " [{url}]")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ScraperError {
+    /// The request failed, or the server answered with an error status
+    #[error("HTTP request failed{context}: {message}")]
+    Http {
+        status: Option<StatusCode>,
+        /// Connection failures and timeouts, as opposed to the server refusing the request
+        network: bool,
+        message: String,
+        context: ErrorContext,
+    },
+    /// The server kept telling us to back off, however long we waited. Not worth
+    /// retrying straight away, as the limiter already has.
+    #[error("Still rate limited after {attempts} attempts{context}")]
+    RateLimitExhausted {
+        attempts: usize,
+        context: ErrorContext,
+    },
+    /// The response wasn't the JSON we expected
+    #[error("Couldn't decode response{context}: {source}")]
+    Decode {
+        source: serde_json::Error,
+        context: ErrorContext,
+    },
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+    #[error("Config error: {0}")]
+    Config(String),
+    /// A value inside an otherwise valid response couldn't be understood
+    #[error("Couldn't parse {what} {value:?}: {reason}")]
+    Parse {
+        what: &'static str,
+        value: String,
+        reason: String,
+    },
+    #[error("Couldn't write scraped data: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Couldn't store image: {0}")]
+    Storage(#[from] image_store::Error),
+}
+
+impl ScraperError {
+    pub fn http(error: reqwest::Error, context: ErrorContext) -> Self {
+        ScraperError::Http {
+            status: error.status(),
+            network: error.is_connect() || error.is_timeout() || error.is_request() || error.is_body(),
+            message: error.to_string(),
+            context,
+        }
+    }
+
+    pub fn status(status: StatusCode, context: ErrorContext) -> Self {
+        ScraperError::Http {
+            status: Some(status),
+            network: false,
+            message: format!("server returned {status}"),
+            context,
+        }
+    }
+
+    /// Whether trying again later might work: network trouble and server side
+    /// failures, but not bad requests, bad data or our own bugs
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ScraperError::Http { status: Some(status), .. } => {
+                status.is_server_error()
+                    || *status == StatusCode::TOO_MANY_REQUESTS
+                    || *status == StatusCode::REQUEST_TIMEOUT
+            }
+            ScraperError::Http { network, .. } => *network,
+            ScraperError::Db(DbErr::Conn(_) | DbErr::ConnectionAcquire(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Short name stored with scrape runs
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ScraperError::Http { .. } => "http",
+            ScraperError::RateLimitExhausted { .. } => "rate_limited",
+            ScraperError::Decode { .. } => "decode",
+            ScraperError::Db(_) => "db",
+            ScraperError::Config(_) => "config",
+            ScraperError::Parse { .. } => "parse",
+            ScraperError::Io(_) => "io",
+            ScraperError::Storage(_) => "storage",
+        }
+    }
+
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            ScraperError::Http { context, .. }
+            | ScraperError::RateLimitExhausted { context, .. }
+            | ScraperError::Decode { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+}
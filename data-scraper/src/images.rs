@@ -0,0 +1,339 @@
+use std::{io::Cursor, time::Duration};
+
+use chrono::Utc;
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, ImageError, ImageReader, Limits, RgbImage};
+use image_store::{original_key, thumbnail_key, ImageStore, THUMBNAIL_SIZES};
+use log::{info, warn};
+use reqwest::{Client, StatusCode};
+use sea_orm::{DatabaseConnection, Set};
+use sha2::{Digest, Sha256};
+
+use crate::{config::ImageConfig, db::{entities::product_image, get_image_by_hash, get_unmirrored_image_urls, save_image_source, save_product_image}, error::{ErrorContext, ScraperError}, metrics, supermarkets::fetch_concurrently};
+
+const THUMBNAIL_QUALITY: u8 = 85;
+
+/// Far bigger than any product photo, but small enough that a file claiming to be
+/// huge can't run the scraper out of memory
+const MAX_DIMENSION: u32 = 6000;
+const MAX_DECODE_BYTES: u64 = 200 * 1024 * 1024;
+
+/// Largest file downloaded, so a huge or endless response isn't read into memory
+const MAX_DOWNLOAD_BYTES: usize = 20 * 1024 * 1024;
+
+/// Copies product images from the supermarkets' CDNs into the image store
+pub struct ImageMirror {
+    client: Client,
+    store: ImageStore,
+    settings: &'static ImageConfig,
+}
+
+/// What's at an image URL
+enum Download {
+    Image(Vec<u8>),
+    Missing,
+    /// Bigger than `MAX_DOWNLOAD_BYTES`
+    TooBig,
+}
+
+/// A downloaded image, decoded and shrunk to each of `THUMBNAIL_SIZES`
+struct Thumbnails {
+    extension: &'static str,
+    width: u32,
+    height: u32,
+    thumbnails: Vec<(u32, Vec<u8>)>,
+}
+
+impl ImageMirror {
+    pub fn new(settings: &'static ImageConfig) -> Result<Self, ScraperError> {
+        let store = ImageStore::open(&settings.store)
+            .map_err(|e| ScraperError::Config(format!("images.store {:?} can't be opened: {e}", settings.store)))?;
+        let client = reqwest::ClientBuilder::new()
+            .timeout(Duration::from_secs(settings.request_timeout_secs))
+            .build()
+            .map_err(|e| ScraperError::Config(format!("Couldn't build the image HTTP client: {e}")))?;
+
+        Ok(ImageMirror { client, store, settings })
+    }
+
+    /// Downloads the images of products whose image URL hasn't been seen before.
+    /// A file that's already stored under another URL is linked rather than stored
+    /// again. URLs that fail for a reason that might go away are tried next run, but
+    /// ones that are missing or aren't images are remembered and left alone.
+    pub async fn mirror(&self, db: &DatabaseConnection) -> Result<(), ScraperError> {
+        let urls = get_unmirrored_image_urls(db, self.settings.max_per_run).await?;
+        if urls.is_empty() {
+            return Ok(());
+        }
+
+        info!("Mirroring {} product images", urls.len());
+        let downloads = fetch_concurrently(urls, self.settings.workers, |url| async move {
+            self.download(&url).await
+        }).await;
+
+        for (url, download) in downloads {
+            let bytes = match download {
+                Ok(Download::Image(bytes)) => bytes,
+                Ok(Download::Missing) => {
+                    warn!("No image at {}", url);
+                    save_image_source(db, url, None).await?;
+                    metrics::IMAGES_MIRRORED.with_label_values(&["not_found"]).inc();
+                    continue;
+                }
+                Ok(Download::TooBig) => {
+                    warn!("{} is over {} bytes, too big to be a product image", url, MAX_DOWNLOAD_BYTES);
+                    save_image_source(db, url, None).await?;
+                    metrics::IMAGES_MIRRORED.with_label_values(&["too_big"]).inc();
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Couldn't download image, trying again next run: {}", e);
+                    metrics::IMAGES_MIRRORED.with_label_values(&["failed"]).inc();
+                    continue;
+                }
+            };
+
+            let hash = hex::encode(Sha256::digest(&bytes));
+            if let Some(existing) = get_image_by_hash(db, &hash).await? {
+                save_image_source(db, url, Some(existing.id)).await?;
+                metrics::IMAGES_MIRRORED.with_label_values(&["duplicate"]).inc();
+                continue;
+            }
+
+            let decoded = tokio::task::spawn_blocking(move || {
+                make_thumbnails(&bytes).map(|made| (bytes, made)).map_err(|e| e.to_string())
+            })
+            .await
+            // A decoder that panics on a file has rejected it as surely as one that errors
+            .unwrap_or_else(|e| Err(format!("decoding panicked: {e}")));
+
+            let (bytes, made) = match decoded {
+                Ok(decoded) => decoded,
+                Err(e) => {
+                    warn!("{} isn't an image we can read: {}", url, e);
+                    save_image_source(db, url, None).await?;
+                    metrics::IMAGES_MIRRORED.with_label_values(&["undecodable"]).inc();
+                    continue;
+                }
+            };
+
+            let byte_size = bytes.len() as i32;
+            self.store.put(&original_key(&hash, made.extension), bytes).await?;
+            for (size, thumbnail) in made.thumbnails {
+                self.store.put(&thumbnail_key(&hash, size), thumbnail).await?;
+            }
+
+            let image = save_product_image(db, product_image::ActiveModel {
+                content_hash: Set(hash),
+                format: Set(made.extension.to_owned()),
+                width: Set(made.width as i32),
+                height: Set(made.height as i32),
+                byte_size: Set(byte_size),
+                created_at: Set(Utc::now().into()),
+                ..Default::default()
+            }).await?;
+            save_image_source(db, url, Some(image.id)).await?;
+            metrics::IMAGES_MIRRORED.with_label_values(&["stored"]).inc();
+        }
+
+        return Ok(());
+    }
+
+    /// The image's bytes, read no further than `MAX_DOWNLOAD_BYTES` whether or not the
+    /// server says how long it is
+    async fn download(&self, url: &str) -> Result<Download, ScraperError> {
+        let context = || ErrorContext { url: Some(url.to_owned()), ..Default::default() };
+
+        let mut response = self.client.get(url).send().await.map_err(|e| ScraperError::http(e, context()))?;
+        match response.status() {
+            StatusCode::NOT_FOUND | StatusCode::GONE => return Ok(Download::Missing),
+            status if !status.is_success() => return Err(ScraperError::status(status, context())),
+            _ => {}
+        }
+        if response.content_length().is_some_and(|x| x > MAX_DOWNLOAD_BYTES as u64) {
+            return Ok(Download::TooBig);
+        }
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| ScraperError::http(e, context()))? {
+            if bytes.len() + chunk.len() > MAX_DOWNLOAD_BYTES {
+                return Ok(Download::TooBig);
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        return Ok(Download::Image(bytes));
+    }
+}
+
+fn make_thumbnails(bytes: &[u8]) -> Result<Thumbnails, ImageError> {
+    let format = image::guess_format(bytes)?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_BYTES);
+
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+    let image = reader.decode()?;
+
+    let mut thumbnails = Vec::new();
+    for size in THUMBNAIL_SIZES {
+        // Never made bigger, only smaller
+        let resized = match image.width() > size || image.height() > size {
+            true => image.resize(size, size, FilterType::Lanczos3),
+            false => image.clone(),
+        };
+
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, THUMBNAIL_QUALITY).encode_image(&on_white(resized))?;
+        thumbnails.push((size, jpeg));
+    }
+
+    Ok(Thumbnails {
+        extension: format.extensions_str().first().copied().unwrap_or("img"),
+        width: image.width(),
+        height: image.height(),
+        thumbnails,
+    })
+}
+
+/// JPEG has no transparency, so transparent parts of PNGs and WebPs are filled in
+/// white, like the product pages they came from, instead of black
+fn on_white(image: DynamicImage) -> RgbImage {
+    let rgba = image.into_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |channel: u8| ((channel as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+        image::Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::Infallible, net::SocketAddr};
+
+    use axum::{body::{Bytes, StreamBody}, http::StatusCode as AxumStatus, routing::get, Router};
+    use futures::stream;
+    use image::{ImageFormat, RgbaImage};
+    use sea_orm::{ActiveModelTrait, EntityTrait, PaginatorTrait};
+
+    use crate::db::{connect_for_tests, entities::{prelude::*, product_db}};
+
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(width, height, image::Rgba([200, 40, 40, 128]))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    /// Serves the same picture at two URLs, something that isn't a picture, and nothing
+    async fn start_cdn() -> String {
+        let photo = png(600, 300);
+        let app = Router::new()
+            .route("/a.png", get({ let photo = photo.clone(); || async move { photo } }))
+            .route("/b.png", get(|| async move { photo }))
+            .route("/broken.png", get(|| async { "<html>Not a picture</html>" }))
+            .route("/gone.png", get(|| async { AxumStatus::NOT_FOUND }));
+
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(app.into_make_service());
+        let base_url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+
+        base_url
+    }
+
+    async fn add_product(db: &DatabaseConnection, title: &str, image_url: String) {
+        product_db::ActiveModel {
+            product_title: Set(title.to_owned()),
+            image_url: Set(Some(image_url)),
+            quantity: Set(1),
+            first_index_timestamp: Set(Utc::now().into()),
+            ..Default::default()
+        }.insert(db).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stores_each_picture_once_with_its_thumbnails() {
+        let base_url = start_cdn().await;
+        let db = connect_for_tests().await;
+        for name in ["a", "b", "broken", "gone"] {
+            add_product(&db, name, format!("{base_url}/{name}.png")).await;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let settings = Box::leak(Box::new(ImageConfig { store: dir.path().display().to_string(), ..ImageConfig::default() }));
+        let mirror = ImageMirror::new(settings).unwrap();
+        mirror.mirror(&db).await.unwrap();
+
+        let images = ProductImage::find().all(&db).await.unwrap();
+        assert_eq!(images.len(), 1, "Both URLs of the same picture share one copy");
+        let image = &images[0];
+        assert_eq!((image.format.as_str(), image.width, image.height), ("png", 600, 300));
+
+        let sources = ProductImageSource::find().all(&db).await.unwrap();
+        let image_of = |name: &str| sources.iter().find(|x| x.url.ends_with(&format!("/{name}.png"))).unwrap().image_id;
+        assert_eq!(image_of("a"), Some(image.id));
+        assert_eq!(image_of("b"), Some(image.id));
+        // Remembered, so they aren't downloaded every run
+        assert_eq!(image_of("broken"), None);
+        assert_eq!(image_of("gone"), None);
+
+        let store = ImageStore::open(&settings.store).unwrap();
+        let original = store.get(&original_key(&image.content_hash, "png")).await.unwrap().unwrap();
+        assert_eq!(original, png(600, 300));
+        for (size, height) in [(160, 80), (480, 240)] {
+            let thumbnail = store.get(&thumbnail_key(&image.content_hash, size)).await.unwrap().unwrap();
+            let thumbnail = image::load_from_memory_with_format(&thumbnail, ImageFormat::Jpeg).unwrap();
+            assert_eq!((thumbnail.width(), thumbnail.height()), (size, height));
+            // Transparency is filled with white rather than black
+            assert!(thumbnail.to_rgb8().get_pixel(0, 0).0.iter().all(|x| *x > 100));
+        }
+
+        // Nothing is left to do the next time round
+        mirror.mirror(&db).await.unwrap();
+        assert_eq!(ProductImageSource::find().count(&db).await.unwrap(), 4);
+        assert_eq!(ProductImage::find().count(&db).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn stops_downloading_files_too_big_for_a_picture() {
+        let chunk = Bytes::from(vec![0u8; 64 * 1024]);
+        let app = Router::new()
+            .route("/declared.png", get(|| async { vec![0u8; MAX_DOWNLOAD_BYTES + 1] }))
+            // Sent in chunks with no length, and never ends
+            .route("/endless.png", get(|| async move {
+                StreamBody::new(stream::repeat(Ok::<_, Infallible>(chunk)))
+            }))
+            .route("/fine.png", get(|| async { png(10, 10) }));
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(app.into_make_service());
+        let base_url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+
+        let dir = tempfile::tempdir().unwrap();
+        let settings = Box::leak(Box::new(ImageConfig { store: dir.path().display().to_string(), ..ImageConfig::default() }));
+        let mirror = ImageMirror::new(settings).unwrap();
+
+        assert!(matches!(mirror.download(&format!("{base_url}/declared.png")).await, Ok(Download::TooBig)));
+        assert!(matches!(mirror.download(&format!("{base_url}/endless.png")).await, Ok(Download::TooBig)));
+        assert!(matches!(mirror.download(&format!("{base_url}/fine.png")).await, Ok(Download::Image(bytes)) if bytes == png(10, 10)));
+    }
+
+    #[test]
+    fn small_pictures_arent_enlarged() {
+        let made = make_thumbnails(&png(100, 50)).unwrap();
+
+        assert_eq!(made.extension, "png");
+        for (_, thumbnail) in made.thumbnails {
+            let thumbnail = image::load_from_memory(&thumbnail).unwrap();
+            assert_eq!((thumbnail.width(), thumbnail.height()), (100, 50));
+        }
+    }
+
+    #[test]
+    fn refuses_pictures_too_big_to_decode() {
+        assert!(make_thumbnails(&png(MAX_DIMENSION + 1, 1)).is_err());
+    }
+}
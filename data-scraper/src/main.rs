@@ -3,23 +3,71 @@ use std::time::Duration;
 use log::{error, info};
 use tokio::fs;
 
-use crate::{supermarkets::super_fetch, config::DATA_OUT_DIR};
+use crate::{supermarkets::super_fetch, error::ScraperError, images::ImageMirror, price_index::update_price_index, product_index::ProductIndex, supermarket_registry::SupermarketRegistry};
 
 mod config;
 mod db;
+mod error;
+mod images;
+mod metrics;
+mod price_index;
+mod price_validation;
+mod product_index;
+mod product_revisions;
+mod rate_limit;
+mod schema_drift;
+mod server;
+mod supermarket_registry;
 mod supermarkets;
 
+#[cfg(test)]
+#[path = "../examples/mock_countdown/mock.rs"]
+mod mock_countdown;
+
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    pretty_flexible_env_logger::try_init_with("INFO").unwrap();
+async fn main() -> Result<(), ScraperError> {
+    pretty_flexible_env_logger::try_init_with("INFO")
+        .map_err(|e| ScraperError::Config(format!("Couldn't start logging: {e}")))?;
+
+    let config = config::load()?;
+
+    fs::create_dir_all(&config.output_dir).await?;
 
-    fs::create_dir_all(DATA_OUT_DIR).await?;
+    let mut db = db::connect().await?;
 
-    let mut db = db::connect().await;
+    tokio::spawn(server::serve(config.metrics_addr, db.clone()));
 
     info!("Starting app");
+    let mut products = ProductIndex::load(&mut db).await?;
+    let mut supermarkets = SupermarketRegistry::load(&db).await?;
+    let images = match config.images.enabled {
+        true => Some(ImageMirror::new(&config.images)?),
+        false => None,
+    };
     loop {
-        super_fetch(&mut db).await?;
-        tokio::time::sleep(Duration::from_secs(30)).await;
+        // A scrape that failed for a reason that might go away gets another go next round
+        if let Err(e) = super_fetch(&mut db, &mut products, &mut supermarkets).await {
+            if !e.is_transient() {
+                return Err(e);
+            }
+            error!("Scrape failed, trying again next round: {}", e);
+        }
+        // Products are still fine without pictures, so this never stops the scraper
+        if let Some(images) = &images {
+            if let Err(e) = images.mirror(&db).await {
+                error!("Image mirroring failed, trying again next round: {}", e);
+            }
+        }
+        if let Err(e) = db::maintain_price_storage(&db).await {
+            if !e.is_transient() {
+                return Err(e);
+            }
+            error!("Price storage maintenance failed, trying again next round: {}", e);
+        }
+        // The index can always be caught up on later, so this never stops the scraper
+        if let Err(e) = update_price_index(&mut db).await {
+            error!("Price index update failed, trying again next round: {}", e);
+        }
+        tokio::time::sleep(Duration::from_secs(config.scrape_interval_secs)).await;
     }
 }
@@ -0,0 +1,93 @@
+use once_cell::sync::Lazy;
+use prometheus::{register_histogram_vec, register_int_counter_vec, Encoder, HistogramVec, IntCounterVec, TextEncoder};
+
+pub static PRODUCTS_SCRAPED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("scraper_products_scraped_total", "Products read from supermarket APIs", &["supermarket"])
+        .expect("Metric can be registered")
+});
+
+pub static NEW_PRODUCTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("scraper_new_products_total", "Products seen for the first time", &["supermarket"])
+        .expect("Metric can be registered")
+});
+
+pub static PRICE_ROWS_WRITTEN: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("scraper_price_rows_written_total", "Prices stored", &["supermarket"])
+        .expect("Metric can be registered")
+});
+
+/// Labelled with how it went: "saved", "not_found" or "failed"
+pub static PRODUCT_DETAILS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("scraper_product_details_total", "Product pages fetched for ingredients and nutrition", &["supermarket", "result"])
+        .expect("Metric can be registered")
+});
+
+/// Runs of product detail fetching cut short, e.g. by the database going away. The
+/// scrape they were part of still succeeds.
+pub static DETAIL_RUN_FAILURES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("scraper_detail_run_failures_total", "Product detail runs that failed part way through", &["supermarket"])
+        .expect("Metric can be registered")
+});
+
+/// Labelled with how it went: "stored", "duplicate", "not_found", "too_big", "undecodable" or "failed"
+pub static IMAGES_MIRRORED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("scraper_images_mirrored_total", "Product images downloaded into the image store", &["result"])
+        .expect("Metric can be registered")
+});
+
+/// Labelled with what was done with the price: "flagged" or "quarantined"
+pub static PRICE_ANOMALIES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("scraper_price_anomalies_total", "Prices that didn't fit the product's history", &["supermarket", "action"])
+        .expect("Metric can be registered")
+});
+
+/// Labelled with the response status, or "error" if there wasn't one
+pub static HTTP_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "scraper_http_request_duration_seconds",
+        "Time taken by requests to supermarket APIs",
+        &["supermarket", "status"],
+        vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]
+    ).expect("Metric can be registered")
+});
+
+/// Labelled with why: "rate_limited" or "transient"
+pub static HTTP_RETRIES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("scraper_http_retries_total", "Requests tried again", &["supermarket", "reason"])
+        .expect("Metric can be registered")
+});
+
+/// Labelled with "page" when a whole response couldn't be read, or "product" when one was quarantined
+pub static PARSE_FAILURES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("scraper_parse_failures_total", "Responses or products that couldn't be read", &["supermarket", "what"])
+        .expect("Metric can be registered")
+});
+
+/// Labelled with the scrape run's status
+pub static SCRAPE_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "scraper_scrape_duration_seconds",
+        "Time taken to scrape a supermarket",
+        &["supermarket", "status"],
+        vec![10.0, 30.0, 60.0, 300.0, 600.0, 1800.0, 3600.0, 7200.0]
+    ).expect("Metric can be registered")
+});
+
+pub static DB_QUERY_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "scraper_db_query_duration_seconds",
+        "Time taken by database queries",
+        &["query"],
+        vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 30.0]
+    ).expect("Metric can be registered")
+});
+
+/// Everything registered, in the Prometheus text format
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        log::error!("Couldn't encode metrics: {}", e);
+    }
+
+    return String::from_utf8(buffer).unwrap_or_default();
+}
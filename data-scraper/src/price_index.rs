@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Utc};
+use log::info;
+use sea_orm::{
+    prelude::DateTimeWithTimeZone, sea_query::{Expr, OnConflict}, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
+    FromQueryResult, QueryFilter, QuerySelect, Set, Statement,
+};
+
+use crate::{db::entities::{price_daily, price_index, prelude::*}, error::ScraperError, metrics};
+
+/// Granularity that the price index is computed at
+#[derive(Debug, Clone, Copy)]
+pub enum IndexPeriod {
+    Daily,
+    Weekly,
+}
+
+impl IndexPeriod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IndexPeriod::Daily => "daily",
+            IndexPeriod::Weekly => "weekly",
+        }
+    }
+
+    /// The first day of the period containing `date`, weeks start on Monday
+    fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            IndexPeriod::Daily => date,
+            IndexPeriod::Weekly => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+        }
+    }
+
+    /// The aggregate holding each product's prices for a period of this length
+    fn aggregate(&self) -> &'static str {
+        match self {
+            IndexPeriod::Daily => "price_daily",
+            IndexPeriod::Weekly => "price_weekly",
+        }
+    }
+
+    fn length(&self) -> Duration {
+        match self {
+            IndexPeriod::Daily => Duration::days(1),
+            IndexPeriod::Weekly => Duration::weeks(1),
+        }
+    }
+}
+
+#[derive(Debug, FromQueryResult)]
+struct PeriodPrice {
+    product_id: i32,
+    supermarket_id: i32,
+    department: Option<String>,
+    brand_name: String,
+    price: f64,
+}
+
+/// Running totals for one scope (overall, a department or a chain) of the index
+#[derive(Debug, Default)]
+struct IndexAccumulator {
+    basket_size: usize,
+    matched_items: usize,
+    base_total: f64,
+    current_total: f64,
+    log_relative_total: f64,
+    relative_total: f64,
+    inverse_relative_total: f64,
+}
+
+impl IndexAccumulator {
+    fn add(&mut self, base_price: f64, current_price: Option<f64>) {
+        self.basket_size += 1;
+
+        // Matched-model: items missing from this period drop out of both sides
+        if let Some(current_price) = current_price {
+            self.matched_items += 1;
+            self.base_total += base_price;
+            self.current_total += current_price;
+            self.log_relative_total += (current_price / base_price).ln();
+            self.relative_total += current_price / base_price;
+            self.inverse_relative_total += base_price / current_price;
+        }
+    }
+
+    fn laspeyres(&self) -> f64 {
+        100.0 * self.current_total / self.base_total
+    }
+
+    fn jevons(&self) -> f64 {
+        100.0 * (self.log_relative_total / self.matched_items as f64).exp()
+    }
+
+    /// Spending the same on every product, the Laspeyres index is the mean of the price
+    /// relatives and the Paasche index their harmonic mean
+    fn fisher(&self) -> f64 {
+        let laspeyres = self.relative_total / self.matched_items as f64;
+        let paasche = self.matched_items as f64 / self.inverse_relative_total;
+        100.0 * (laspeyres * paasche).sqrt()
+    }
+}
+
+/// Computes the basket price index for every completed period that hasn't been
+/// computed yet.
+///
+/// The basket is fixed to the products seen in the first period of data, with
+/// one unit of each, so the Laspeyres index is the basket's cost relative to the
+/// base period. The Jevons column is the geometric mean of the price relatives,
+/// which is how Stats NZ aggregates the elementary levels of the Food Price Index.
+/// The Fisher column is the geometric mean of the Laspeyres and Paasche indexes of
+/// a basket with the same amount spent on each product, as we don't know how much
+/// of anything is bought. A period computed again replaces its old rows.
+pub async fn update_price_index(db: &mut DatabaseConnection) -> Result<(), ScraperError> {
+    for period in [IndexPeriod::Daily, IndexPeriod::Weekly] {
+        update_period(db, period).await?;
+    }
+
+    Ok(())
+}
+
+async fn update_period(db: &mut DatabaseConnection, period: IndexPeriod) -> Result<(), ScraperError> {
+    let first_timestamp: Option<DateTimeWithTimeZone> = PriceDaily::find()
+        .select_only()
+        .column_as(price_daily::Column::Bucket.min(), "first")
+        .into_tuple()
+        .one(db).await?
+        .flatten();
+
+    let Some(first_timestamp) = first_timestamp else {
+        return Ok(());
+    };
+
+    let base_period = period.start_of(first_timestamp.naive_utc().date());
+    let current_period = period.start_of(Utc::now().naive_utc().date());
+
+    let last_computed: Option<NaiveDate> = PriceIndex::find()
+        .select_only()
+        .column_as(price_index::Column::PeriodStart.max(), "last")
+        .filter(price_index::Column::PeriodType.eq(period.name()))
+        .into_tuple()
+        .one(db).await?
+        .flatten();
+
+    let mut period_start = last_computed.map_or(base_period, |x| x + period.length());
+    if period_start >= current_period {
+        return Ok(());
+    }
+
+    let base_prices = get_period_prices(db, base_period, period).await?;
+
+    while period_start < current_period {
+        let period_prices = get_period_prices(db, period_start, period).await?;
+        let current_prices: HashMap<(i32, i32), f64> = period_prices
+            .into_iter()
+            .map(|x| ((x.product_id, x.supermarket_id), x.price))
+            .collect();
+
+        let mut scopes: HashMap<(Option<String>, Option<String>), IndexAccumulator> = HashMap::new();
+        for base in &base_prices {
+            let current_price = current_prices.get(&(base.product_id, base.supermarket_id)).copied();
+
+            scopes.entry((None, None)).or_default().add(base.price, current_price);
+            if let Some(department) = &base.department {
+                scopes.entry((Some(department.clone()), None)).or_default().add(base.price, current_price);
+            }
+            scopes.entry((None, Some(base.brand_name.clone()))).or_default().add(base.price, current_price);
+        }
+
+        for ((department, brand_name), accumulator) in scopes {
+            if accumulator.matched_items == 0 {
+                continue;
+            }
+
+            let row = price_index::ActiveModel {
+                period_type: Set(period.name().to_owned()),
+                period_start: Set(period_start),
+                department: Set(department),
+                brand_name: Set(brand_name),
+                laspeyres: Set(accumulator.laspeyres()),
+                jevons: Set(accumulator.jevons()),
+                fisher: Set(accumulator.fisher()),
+                basket_size: Set(accumulator.basket_size as i32),
+                matched_items: Set(accumulator.matched_items as i32),
+                computed_at: Set(Utc::now().into()),
+                ..Default::default()
+            };
+            PriceIndex::insert(row).on_conflict(scope_conflict()).exec(db).await?;
+        }
+
+        info!("Computed {} price index for {}", period.name(), period_start);
+        period_start += period.length();
+    }
+
+    Ok(())
+}
+
+/// A row for the same period and scope as one already stored, which it replaces
+fn scope_conflict() -> OnConflict {
+    // Written out like the unique index's expressions, as a bound '' wouldn't match them
+    let or_empty = |column: &str| Expr::cust(format!("COALESCE({column}, '')"));
+
+    OnConflict::new()
+        .exprs([
+            Expr::col(price_index::Column::PeriodType).into(),
+            Expr::col(price_index::Column::PeriodStart).into(),
+            or_empty("department"),
+            or_empty("brand_name"),
+        ])
+        .update_columns([
+            price_index::Column::Laspeyres,
+            price_index::Column::Jevons,
+            price_index::Column::Fisher,
+            price_index::Column::BasketSize,
+            price_index::Column::MatchedItems,
+            price_index::Column::ComputedAt,
+        ])
+        .to_owned()
+}
+
+/// Average price of every product at every store over one period, from the
+/// period's aggregate
+async fn get_period_prices(db: &mut DatabaseConnection, period_start: NaiveDate, period: IndexPeriod) -> Result<Vec<PeriodPrice>, ScraperError> {
+    let start = period_start.and_time(NaiveTime::MIN).and_utc();
+
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["period_prices"]).start_timer();
+    let prices = PeriodPrice::find_by_statement(Statement::from_sql_and_values(
+        db.get_database_backend(),
+        &format!(
+            r#"
+            SELECT a.product_id, a.supermarket_id, p.department, s.brand_name, CAST(a.avg_price AS double precision) AS price
+            FROM {} a
+            JOIN product_db p ON p.product_id = a.product_id
+            JOIN supermarkets s ON s.supermarket_id = a.supermarket_id
+            WHERE a.bucket = $1
+            "#,
+            period.aggregate(),
+        ),
+        [start.into()],
+    ))
+    .all(db).await?;
+
+    Ok(prices)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use sea_orm::{EntityTrait, PaginatorTrait};
+
+    use crate::db::connect_for_tests;
+
+    use super::*;
+
+    #[test]
+    fn fisher_sits_between_the_equal_spend_laspeyres_and_paasche() {
+        let mut accumulator = IndexAccumulator::default();
+        // Doubled and halved
+        accumulator.add(1.0, Some(2.0));
+        accumulator.add(4.0, Some(2.0));
+        // Gone from the shelves, so left out
+        accumulator.add(3.0, None);
+
+        assert_eq!(accumulator.basket_size, 3);
+        assert_eq!(accumulator.matched_items, 2);
+        assert!((accumulator.laspeyres() - 80.0).abs() < 1e-9);
+        assert!((accumulator.jevons() - 100.0).abs() < 1e-9);
+        // sqrt(125 * 80)
+        assert!((accumulator.fisher() - 100.0).abs() < 1e-9);
+
+        accumulator.add(2.0, Some(3.0));
+        let relatives = [2.0, 0.5, 1.5];
+        let arithmetic = 100.0 * relatives.iter().sum::<f64>() / 3.0;
+        let harmonic = 100.0 * 3.0 / relatives.iter().map(|x| 1.0 / x).sum::<f64>();
+        assert!(harmonic < accumulator.fisher() && accumulator.fisher() < arithmetic);
+    }
+
+    fn row(department: Option<&str>, laspeyres: f64) -> price_index::ActiveModel {
+        price_index::ActiveModel {
+            period_type: Set(String::from("daily")),
+            period_start: Set(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()),
+            department: Set(department.map(|x| x.to_owned())),
+            brand_name: Set(None),
+            laspeyres: Set(laspeyres),
+            jevons: Set(laspeyres),
+            fisher: Set(laspeyres),
+            basket_size: Set(1),
+            matched_items: Set(1),
+            computed_at: Set(Utc::now().into()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn computing_a_period_again_replaces_it() {
+        let db = connect_for_tests().await;
+
+        for (department, laspeyres) in [(None, 100.0), (Some("pantry"), 100.0), (None, 110.0), (Some("pantry"), 90.0)] {
+            PriceIndex::insert(row(department, laspeyres)).on_conflict(scope_conflict()).exec(&db).await.unwrap();
+        }
+
+        assert_eq!(PriceIndex::find().count(&db).await.unwrap(), 2);
+        let overall = PriceIndex::find()
+            .filter(price_index::Column::Department.is_null())
+            .one(&db).await.unwrap().unwrap();
+        assert_eq!(overall.laspeyres, 110.0);
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+use money::Money;
+use sea_orm::{
+    prelude::DateTimeWithTimeZone, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, FromQueryResult,
+    QueryFilter, QuerySelect, Statement,
+};
+
+use crate::{db::entities::{prelude::*, supermarket_price}, error::ScraperError, metrics};
+
+/// How far back a product's prices count as its history
+const HISTORY_WINDOW_DAYS: i32 = 90;
+
+/// Prices seen before the history is trusted enough to judge a new one
+const MIN_HISTORY_SAMPLES: i64 = 3;
+
+/// Nothing in a supermarket really costs less than this
+const MIN_PLAUSIBLE_PRICE: Money = Money::from_cents(5);
+
+/// Specials can halve a price, so only bigger moves than this are suspicious.
+/// They're still stored, but kept out of graphs and alerts.
+const FLAG_RATIO: f64 = 4.0;
+
+/// Moves this big are almost always a bad parse or a store glitch, and aren't stored
+const QUARANTINE_RATIO: f64 = 10.0;
+
+/// Recent unflagged prices of one product at one store
+#[derive(Debug, FromQueryResult)]
+pub struct PriceHistory {
+    pub product_id: i32,
+    pub median: Money,
+    pub samples: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct PriceAnomaly {
+    pub product_id: i32,
+    pub price: Money,
+    /// What the price was expected to be near, if there was enough history to say
+    pub reference_price: Option<Money>,
+    pub reason: &'static str,
+    pub quarantined: bool,
+}
+
+/// How a run's prices fared against their history
+#[derive(Clone, Debug, Default)]
+pub struct QualityReport {
+    pub accepted: usize,
+    pub flagged: usize,
+    pub quarantined: usize,
+    pub anomalies: Vec<PriceAnomaly>,
+}
+
+impl QualityReport {
+    /// Checks a newly scraped price, returning the anomaly if it is one
+    pub fn check(&mut self, supermarket: &str, product_id: i32, price: Money, history: Option<&PriceHistory>) -> Option<PriceAnomaly> {
+        let anomaly = find_anomaly(product_id, price, history);
+
+        match &anomaly {
+            None => self.accepted += 1,
+            Some(x) if x.quarantined => self.quarantined += 1,
+            Some(_) => self.flagged += 1,
+        }
+        if let Some(anomaly) = &anomaly {
+            let action = if anomaly.quarantined { "quarantined" } else { "flagged" };
+            metrics::PRICE_ANOMALIES.with_label_values(&[supermarket, action]).inc();
+            self.anomalies.push(anomaly.clone());
+        }
+
+        anomaly
+    }
+}
+
+fn find_anomaly(product_id: i32, price: Money, history: Option<&PriceHistory>) -> Option<PriceAnomaly> {
+    let anomaly = |reason, reference_price, quarantined| Some(PriceAnomaly {
+        product_id,
+        price,
+        reference_price,
+        reason,
+        quarantined,
+    });
+
+    if price < MIN_PLAUSIBLE_PRICE {
+        return anomaly("implausible price", None, true);
+    }
+
+    let Some(history) = history.filter(|x| x.samples >= MIN_HISTORY_SAMPLES) else {
+        return None;
+    };
+    let Some(ratio) = price.ratio(history.median).filter(|_| history.median > Money::ZERO) else {
+        return None;
+    };
+
+    let reference = Some(history.median);
+
+    if ratio >= QUARANTINE_RATIO {
+        return anomaly("far above usual price", reference, true);
+    }
+    if ratio <= 1.0 / QUARANTINE_RATIO {
+        return anomaly("far below usual price", reference, true);
+    }
+    if ratio >= FLAG_RATIO {
+        return anomaly("well above usual price", reference, false);
+    }
+    if ratio <= 1.0 / FLAG_RATIO {
+        return anomaly("well below usual price", reference, false);
+    }
+
+    None
+}
+
+/// The recent price history of everything a supermarket sells, by product ID
+pub async fn load_price_history(db: &DatabaseConnection, supermarket_id: i32) -> Result<HashMap<i32, PriceHistory>, ScraperError> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["price_history"]).start_timer();
+
+    if db.get_database_backend() != DbBackend::Postgres {
+        return load_price_history_in_memory(db, supermarket_id).await;
+    }
+
+    let history = PriceHistory::find_by_statement(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        SELECT product_id, percentile_cont(0.5) WITHIN GROUP (ORDER BY price)::numeric(12, 2) AS median, COUNT(*) AS samples
+        FROM supermarket_price
+        WHERE supermarket_id = $1
+            AND NOT flagged
+            AND price > 0
+            AND timestamp >= NOW() - make_interval(days => $2)
+        GROUP BY product_id
+        "#,
+        [supermarket_id.into(), HISTORY_WINDOW_DAYS.into()],
+    ))
+    .all(db).await?;
+
+    return Ok(history.into_iter().map(|x| (x.product_id, x)).collect());
+}
+
+/// The same as `load_price_history`, taking the medians here for databases without
+/// `percentile_cont`
+async fn load_price_history_in_memory(db: &DatabaseConnection, supermarket_id: i32) -> Result<HashMap<i32, PriceHistory>, ScraperError> {
+    let since: DateTimeWithTimeZone = (Utc::now() - Duration::days(HISTORY_WINDOW_DAYS as i64)).into();
+
+    let prices: Vec<(i32, Money)> = SupermarketPrice::find()
+        .select_only()
+        .column(supermarket_price::Column::ProductId)
+        .column(supermarket_price::Column::Price)
+        .filter(supermarket_price::Column::SupermarketId.eq(supermarket_id))
+        .filter(supermarket_price::Column::Flagged.eq(false))
+        .filter(supermarket_price::Column::Price.gt(Money::ZERO))
+        .filter(supermarket_price::Column::Timestamp.gte(since))
+        .into_tuple()
+        .all(db).await?;
+
+    let mut by_product: HashMap<i32, Vec<Money>> = HashMap::new();
+    for (product_id, price) in prices {
+        by_product.entry(product_id).or_default().push(price);
+    }
+
+    let history = by_product
+        .into_iter()
+        .map(|(product_id, mut prices)| {
+            prices.sort();
+            let middle = prices.len() / 2;
+            // Halfway between the middle two, rounded up, like percentile_cont
+            let median = match prices.len() % 2 {
+                0 => Money::from_cents((prices[middle - 1].cents() + prices[middle].cents() + 1) / 2),
+                _ => prices[middle],
+            };
+
+            (product_id, PriceHistory { product_id, median, samples: prices.len() as i64 })
+        })
+        .collect();
+
+    return Ok(history);
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{ActiveModelTrait, Set};
+
+    use crate::db::{connect_for_tests, entities::product_db, upsert_supermarket};
+
+    use super::*;
+
+    fn history(median_cents: i64, samples: i64) -> PriceHistory {
+        PriceHistory { product_id: 1, median: Money::from_cents(median_cents), samples }
+    }
+
+    /// (reason, quarantined) for a price against a usual price of $1.00
+    fn judge(price_cents: i64) -> Option<(&'static str, bool)> {
+        find_anomaly(1, Money::from_cents(price_cents), Some(&history(100, MIN_HISTORY_SAMPLES)))
+            .map(|x| (x.reason, x.quarantined))
+    }
+
+    #[test]
+    fn quarantines_prices_too_low_to_be_real() {
+        let anomaly = find_anomaly(1, Money::from_cents(4), None).unwrap();
+        assert_eq!((anomaly.reason, anomaly.quarantined, anomaly.reference_price), ("implausible price", true, None));
+        assert!(find_anomaly(1, MIN_PLAUSIBLE_PRICE, None).is_none());
+        // Even a usual price that low doesn't make it plausible
+        assert!(find_anomaly(1, Money::from_cents(4), Some(&history(4, 10))).unwrap().quarantined);
+    }
+
+    #[test]
+    fn trusts_nothing_without_enough_history() {
+        assert!(find_anomaly(1, Money::from_cents(100_000), None).is_none());
+        assert!(find_anomaly(1, Money::from_cents(100_000), Some(&history(100, MIN_HISTORY_SAMPLES - 1))).is_none());
+        assert!(find_anomaly(1, Money::from_cents(100_000), Some(&history(100, MIN_HISTORY_SAMPLES))).is_some());
+    }
+
+    #[test]
+    fn flags_then_quarantines_rises() {
+        assert_eq!(judge(399), None);
+        assert_eq!(judge(400), Some(("well above usual price", false)));
+        assert_eq!(judge(999), Some(("well above usual price", false)));
+        assert_eq!(judge(1000), Some(("far above usual price", true)));
+    }
+
+    #[test]
+    fn flags_then_quarantines_drops() {
+        assert_eq!(judge(26), None);
+        assert_eq!(judge(25), Some(("well below usual price", false)));
+        assert_eq!(judge(11), Some(("well below usual price", false)));
+        assert_eq!(judge(10), Some(("far below usual price", true)));
+    }
+
+    #[test]
+    fn reports_the_usual_price() {
+        let anomaly = find_anomaly(1, Money::from_cents(500), Some(&history(100, 5))).unwrap();
+        assert_eq!(anomaly.reference_price, Some(Money::from_cents(100)));
+
+        let mut report = QualityReport::default();
+        report.check("test", 1, Money::from_cents(500), Some(&history(100, 5)));
+        report.check("test", 1, Money::from_cents(2000), Some(&history(100, 5)));
+        report.check("test", 1, Money::from_cents(110), Some(&history(100, 5)));
+        assert_eq!((report.accepted, report.flagged, report.quarantined, report.anomalies.len()), (1, 1, 1, 2));
+    }
+
+    #[tokio::test]
+    async fn medians_match_postgres() {
+        let db = connect_for_tests().await;
+        let store = upsert_supermarket(&db, "Countdown Online", "Countdown", "Online", "online").await.unwrap();
+        let now = Utc::now();
+
+        let add_prices = async |prices: &[(i64, bool, i64)]| {
+            let product = product_db::ActiveModel {
+                product_title: Set(String::from("Milk")),
+                quantity: Set(1),
+                updated_at: Set(now.into()),
+                ..Default::default()
+            }.insert(&db).await.unwrap();
+
+            for (cents, flagged, days_ago) in prices {
+                supermarket_price::ActiveModel {
+                    timestamp: Set((now - Duration::days(*days_ago)).into()),
+                    supermarket_id: Set(store.supermarket_id),
+                    product_id: Set(product.product_id),
+                    price: Set(Money::from_cents(*cents)),
+                    flagged: Set(*flagged),
+                    ..Default::default()
+                }.insert(&db).await.unwrap();
+            }
+            product.product_id
+        };
+
+        // Expected medians are what the Postgres query gives for the same prices
+        let halfway_rounds_up = add_prices(&[(100, false, 0), (105, false, 1)]).await;
+        let smallest = add_prices(&[(1, false, 0), (2, false, 1)]).await;
+        let middle_two = add_prices(&[
+            (199, false, 0), (250, false, 1), (300, false, 2), (999, false, 3),
+            // Flagged, free and old prices aren't history
+            (5000, true, 0), (0, false, 0), (10, false, HISTORY_WINDOW_DAYS as i64 + 1),
+        ]).await;
+        let odd = add_prices(&[(300, false, 0), (100, false, 1), (200, false, 2)]).await;
+
+        let history = load_price_history(&db, store.supermarket_id).await.unwrap();
+
+        let median = |product_id: i32| (history[&product_id].median, history[&product_id].samples);
+        assert_eq!(median(halfway_rounds_up), (Money::from_cents(103), 2));
+        assert_eq!(median(smallest), (Money::from_cents(2), 2));
+        assert_eq!(median(middle_two), (Money::from_cents(275), 4));
+        assert_eq!(median(odd), (Money::from_cents(200), 3));
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+use log::info;
+use sea_orm::{prelude::DateTimeWithTimeZone, DatabaseConnection};
+
+use crate::{db::{entities::product_db, get_products, get_products_changed}, error::ScraperError};
+
+/// How far back before the last load `refresh` looks, for products saved by a process
+/// whose clock is behind ours or whose save committed after our load
+const REFRESH_OVERLAP_MINUTES: i64 = 5;
+
+/// Brand, title and variety, for products without a usable barcode
+pub fn name_key(brand: Option<&str>, title: &str, variety: Option<&str>) -> String {
+    [brand.unwrap_or(""), title, variety.unwrap_or("")]
+        .iter()
+        .map(|x| x.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase())
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// Every `product_db` row, kept in memory between scrapes and looked up by
+/// barcode instead of scanned
+pub struct ProductIndex {
+    products: HashMap<i32, product_db::Model>,
+    by_barcode: HashMap<String, i32>,
+    by_name: HashMap<String, i32>,
+    /// Newest product loaded from the database, anything after it is picked up by `refresh`
+    last_product_id: i32,
+    /// When the database was last read, products saved after it are picked up by `refresh`
+    loaded_at: Option<DateTimeWithTimeZone>,
+}
+
+impl ProductIndex {
+    pub async fn load(db: &mut DatabaseConnection) -> Result<Self, ScraperError> {
+        let mut index = ProductIndex {
+            products: HashMap::new(),
+            by_barcode: HashMap::new(),
+            by_name: HashMap::new(),
+            last_product_id: 0,
+            loaded_at: Some(Utc::now().into()),
+        };
+        for product in get_products(db).await? {
+            index.insert(product);
+        }
+
+        info!("Loaded {} products into the index", index.len());
+        return Ok(index);
+    }
+
+    /// Loads products added or changed by something else since the last load
+    pub async fn refresh(&mut self, db: &mut DatabaseConnection) -> Result<usize, ScraperError> {
+        let since = self.loaded_at.map(|x| x - Duration::minutes(REFRESH_OVERLAP_MINUTES));
+        self.loaded_at = Some(Utc::now().into());
+
+        let products = get_products_changed(db, self.last_product_id, since).await?;
+        let loaded = products.len();
+        for product in products {
+            self.insert(product);
+        }
+
+        return Ok(loaded);
+    }
+
+    /// Adds a product, or replaces it with a newer copy after it's been saved
+    pub fn insert(&mut self, product: product_db::Model) {
+        let product_id = product.product_id;
+
+        if let Some(old) = self.products.get(&product_id) {
+            let old_key = Self::name_key_of(old);
+            if self.by_name.get(&old_key) == Some(&product_id) {
+                self.by_name.remove(&old_key);
+            }
+            if let Some(old_barcode) = &old.normalized_barcode {
+                if self.by_barcode.get(old_barcode) == Some(&product_id) {
+                    self.by_barcode.remove(old_barcode);
+                }
+            }
+        }
+
+        if let Some(barcode) = product.normalized_barcode.clone() {
+            self.by_barcode.entry(barcode).or_insert(product_id);
+        }
+        self.by_name.entry(Self::name_key_of(&product)).or_insert(product_id);
+
+        self.last_product_id = self.last_product_id.max(product_id);
+        self.products.insert(product_id, product);
+    }
+
+    /// The product with this barcode, or if the barcode can't be used, with the same brand, title and variety
+    pub fn find(&self, barcode: &str, brand: Option<&str>, title: &str, variety: Option<&str>) -> Option<&product_db::Model> {
+        let product_id = match barcode::normalize(barcode) {
+            Some(barcode) => self.by_barcode.get(&barcode),
+            None => self.by_name.get(&name_key(brand, title, variety)),
+        }?;
+
+        return self.products.get(product_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.products.len()
+    }
+
+    fn name_key_of(product: &product_db::Model) -> String {
+        name_key(product.product_brand.as_deref(), &product.product_title, product.product_variety.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use sea_orm::{ActiveModelTrait, Set};
+
+    use crate::db::connect_for_tests;
+
+    use super::*;
+
+    fn product(title: &str, barcode: &str, updated_at: DateTime<Utc>) -> product_db::ActiveModel {
+        product_db::ActiveModel {
+            product_title: Set(title.to_owned()),
+            product_brand: Set(Some(String::from("Anchor"))),
+            barcode: Set(Some(barcode.to_owned())),
+            normalized_barcode: Set(barcode::normalize(barcode)),
+            quantity: Set(1),
+            updated_at: Set(updated_at.into()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_sees_products_changed_elsewhere() {
+        let mut db = connect_for_tests().await;
+        let long_ago = Utc::now() - Duration::days(2);
+        let milk = product("Blue Top Milk", "09415007000101", long_ago).insert(&db).await.unwrap();
+        product("Cheddar", "9415007000408", long_ago).insert(&db).await.unwrap();
+        let mut products = ProductIndex::load(&mut db).await.unwrap();
+
+        // Another process renames the milk, moves its barcode and adds butter
+        let mut renamed: product_db::ActiveModel = milk.into();
+        renamed.product_title = Set(String::from("Lite Milk"));
+        renamed.barcode = Set(Some(String::from("9415007000200")));
+        renamed.normalized_barcode = Set(Some(String::from("9415007000200")));
+        renamed.updated_at = Set(Utc::now().into());
+        renamed.update(&db).await.unwrap();
+        product("Butter", "9415007000309", Utc::now()).insert(&db).await.unwrap();
+
+        // Only what changed is loaded again, the cheddar isn't
+        assert_eq!(products.refresh(&mut db).await.unwrap(), 2);
+        assert_eq!(products.len(), 3);
+        assert_eq!(products.find("9415007000200", None, "", None).map(|x| x.product_title.as_str()), Some("Lite Milk"));
+        assert!(products.find("9415007000101", None, "", None).is_none());
+        assert!(products.find("09415007000309", None, "", None).is_some());
+    }
+}
@@ -0,0 +1,72 @@
+use money::Money;
+
+use crate::db::entities::product_db;
+
+/// Sizes within this fraction of each other are the same size. Sizes worked out
+/// from the cup price wobble a little with rounding.
+const SIZE_TOLERANCE: f32 = 0.02;
+
+/// The details of a product that are tracked over time
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProductDetails {
+    pub title: String,
+    pub image_url: Option<String>,
+    pub size: Option<f32>,
+    pub unit: Option<String>,
+    pub quantity: i32,
+}
+
+impl ProductDetails {
+    pub fn from_product(product: &product_db::Model) -> Self {
+        ProductDetails {
+            title: product.product_title.clone(),
+            image_url: product.image_url.clone(),
+            size: product.size,
+            unit: product.unit.clone(),
+            quantity: product.quantity,
+        }
+    }
+
+    /// Names of the fields that differ in `newer`
+    pub fn changes(&self, newer: &ProductDetails) -> Vec<&'static str> {
+        let mut changes = Vec::new();
+
+        if self.title != newer.title {
+            changes.push("title");
+        }
+        if self.image_url != newer.image_url {
+            changes.push("image");
+        }
+        let same_size = match (self.size, newer.size) {
+            (Some(a), Some(b)) => (a - b).abs() <= a.abs().max(b.abs()) * SIZE_TOLERANCE,
+            (a, b) => a == b,
+        };
+        if !same_size {
+            changes.push("size");
+        }
+        if self.unit != newer.unit {
+            changes.push("unit");
+        }
+        if self.quantity != newer.quantity {
+            changes.push("quantity");
+        }
+
+        changes
+    }
+
+    fn amount(&self) -> (f32, String) {
+        pack_size::amount(self.size, self.unit.as_deref(), self.quantity)
+    }
+}
+
+/// Less product for the same money, or more
+pub fn is_shrinkflation(old: &ProductDetails, new: &ProductDetails, old_price: Option<Money>, new_price: Money) -> bool {
+    let Some(old_price) = old_price else {
+        return false;
+    };
+    let ((old_amount, old_unit), (new_amount, new_unit)) = (old.amount(), new.amount());
+
+    old_unit == new_unit
+        && new_amount < old_amount * (1.0 - SIZE_TOLERANCE)
+        && new_price >= old_price
+}
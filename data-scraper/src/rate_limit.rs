@@ -0,0 +1,197 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use rand::Rng;
+use reqwest::{header::{HeaderMap, RETRY_AFTER}, StatusCode};
+use serde::Deserialize;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// How many healthy responses in a row before speeding back up
+const RECOVERY_STREAK: u32 = 10;
+
+/// How polite to be to one supermarket's servers
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimits {
+    /// The steady rate to aim for when the server is healthy
+    pub requests_per_second: f64,
+    /// Never slow down below this, however often we're told to back off
+    pub min_requests_per_second: f64,
+    /// Requests that can be sent back to back after a quiet spell
+    pub burst: u32,
+    /// Requests allowed in flight at once
+    pub max_concurrent: usize,
+    /// Longest a server's Retry-After is obeyed for, so a bad header can't stall the scrape
+    pub max_retry_after_secs: u64,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        RateLimits {
+            requests_per_second: 1.0,
+            min_requests_per_second: 0.1,
+            burst: 2,
+            max_concurrent: 2,
+            max_retry_after_secs: 60,
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    /// Current refill rate, somewhere between the min and the configured rate
+    rate: f64,
+    last_refill: Instant,
+    /// Set from Retry-After, nothing is sent before this
+    blocked_until: Option<Instant>,
+    healthy_streak: u32,
+}
+
+/// A token bucket for one host, which halves its rate whenever the server pushes back
+/// and creeps back up to the configured rate while responses are healthy
+pub struct HostRateLimiter {
+    host: String,
+    limits: RateLimits,
+    bucket: Mutex<Bucket>,
+    in_flight: Semaphore,
+}
+
+static LIMITERS: Lazy<Mutex<HashMap<String, Arc<HostRateLimiter>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// The limiter shared by everything talking to `host`. The limits only apply
+/// the first time a host is seen.
+pub fn for_host(host: &str, limits: &RateLimits) -> Arc<HostRateLimiter> {
+    let mut limiters = LIMITERS.lock().unwrap();
+
+    limiters
+        .entry(host.to_owned())
+        .or_insert_with(|| Arc::new(HostRateLimiter::new(host, limits.clone())))
+        .clone()
+}
+
+impl HostRateLimiter {
+    fn new(host: &str, limits: RateLimits) -> Self {
+        HostRateLimiter {
+            host: host.to_owned(),
+            bucket: Mutex::new(Bucket {
+                tokens: limits.burst as f64,
+                rate: limits.requests_per_second,
+                last_refill: Instant::now(),
+                blocked_until: None,
+                healthy_streak: 0,
+            }),
+            in_flight: Semaphore::new(limits.max_concurrent.max(1)),
+            limits,
+        }
+    }
+
+    /// Waits until a request may be sent. Hold on to the permit until the response has arrived.
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        let permit = self.in_flight.acquire().await.expect("Rate limiter semaphore is never closed");
+
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+
+                let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * bucket.rate).min(self.limits.burst.max(1) as f64);
+                bucket.last_refill = now;
+
+                match bucket.blocked_until {
+                    Some(until) if until > now => until - now,
+                    _ if bucket.tokens >= 1.0 => {
+                        bucket.tokens -= 1.0;
+                        return permit;
+                    }
+                    _ => Duration::from_secs_f64((1.0 - bucket.tokens) / bucket.rate),
+                }
+            };
+
+            // A little jitter so requests don't land in a robotic rhythm
+            let jitter = rand::thread_rng().gen_range(0.0..0.1);
+            tokio::time::sleep(wait.mul_f64(1.0 + jitter)).await;
+        }
+    }
+
+    /// Adjusts the rate to how the server is coping. Returns whether the response
+    /// was the server asking us to back off, in which case it's worth retrying.
+    pub fn record_response(&self, status: StatusCode, headers: &HeaderMap) -> bool {
+        let mut bucket = self.bucket.lock().unwrap();
+
+        if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE {
+            bucket.rate = (bucket.rate / 2.0).max(self.limits.min_requests_per_second);
+            bucket.tokens = 0.0;
+            bucket.healthy_streak = 0;
+
+            let retry_after = parse_retry_after(headers)
+                .map(|x| x.min(Duration::from_secs(self.limits.max_retry_after_secs)))
+                .unwrap_or(Duration::from_secs_f64(1.0 / bucket.rate));
+            bucket.blocked_until = Some(Instant::now() + retry_after);
+
+            warn!("[{}] Got {}, slowing to {:.2} requests/s, waiting {:?}", self.host, status, bucket.rate, retry_after);
+            return true;
+        }
+
+        if status.is_success() && bucket.rate < self.limits.requests_per_second {
+            bucket.healthy_streak += 1;
+
+            if bucket.healthy_streak >= RECOVERY_STREAK {
+                bucket.rate = (bucket.rate * 1.5).min(self.limits.requests_per_second);
+                bucket.healthy_streak = 0;
+                info!("[{}] Responses healthy, speeding up to {:.2} requests/s", self.host, bucket.rate);
+            }
+        }
+
+        false
+    }
+}
+
+/// Retry-After is either a number of seconds or an HTTP date
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let until = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    (until - Utc::now()).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn retry_after(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn reads_seconds_and_dates() {
+        assert_eq!(parse_retry_after(&retry_after("30")), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after(&retry_after("soon")), None);
+
+        let date = (Utc::now() + chrono::Duration::seconds(120)).to_rfc2822();
+        let wait = parse_retry_after(&retry_after(&date)).unwrap();
+        assert!(wait > Duration::from_secs(110) && wait <= Duration::from_secs(120));
+    }
+
+    #[test]
+    fn waits_no_longer_than_the_limit() {
+        let limiter = HostRateLimiter::new("example.com", RateLimits { max_retry_after_secs: 5, ..RateLimits::default() });
+
+        let before = Instant::now();
+        assert!(limiter.record_response(StatusCode::TOO_MANY_REQUESTS, &retry_after("86400")));
+
+        let blocked_until = limiter.bucket.lock().unwrap().blocked_until.unwrap();
+        assert!(blocked_until <= before + Duration::from_secs(6));
+        assert!(blocked_until >= before + Duration::from_secs(5));
+    }
+}
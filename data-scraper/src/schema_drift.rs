@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+
+use serde::de::{Deserialize, DeserializeOwned};
+use serde_aux::serde_introspection::serde_introspect;
+use serde_json::Value;
+
+/// How a field differs from what we expect
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FieldChange {
+    /// The API sends a field we don't know about
+    Unknown,
+    /// A field we know about wasn't sent
+    Missing,
+    /// A field was sent, but not in a shape we could read
+    Invalid,
+}
+
+impl FieldChange {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FieldChange::Unknown => "unknown",
+            FieldChange::Missing => "missing",
+            FieldChange::Invalid => "invalid",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FieldDrift {
+    pub occurrences: usize,
+    /// The first value or error seen, to help work out what changed
+    pub example: Option<String>,
+}
+
+/// An item that couldn't be read, set aside so it doesn't take the rest of its page with it
+#[derive(Clone, Debug)]
+pub struct QuarantinedItem {
+    pub department: Option<String>,
+    pub page: usize,
+    pub sku: Option<String>,
+    pub error: String,
+    pub raw: Value,
+}
+
+/// Everything in a scrape's responses that didn't match the shape we expect
+#[derive(Clone, Debug, Default)]
+pub struct DriftReport {
+    fields: BTreeMap<(String, FieldChange), FieldDrift>,
+    pub quarantined: Vec<QuarantinedItem>,
+}
+
+impl DriftReport {
+    pub fn record(&mut self, field: &str, change: FieldChange, example: Option<String>) {
+        let drift = self.fields
+            .entry((field.to_owned(), change))
+            .or_insert(FieldDrift { occurrences: 0, example });
+        drift.occurrences += 1;
+    }
+
+    /// Compares the keys of a JSON object with the fields `T` reads. `path` is where
+    /// the object sits in the response, e.g. `product.price`.
+    pub fn check_fields<'de, T: Deserialize<'de>>(&mut self, value: &Value, path: &str) {
+        let Some(object) = value.as_object() else {
+            return;
+        };
+        let known = serde_introspect::<T>();
+
+        for (key, value) in object {
+            if !known.contains(&key.as_str()) {
+                self.record(&format!("{path}.{key}"), FieldChange::Unknown, Some(value.to_string()));
+            }
+        }
+        for key in known {
+            if !object.contains_key(*key) {
+                self.record(&format!("{path}.{key}"), FieldChange::Missing, None);
+            }
+        }
+    }
+
+    /// Reads a `T` out of `value`, recording which field was to blame if it can't be
+    pub fn parse<T: DeserializeOwned>(&mut self, value: &Value, path: &str) -> Result<T, String> {
+        serde_path_to_error::deserialize::<_, T>(value.clone()).map_err(|e| {
+            let field = match e.path().to_string() {
+                x if x == "." => path.to_owned(),
+                x => format!("{path}.{x}"),
+            };
+            let reason = e.into_inner().to_string();
+            self.record(&field, FieldChange::Invalid, Some(reason.clone()));
+
+            format!("{field}: {reason}")
+        })
+    }
+
+    pub fn quarantine(&mut self, item: QuarantinedItem) {
+        self.quarantined.push(item);
+    }
+
+    pub fn merge(&mut self, other: DriftReport) {
+        for ((field, change), drift) in other.fields {
+            let existing = self.fields
+                .entry((field, change))
+                .or_insert(FieldDrift { occurrences: 0, example: drift.example });
+            existing.occurrences += drift.occurrences;
+        }
+        self.quarantined.extend(other.quarantined);
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&str, FieldChange, &FieldDrift)> {
+        self.fields.iter().map(|((field, change), drift)| (field.as_str(), *change, drift))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.quarantined.is_empty()
+    }
+}
@@ -0,0 +1,104 @@
+use std::net::SocketAddr;
+
+use axum::{extract::State, http::{header, StatusCode}, response::IntoResponse, routing::get, Json, Router};
+use chrono::{NaiveDateTime, Utc};
+use log::{error, info};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+
+use crate::{config, db::last_scrapes, metrics};
+
+#[derive(Clone)]
+struct ServerState {
+    db: DatabaseConnection,
+    /// Supermarkets that haven't been scraped yet get until the window passes after startup
+    started_at: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+struct SupermarketHealth {
+    name: String,
+    last_scrape: Option<NaiveDateTime>,
+    age_seconds: Option<i64>,
+    healthy: bool,
+}
+
+#[derive(Serialize)]
+struct Health {
+    status: &'static str,
+    supermarkets: Vec<SupermarketHealth>,
+}
+
+/// A small HTTP server for watching the scraper from outside
+pub async fn serve(addr: SocketAddr, db: DatabaseConnection) {
+    let state = ServerState {
+        db,
+        started_at: Utc::now().naive_utc(),
+    };
+
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(state);
+
+    let server = match axum::Server::try_bind(&addr) {
+        Ok(server) => server,
+        Err(e) => {
+            error!("Couldn't listen on {}: {}", addr, e);
+            return;
+        }
+    };
+
+    info!("Serving metrics and health checks on http://{}", addr);
+    if let Err(e) = server.serve(app.into_make_service()).await {
+        error!("Metrics server stopped: {}", e);
+    }
+}
+
+async fn metrics_handler() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics::render())
+}
+
+/// Healthy only while every supermarket has finished a scrape within the window
+async fn healthz(State(state): State<ServerState>) -> impl IntoResponse {
+    let scrapes = match last_scrapes(&state.db).await {
+        Ok(scrapes) => scrapes,
+        Err(e) => return (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response(),
+    };
+
+    let now = Utc::now().naive_utc();
+    let window = config::get().scrape_health_window_secs as i64;
+
+    let supermarkets = scrapes.into_iter().map(|(supermarket, last_scrape)| {
+        let age_seconds = last_scrape.map(|x| (now - x).num_seconds());
+        let healthy = match age_seconds {
+            Some(age) => age <= window,
+            None => (now - state.started_at).num_seconds() <= window,
+        };
+
+        SupermarketHealth {
+            name: supermarket.name,
+            last_scrape,
+            age_seconds,
+            healthy,
+        }
+    }).collect::<Vec<_>>();
+
+    let healthy = supermarkets.iter().all(|x| x.healthy);
+    let health = Health {
+        status: if healthy { "healthy" } else { "unhealthy" },
+        supermarkets,
+    };
+    let status = if healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    (status, Json(health)).into_response()
+}
+
+/// Ready once the database can be reached
+async fn readyz(State(state): State<ServerState>) -> impl IntoResponse {
+    match state.db.ping().await {
+        Ok(()) => (StatusCode::OK, "ready").into_response(),
+        Err(e) => (StatusCode::SERVICE_UNAVAILABLE, format!("Database unreachable: {e}")).into_response(),
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use log::info;
+use sea_orm::DatabaseConnection;
+
+use crate::{db::{entities::supermarkets, get_supermarkets, upsert_supermarket}, error::ScraperError};
+
+/// Every supermarket, kept in memory between scrapes and looked up by exactly its
+/// brand and location ID, which is what the unique constraint on the table covers
+pub struct SupermarketRegistry {
+    supermarkets: HashMap<(String, String), supermarkets::Model>,
+}
+
+impl SupermarketRegistry {
+    pub async fn load(db: &DatabaseConnection) -> Result<Self, ScraperError> {
+        let supermarkets = get_supermarkets(db)
+            .await?
+            .into_iter()
+            .map(|x| ((x.brand_name.clone(), x.location_id.clone()), x))
+            .collect::<HashMap<_, _>>();
+
+        info!("Loaded {} supermarkets", supermarkets.len());
+        return Ok(SupermarketRegistry { supermarkets });
+    }
+
+    /// The ID of the store with this brand and location ID. A store that hasn't been
+    /// seen before is added, and one whose name or location changed is updated.
+    pub async fn register(
+        &mut self,
+        db: &DatabaseConnection,
+        name: &str,
+        brand: &str,
+        location: &str,
+        location_id: &str,
+    ) -> Result<i32, ScraperError> {
+        let key = (brand.to_owned(), location_id.to_owned());
+
+        match self.supermarkets.get(&key) {
+            Some(known) if known.name == name && known.location == location => return Ok(known.supermarket_id),
+            Some(_) => info!("Updating details of {} store {}", brand, location_id),
+            None => info!("Found new {} store {} ({})", brand, location_id, name),
+        }
+
+        let stored = upsert_supermarket(db, name, brand, location, location_id).await?;
+        let supermarket_id = stored.supermarket_id;
+        self.supermarkets.insert(key, stored);
+
+        return Ok(supermarket_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{EntityTrait, PaginatorTrait};
+
+    use crate::db::{connect_for_tests, entities::prelude::Supermarkets};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn keeps_stores_of_one_brand_apart() {
+        let db = connect_for_tests().await;
+        let mut registry = SupermarketRegistry::load(&db).await.unwrap();
+
+        let online = registry.register(&db, "Countdown Online", "Countdown", "Online", "online").await.unwrap();
+        let other = registry.register(&db, "Countdown Online 2", "Countdown", "Online", "online-2").await.unwrap();
+        let again = registry.register(&db, "Countdown Online", "Countdown", "Online", "online").await.unwrap();
+
+        assert_ne!(online, other);
+        assert_eq!(online, again);
+        assert_eq!(Supermarkets::find().count(&db).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn updates_a_store_registered_again() {
+        let db = connect_for_tests().await;
+        let mut registry = SupermarketRegistry::load(&db).await.unwrap();
+        // Loaded before the store was added, so only the database's unique constraint
+        // stops it adding the store again
+        let mut stale = SupermarketRegistry::load(&db).await.unwrap();
+        let id = registry.register(&db, "Countdown Online", "Countdown", "Online", "online").await.unwrap();
+
+        let renamed = registry.register(&db, "Woolworths Online", "Countdown", "Auckland", "online").await.unwrap();
+        let renamed_again = stale.register(&db, "Woolworths Online NZ", "Countdown", "Wellington", "online").await.unwrap();
+
+        assert_eq!(renamed, id);
+        assert_eq!(renamed_again, id);
+        let stored = Supermarkets::find().all(&db).await.unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].name, "Woolworths Online NZ");
+        assert_eq!(stored[0].location, "Wellington");
+    }
+}
@@ -1,88 +1,121 @@
+use money::Money;
 use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{metrics, schema_drift::{DriftReport, FieldChange, QuarantinedItem}};
+
+use super::SUPERMARKET;
+
+// Only the fields the scraper actually uses are required, anything else missing is
+// defaulted and shows up in the drift report instead of failing the product.
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct ApiResponseRoot {
     pub products: ApiResponseItems,
+    #[serde(default)]
     pub isSuccessful: bool,
+    #[serde(default)]
     pub dasFacets: Vec<ApiResponseDasFacet>,
 }
 
+/// Items are kept as raw JSON so each one can be read, or quarantined, on its own
 #[derive(Deserialize, Debug, Clone)]
 pub struct ApiResponseItems {
-    pub items: Vec<ApiResponseItem>,
+    pub items: Vec<Value>,
     pub totalItems: usize
 }
 
-#[derive(Deserialize, Debug, Clone)]
-#[serde(untagged)]
-pub enum ApiResponseItem {
-    Product(ApiProduct),
-    PromoTile(ApiPromoTile),
-}
-
 #[derive(Deserialize, Debug, Clone)]
 pub struct ApiProduct {
+    pub r#type: Option<String>,
     pub name: String,
     pub barcode: String,
     pub variety: Option<String>,
     pub brand: String,
+    #[serde(default)]
     pub slug: String,
     pub sku: Option<String>,
+    #[serde(default)]
     pub unit: String,
     pub price: ApiResponsePrice,
     pub images: ApiResponseImages,
+    #[serde(default)]
     pub quantity: ApiResponseQuantity,
+    #[serde(default)]
     pub stockLevel: usize,
     pub eachUnitQuantity: Option<String>,
     pub averageWeightPerUnit: Option<f32>,
+    #[serde(default)]
     pub size: ApiResponseSize,
+    #[serde(default)]
     pub departments: Vec<ApiResponseDepartment>,
+    #[serde(default)]
     pub subsAllowed: bool,
+    #[serde(default)]
     pub supportsBothEachAndKgPricing: bool,
+    #[serde(default)]
     pub availabilityStatus: String,
     pub adId: Option<String>,
-}
-
-#[derive(Deserialize, Debug, Clone)]
-pub struct ApiPromoTile {
-    pub name: String,
-    pub id: usize,
-    pub link: Option<String>,
-    pub content: Option<String>
+    pub productTag: Option<ApiResponseProductTag>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct ApiResponsePrice {
-    pub originalPrice: Option<f32>,
-    pub salePrice: Option<f32>,
-    pub savePrice: Option<f32>,
+    #[serde(default, deserialize_with = "money::dollars::deserialize_option")]
+    pub originalPrice: Option<Money>,
+    #[serde(default, deserialize_with = "money::dollars::deserialize_option")]
+    pub salePrice: Option<Money>,
+    #[serde(default, deserialize_with = "money::dollars::deserialize_option")]
+    pub savePrice: Option<Money>,
     pub savePercentage: Option<f32>,
+    #[serde(default)]
     pub canShowSavings: bool,
+    #[serde(default)]
     pub hasBonusPoints: bool,
+    #[serde(default)]
     pub isClubPrice: bool,
     pub isSpecial: bool,
+    #[serde(default)]
     pub isNew: bool,
+    #[serde(default)]
     pub canShowOriginalPrice: bool,
     pub discount: Option<String>,
     pub total: Option<String>,
+    #[serde(default)]
     pub isTargetedOffer: bool,
     pub averagePricePerSingleUnit: Option<f32>,
+    #[serde(default)]
     pub isBoostOffer: bool,
     pub purchasingUnitPrice: Option<String>,
     pub orderedPrice: Option<String>,
+    #[serde(default)]
     pub isUsingOrderedPrice: bool,
     pub currentPricingMatchesOrderedPricing: Option<String>,
     pub extendedListPrice: Option<String>,
     pub originalAveragePricePerSingleUnit: Option<String>,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiResponseProductTag {
+    pub tagType: Option<String>,
+    pub multiBuy: Option<ApiResponseMultiBuy>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiResponseMultiBuy {
+    pub quantity: usize,
+    #[serde(deserialize_with = "money::dollars::deserialize")]
+    pub value: Money,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct ApiResponseImages {
+    #[serde(default)]
     pub small: String,
     pub big: String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct ApiResponseQuantity {
     pub min: Option<f32>,
     pub max: Option<f32>,
@@ -92,7 +125,7 @@ pub struct ApiResponseQuantity {
     pub purchasingQuantityString: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct ApiResponseSize {
     pub cupPrice: Option<f32>,
     pub cupMeasure: Option<String>,
@@ -102,8 +135,9 @@ pub struct ApiResponseSize {
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct ApiResponseDepartment {
-    id: usize,
-    name: String,
+    #[serde(default)]
+    pub id: usize,
+    pub name: String,
 }
 
 // {
@@ -118,9 +152,90 @@ pub struct ApiResponseDepartment {
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct ApiResponseDasFacet {
+    #[serde(default)]
     pub key: String,
+    #[serde(default)]
     pub value: String,
     pub name: String,
+    #[serde(default)]
     pub productCount: usize,
+    #[serde(default)]
     pub group: String,
+}
+
+
+/// A product's page, from the detail endpoint
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiProductDetail {
+    pub sku: String,
+    #[serde(default)]
+    pub name: String,
+    /// HTML
+    pub description: Option<String>,
+    pub ingredients: Option<ApiDetailIngredients>,
+    /// e.g. "Made in New Zealand from local and imported ingredients"
+    pub originStatement: Option<String>,
+    pub nutritionInfoServingSize: Option<String>,
+    pub nutritionInfoServingsPerPack: Option<String>,
+    #[serde(default)]
+    pub nutritionInfo: Vec<ApiDetailNutrient>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiDetailIngredients {
+    pub ingredients: Option<String>,
+    pub allergens: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiDetailNutrient {
+    pub name: String,
+    pub valuePerServing: Option<String>,
+    /// Per 100g, or 100ml for drinks
+    pub valuePer100g: Option<String>,
+}
+
+
+/// Reads one entry from a page of items, noting anything that has changed shape.
+/// Products that can't be read are quarantined, promo tiles and the like are skipped.
+pub fn parse_item(item: Value, department: Option<&str>, page: usize, report: &mut DriftReport) -> Option<ApiProduct> {
+    match item.get("type").and_then(|x| x.as_str()) {
+        Some("Product") | None => {},
+        Some("PromoTile") => return None,
+        Some(other) => {
+            report.record("item.type", FieldChange::Unknown, Some(other.to_owned()));
+            return None;
+        }
+    }
+
+    check_product_fields(&item, report);
+
+    match report.parse::<ApiProduct>(&item, "product") {
+        Ok(product) => Some(product),
+        Err(error) => {
+            metrics::PARSE_FAILURES.with_label_values(&[SUPERMARKET, "product"]).inc();
+            report.quarantine(QuarantinedItem {
+                department: department.map(|x| x.to_owned()),
+                page,
+                sku: item.get("sku").and_then(|x| x.as_str()).map(|x| x.to_owned()),
+                error,
+                raw: item,
+            });
+            None
+        }
+    }
+}
+
+fn check_product_fields(item: &Value, report: &mut DriftReport) {
+    report.check_fields::<ApiProduct>(item, "product");
+    report.check_fields::<ApiResponsePrice>(&item["price"], "product.price");
+    report.check_fields::<ApiResponseImages>(&item["images"], "product.images");
+    report.check_fields::<ApiResponseQuantity>(&item["quantity"], "product.quantity");
+    report.check_fields::<ApiResponseSize>(&item["size"], "product.size");
+    report.check_fields::<ApiResponseProductTag>(&item["productTag"], "product.productTag");
+    report.check_fields::<ApiResponseMultiBuy>(&item["productTag"]["multiBuy"], "product.productTag.multiBuy");
+
+    if let Some(department) = item["departments"].as_array().and_then(|x| x.first()) {
+        report.check_fields::<ApiResponseDepartment>(department, "product.departments");
+    }
 }
\ No newline at end of file
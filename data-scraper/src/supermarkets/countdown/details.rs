@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+
+use chrono::{Duration, Utc};
+use log::{info, warn};
+use regex::Regex;
+use sea_orm::{DatabaseConnection, Set};
+
+use crate::{config, db::{entities::{product_detail, product_nutrition}, get_detail_fetch_times, save_product_detail}, error::ScraperError, metrics, supermarkets::fetch_concurrently};
+
+use super::{api_response::ApiProductDetail, fetch::{self, CountdownApi}, SUPERMARKET};
+
+/// Fetches the pages of products from this scrape that have no details yet, then of
+/// the ones whose details are stale, oldest first, up to the limit per run. A page
+/// that can't be fetched is tried again next run, but one that doesn't exist is
+/// stored empty so it isn't asked for again until it's stale.
+pub async fn fetch_details(
+    db: &DatabaseConnection,
+    api: &CountdownApi,
+    supermarket_id: i32,
+    scraped: &[(i32, String)],
+) -> Result<(), ScraperError> {
+    let settings = &config::get().countdown.details;
+    let fetched = get_detail_fetch_times(db, supermarket_id).await?;
+    let stale_before = Utc::now() - Duration::days(settings.refresh_days as i64);
+
+    let mut seen = HashSet::new();
+    let mut due = scraped
+        .iter()
+        .filter(|(product_id, _)| seen.insert(*product_id))
+        .filter(|(product_id, _)| fetched.get(product_id).map_or(true, |x| *x < stale_before))
+        .cloned()
+        .collect::<Vec<_>>();
+    due.sort_by_key(|(product_id, _)| fetched.get(product_id).copied());
+    due.truncate(settings.max_products_per_run);
+
+    info!("Fetching details of {} products", due.len());
+    let results = fetch_concurrently(due, config::get().countdown.department_workers, |(_, sku)| async move {
+        fetch::fetch_product_detail(api, &sku).await
+    }).await;
+
+    for ((product_id, sku), result) in results {
+        let detail = match result {
+            Ok(Some(detail)) => detail,
+            Ok(None) => {
+                warn!("No product page for SKU {} (product {})", sku, product_id);
+                save_product_detail(db, empty_detail(product_id, supermarket_id, sku), Vec::new()).await?;
+                metrics::PRODUCT_DETAILS.with_label_values(&[SUPERMARKET, "not_found"]).inc();
+                continue;
+            }
+            Err(e) => {
+                warn!("Couldn't fetch details of product {}, trying again next run: {}", product_id, e);
+                metrics::PRODUCT_DETAILS.with_label_values(&[SUPERMARKET, "failed"]).inc();
+                continue;
+            }
+        };
+
+        let (detail, nutrition) = to_models(detail, product_id, supermarket_id);
+        save_product_detail(db, detail, nutrition).await?;
+        metrics::PRODUCT_DETAILS.with_label_values(&[SUPERMARKET, "saved"]).inc();
+    }
+
+    return Ok(());
+}
+
+fn empty_detail(product_id: i32, supermarket_id: i32, sku: String) -> product_detail::ActiveModel {
+    product_detail::ActiveModel {
+        product_id: Set(product_id),
+        supermarket_id: Set(supermarket_id),
+        sku: Set(sku),
+        description: Set(None),
+        ingredients: Set(None),
+        allergens: Set(None),
+        country_of_origin: Set(None),
+        serving_size: Set(None),
+        servings_per_pack: Set(None),
+        fetched_at: Set(Utc::now().into()),
+        ..Default::default()
+    }
+}
+
+fn to_models(detail: ApiProductDetail, product_id: i32, supermarket_id: i32) -> (product_detail::ActiveModel, Vec<product_nutrition::ActiveModel>) {
+    let (ingredients, allergens) = match detail.ingredients {
+        Some(x) => (x.ingredients, x.allergens),
+        None => (None, None),
+    };
+
+    let nutrition = detail.nutritionInfo
+        .into_iter()
+        .map(|x| product_nutrition::ActiveModel {
+            nutrient: Set(x.name.trim().to_owned()),
+            per_serving: Set(x.valuePerServing.and_then(|x| not_blank(&x))),
+            per_100: Set(x.valuePer100g.and_then(|x| not_blank(&x))),
+            ..Default::default()
+        })
+        .collect();
+
+    let detail = product_detail::ActiveModel {
+        product_id: Set(product_id),
+        supermarket_id: Set(supermarket_id),
+        sku: Set(detail.sku),
+        description: Set(detail.description.as_deref().and_then(plain_text)),
+        ingredients: Set(ingredients.as_deref().and_then(plain_text)),
+        allergens: Set(allergens.as_deref().and_then(plain_text)),
+        country_of_origin: Set(detail.originStatement.and_then(|x| not_blank(&x))),
+        serving_size: Set(detail.nutritionInfoServingSize.and_then(|x| not_blank(&x))),
+        servings_per_pack: Set(detail.nutritionInfoServingsPerPack.and_then(|x| not_blank(&x))),
+        fetched_at: Set(Utc::now().into()),
+        ..Default::default()
+    };
+
+    (detail, nutrition)
+}
+
+fn not_blank(text: &str) -> Option<String> {
+    Some(text.trim().to_owned()).filter(|x| !x.is_empty())
+}
+
+/// Product pages are written in HTML, which is stored as plain text
+fn plain_text(html: &str) -> Option<String> {
+    let tags = Regex::new(r"<[^>]*>").expect("Tag regex is valid");
+    let text = tags
+        .replace_all(html, " ")
+        .replace("&amp;", "&")
+        .replace("&nbsp;", " ");
+
+    not_blank(&text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn fixture(sku: &str) -> ApiProductDetail {
+        let path = format!("{}/fixtures/countdown/products/{sku}.json", env!("CARGO_MANIFEST_DIR"));
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn every_fixture_parses() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/countdown/products");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let detail: ApiProductDetail = serde_json::from_str(&fs::read_to_string(&path).unwrap())
+                .unwrap_or_else(|e| panic!("{} doesn't parse: {e}", path.display()));
+            assert_eq!(Some(detail.sku.as_str()), path.file_stem().and_then(|x| x.to_str()));
+        }
+    }
+
+    #[test]
+    fn maps_ingredients_and_nutrition() {
+        let (detail, nutrition) = to_models(fixture("701425"), 7, 1);
+
+        assert_eq!(detail.product_id, Set(7));
+        assert_eq!(detail.supermarket_id, Set(1));
+        assert_eq!(detail.sku, Set(String::from("701425")));
+        assert_eq!(detail.ingredients, Set(Some(String::from("Peanuts (99%), Salt."))));
+        // HTML is stripped
+        assert_eq!(detail.allergens, Set(Some(String::from("Contains peanuts. Made in a factory that also handles tree nuts."))));
+        assert_eq!(detail.country_of_origin, Set(Some(String::from("Made in New Zealand from imported ingredients"))));
+        assert_eq!(detail.serving_size, Set(Some(String::from("15g"))));
+        assert_eq!(detail.servings_per_pack, Set(Some(String::from("25"))));
+
+        let nutrients = nutrition
+            .iter()
+            .map(|x| (x.nutrient.clone().unwrap(), x.per_serving.clone().unwrap(), x.per_100.clone().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(nutrients.len(), 4);
+        assert_eq!(nutrients[0], (String::from("Energy"), Some(String::from("389kJ")), Some(String::from("2590kJ"))));
+        assert_eq!(nutrients[3], (String::from("Sodium"), Some(String::from("48mg")), Some(String::from("320mg"))));
+    }
+
+    #[test]
+    fn decodes_entities_in_descriptions() {
+        let (detail, _) = to_models(fixture("38570"), 1, 1);
+
+        assert_eq!(
+            detail.description,
+            Set(Some(String::from("Wattie's Baked Beans are a tasty & nutritious meal. Good source of fibre.")))
+        );
+        assert_eq!(detail.allergens, Set(None));
+    }
+
+    #[test]
+    fn leaves_out_what_a_page_doesnt_have() {
+        let (detail, nutrition) = to_models(fixture("282758"), 1, 1);
+
+        assert_eq!(detail.description, Set(Some(String::from("Sweet and crisp."))));
+        assert_eq!(detail.ingredients, Set(None));
+        assert_eq!(detail.serving_size, Set(None));
+        assert!(nutrition.is_empty());
+    }
+}
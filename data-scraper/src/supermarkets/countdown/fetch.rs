@@ -1,108 +1,326 @@
-use std::{time::Duration, io::{Error, ErrorKind}, cmp};
+use std::{time::{Duration, Instant}, cmp, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
 
 use fure::{backoff::{exponential, jitter}, policies::{cond, backoff}};
-use log::info;
-use rand::Rng;
+use log::{error, info, warn};
 use regex::Regex;
-use reqwest::{header, Client};
+use reqwest::{header::{self, HeaderName, HeaderValue}, Client, StatusCode};
+use serde::de::DeserializeOwned;
 use tokio::fs;
+use url::Url;
+
+use crate::{config::{self, CountdownConfig}, db::entities::product_db, error::{ErrorContext, ScraperError}, metrics, product_index::name_key, rate_limit::{self, HostRateLimiter}, schema_drift::DriftReport, supermarkets::{countdown::api_response, fetch_concurrently}};
+
+use super::{api_response::{ApiProduct, ApiProductDetail, ApiResponseRoot}, SUPERMARKET};
+
+/// Path of the product search endpoint, relative to the site's base URL. A
+/// product's details are at `{PRODUCT_API_PATH}/{sku}`.
+const PRODUCT_API_PATH: &str = "/api/v1/products";
+
+/// What to ask the product endpoint for
+#[derive(Clone, Copy, Debug)]
+enum Listing<'a> {
+    /// Everything in a department, or just the list of departments
+    Browse(Option<&'a str>),
+    /// What's on special in a department, or the departments with specials
+    Specials(Option<&'a str>),
+    /// Whatever the site's search turns up
+    Search(&'a str),
+    /// One product's full details, by SKU
+    Detail(&'a str),
+}
+
+impl<'a> Listing<'a> {
+    fn catalogue(department: Option<&'a str>, specials_only: bool) -> Self {
+        match specials_only {
+            true => Listing::Specials(department),
+            false => Listing::Browse(department),
+        }
+    }
+
+    fn department(&self) -> Option<&str> {
+        match self {
+            Listing::Browse(department) | Listing::Specials(department) => *department,
+            Listing::Search(_) | Listing::Detail(_) => None,
+        }
+    }
+
+    /// Names the files responses are written to
+    fn label(&self) -> String {
+        match self {
+            Listing::Browse(department) => department.unwrap_or("root").to_owned(),
+            Listing::Specials(department) => format!("specials-{}", department.unwrap_or("root")),
+            Listing::Search(text) => {
+                let text = text.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '-' }).collect::<String>();
+                format!("search-{text}")
+            }
+            Listing::Detail(sku) => format!("detail-{sku}"),
+        }
+    }
+
+    fn url(&self, api: &CountdownApi) -> String {
+        match self {
+            Listing::Detail(sku) => format!("{}/{}", api.products_url, sku),
+            _ => api.products_url.clone(),
+        }
+    }
 
-use crate::{config, supermarkets::countdown::api_response::{self, ApiResponseItem}};
+    fn query_params(&self, page: usize, size: usize) -> Vec<(&'static str, String)> {
+        let mut params = match self {
+            Listing::Browse(_) => vec![("target", String::from("browse"))],
+            Listing::Specials(_) => vec![("target", String::from("specials"))],
+            Listing::Search(text) => vec![("target", String::from("search")), ("search", text.to_string())],
+            Listing::Detail(_) => return Vec::new(),
+        };
 
-use super::api_response::{ApiProduct, ApiResponseRoot};
+        if let Some(department) = self.department() {
+            params.push(("dasFilter", format!("Department;;{department};false")));
+        }
+        params.extend([
+            ("inStockProductsOnly", String::from("false")),
+            ("page", page.to_string()),
+            ("size", size.to_string()),
+        ]);
 
-const PRODUCT_API_URL: &str = "https://www.countdown.co.nz/api/v1/products";
+        return params;
+    }
+}
 
+/// The Countdown API to scrape, and the client used to talk to it. Pointing this at
+/// another base URL (e.g. the mock server in `examples/mock_countdown`) lets the
+/// fetcher run without hitting countdown.co.nz.
+#[derive(Clone)]
+pub struct CountdownApi {
+    client: Client,
+    products_url: String,
+    limiter: Arc<HostRateLimiter>,
+    settings: &'static CountdownConfig,
+}
 
+impl CountdownApi {
+    /// Uses a client set up with the configured headers, which should look like the
+    /// Countdown web app's
+    pub fn new(settings: &'static CountdownConfig) -> Result<Self, ScraperError> {
+        let mut headers = header::HeaderMap::new();
+        for (name, value) in &settings.headers {
+            let name = HeaderName::try_from(name)
+                .map_err(|e| ScraperError::Config(format!("Invalid Countdown header name {name:?}: {e}")))?;
+            let value = HeaderValue::try_from(value)
+                .map_err(|e| ScraperError::Config(format!("Invalid Countdown header value {value:?}: {e}")))?;
+            headers.insert(name, value);
+        }
 
-pub async fn fetch_countdown_data() -> Result<Vec<ApiProduct>, Box<dyn std::error::Error + Send + Sync>> {
-    let mut headers = header::HeaderMap::new();
-    headers.insert("authority", "www.countdown.co.nz".parse()?);
-    headers.insert("accept", "application/json, text/plain, */*".parse()?);
-    headers.insert("accept-language", "en-GB,en-US;q=0.9,en;q=0.8".parse()?);
-    headers.insert("cache-control", "no-cache".parse()?);
-    headers.insert("User-Agent", "Yes/1.0.0".parse()?);
-    headers.insert("x-requested-with", "OnlineShopping.WebApp".parse()?);
-    headers.insert("x-ui-ver", "7.30.266".parse()?);
+        let client = reqwest::ClientBuilder::new()
+            .default_headers(headers)
+            .connect_timeout(Duration::from_secs(settings.connect_timeout_secs))
+            .connection_verbose(true)
+            .timeout(Duration::from_secs(settings.request_timeout_secs))
+            .build()
+            .map_err(|e| ScraperError::Config(format!("Couldn't build the Countdown HTTP client: {e}")))?;
 
-    let api_client = reqwest::ClientBuilder::new()
-        .default_headers(headers)
-        .connect_timeout(Duration::from_secs(5))
-        .connection_verbose(true)
-        .timeout(Duration::from_secs(10))
-        .build()?;
+        Self::with_client(client, settings)
+    }
+
+    pub fn with_client(client: Client, settings: &'static CountdownConfig) -> Result<Self, ScraperError> {
+        let base_url = &settings.base_url;
+        let url = Url::parse(base_url)
+            .map_err(|e| ScraperError::Config(format!("Countdown base URL {base_url:?} isn't valid: {e}")))?;
+        let host = url.host_str()
+            .ok_or_else(|| ScraperError::Config(format!("Countdown base URL {base_url:?} has no host")))?;
+        // Servers on other ports of the same host, like mock servers, are limited separately
+        let host = match url.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_owned(),
+        };
+
+        Ok(CountdownApi {
+            client,
+            products_url: format!("{}{}", base_url.trim_end_matches('/'), PRODUCT_API_PATH),
+            limiter: rate_limit::for_host(&host, &settings.rate_limits),
+            settings,
+        })
+    }
+}
+
+
+
+/// What a scrape of every department found
+pub struct CountdownData {
+    pub products: Vec<ApiProduct>,
+    /// Departments that couldn't be fetched
+    pub failures: Vec<ScraperError>,
+    pub drift: DriftReport,
+}
+
+/// Every product in every configured department, or only the ones on special, up to
+/// `max_products` of them, along with the failures of any departments that couldn't
+/// be fetched. Only fails outright if no department could be fetched.
+pub async fn fetch_countdown_data(api: &CountdownApi, specials_only: bool, max_products: usize) -> Result<CountdownData, ScraperError> {
+    let filter = &api.settings.departments;
+    let mut departments = list_departments(api, Listing::catalogue(None, specials_only)).await?;
+    for missing in filter.include.iter().filter(|x| !departments.contains(x)) {
+        warn!("Department {:?} is set to be scraped but Countdown doesn't list it", missing);
+    }
+    departments.retain(|x| filter.allows(x));
+    let department_count = departments.len();
+
+    // Each department stops at the limit on its own, and the scrape as a whole is cut
+    // to it afterwards, in department order, so which products are kept doesn't depend
+    // on which department finished first
+    let results = fetch_concurrently(departments, api.settings.department_workers, |department| async move {
+        fetch_department(Listing::catalogue(Some(&department), specials_only), api, max_products).await
+    }).await;
 
     let mut item_store = Vec::new();
+    let mut failures = Vec::new();
+    let mut drift = DriftReport::default();
+    for (department, result) in results {
+        match result {
+            Ok((department_items, department_drift)) => {
+                item_store.extend(department_items);
+                drift.merge(department_drift);
+            },
+            Err(e) => {
+                error!("[{}] Failed to fetch department, skipping it: {}", department, e);
+                failures.push(e);
+            }
+        }
+    }
 
-    for department in list_departments(&api_client).await? {
-        let department_items = fetch_department(&department, api_client.clone()).await?;
-        item_store.extend(department_items);
+    if department_count > 0 && failures.len() == department_count {
+        return Err(failures.remove(0));
     }
 
+    item_store.truncate(max_products);
+
+    return Ok(CountdownData { products: item_store, failures, drift });
+}
+
+/// The current listing of each watched product, found by searching for it instead of
+/// crawling every department. Products the search can't find are left out.
+pub async fn fetch_watched_products(api: &CountdownApi, watched: Vec<product_db::Model>) -> Result<CountdownData, ScraperError> {
+    let product_count = watched.len();
+
+    let results = fetch_concurrently(watched, api.settings.department_workers, |product| async move {
+        find_product(&product, api).await
+    }).await;
+
+    let mut item_store = Vec::new();
+    let mut failures = Vec::new();
+    let mut drift = DriftReport::default();
+    for (product, result) in results {
+        match result {
+            Ok((found, search_drift)) => {
+                match found {
+                    Some(item) => item_store.push(item),
+                    None => warn!("Couldn't find watched product {} ({:?}) on Countdown", product.product_id, product.product_title),
+                }
+                drift.merge(search_drift);
+            },
+            Err(e) => {
+                error!("Failed to look up watched product {}, skipping it: {}", product.product_id, e);
+                failures.push(e);
+            }
+        }
+    }
+
+    if product_count > 0 && failures.len() == product_count {
+        return Err(failures.remove(0));
+    }
+
+    info!("Found {} of {} watched products", item_store.len(), product_count);
+    return Ok(CountdownData { products: item_store, failures, drift });
+}
+
+/// Searches by barcode, then by name, for the listing of one of our products
+async fn find_product(product: &product_db::Model, api: &CountdownApi) -> Result<(Option<ApiProduct>, DriftReport), ScraperError> {
+    let barcode = product.barcode.as_deref().and_then(barcode::normalize);
+    let key = name_key(product.product_brand.as_deref(), &product.product_title, product.product_variety.as_deref());
+    let is_match = |item: &ApiProduct| match &barcode {
+        Some(barcode) => barcode::normalize(&item.barcode).as_ref() == Some(barcode),
+        None => name_key(Some(&item.brand), &item.name, item.variety.as_deref()) == key,
+    };
 
-    return Ok(item_store);
+    let name = [product.product_brand.as_deref(), Some(product.product_title.as_str())]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    // Searched for as stored, leading zeros and all
+    let searches = product.barcode.iter().map(|x| x.trim().to_owned()).filter(|x| barcode.is_some() && !x.is_empty()).chain([name]);
+
+    let mut drift = DriftReport::default();
+    for search in searches {
+        let listing = Listing::Search(&search);
+        let api_response: ApiResponseRoot = send_request(api, listing, 1, api.settings.page_size).await?;
+
+        let found = api_response.products.items
+            .into_iter()
+            .filter_map(|e| api_response::parse_item(e, listing.department(), 1, &mut drift))
+            .find(|x| is_match(x));
+        if found.is_some() {
+            return Ok((found, drift));
+        }
+    }
+
+    Ok((None, drift))
 }
 
 
 
-async fn fetch_department(department: &str, api_client: Client) -> Result<Vec<ApiProduct>, Box<dyn std::error::Error + Send + Sync>> {
+async fn fetch_department(listing: Listing<'_>, api: &CountdownApi, max_products: usize) -> Result<(Vec<ApiProduct>, DriftReport), ScraperError> {
+    let department = listing.label();
     info!("[{}] Fetching Countdown data!", department);
-    let mut rng = rand::thread_rng();
 
-    let number_to_fetch = 120;
-    
+    // The API works out offsets as (page - 1) * size, so the size has to stay the same
+    // for every page or items get skipped and repeated
+    let page_size = cmp::min(api.settings.page_size, max_products);
 
     let mut page_num = 1;
-    let mut total_items = usize::MAX;
     let mut item_store = Vec::new();
+    let mut drift = DriftReport::default();
 
     // Get all pages of data
     loop {
-        let fetch_round_count = cmp::min(number_to_fetch, cmp::min(total_items,config::CONFIG.max_products_scrape) - item_store.len());
-
-        info!("[{}] Loading data, page {}, {} items", department, page_num, fetch_round_count);
+        info!("[{}] Loading data, page {}, {} items", department, page_num, page_size);
 
-        let api_response = send_request(&api_client, Some(department), page_num, fetch_round_count).await?;
+        let api_response: ApiResponseRoot = send_request(api, listing, page_num, page_size).await?;
         
         let items = api_response.products.items;
-        total_items = api_response.products.totalItems;
-
+        let page_was_empty = items.is_empty();
+        let total_items = api_response.products.totalItems;
 
         item_store.extend(
             items
                 .into_iter()
-                .filter_map(|e| match e {
-                    ApiResponseItem::Product(group) => Some(group),
-                    _ => None,
-                })
+                .filter_map(|e| api_response::parse_item(e, listing.department(), page_num, &mut drift))
         );
-        info!("[{}] Found {} items, out of {}, (scrape max: {})", department, item_store.len(), api_response.products.totalItems,config::CONFIG.max_products_scrape);
+        info!("[{}] Found {} items, out of {}, (scrape max {})", department, item_store.len(), total_items, max_products);
 
 
 
-        if item_store.len() >= total_items || item_store.len() >= config::CONFIG.max_products_scrape {
+        if page_was_empty || item_store.len() >= total_items || item_store.len() >= max_products {
             break;
         }
         page_num+=1;
-        let gitter_ms = rng.gen_range(0..500);
-        tokio::time::sleep(Duration::from_millis(1000 + gitter_ms)).await;
     }
 
-    
+    if !drift.quarantined.is_empty() {
+        warn!("[{}] Quarantined {} products that couldn't be read", department, drift.quarantined.len());
+    }
 
-    Ok(item_store)
+    Ok((item_store, drift))
 
 }
 
-async fn list_departments(api_client: &Client) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
-    let api_response = send_request(api_client, None, 1, 1).await?;
+async fn list_departments(api: &CountdownApi, listing: Listing<'_>) -> Result<Vec<String>, ScraperError> {
+    let api_response: ApiResponseRoot = send_request(api, listing, 1, 1).await?;
 
     let human_department_names: Vec<String> = api_response.dasFacets.iter().map(|x| {
         x.name.clone()
     }).collect();
 
+    let regex = Regex::new(r"(?m)\s+").expect("Whitespace regex is valid");
     let filter_names: Vec<String> = human_department_names.into_iter().map(|x| {
-        let regex = Regex::new(r"(?m)\s+").unwrap();
         let cleaned = x.trim().chars().filter(|c| c.is_alphabetic() || c.is_whitespace()).collect::<String>().to_lowercase();
         regex.replace_all(&cleaned, "-").to_string()
     }).collect();
@@ -111,46 +329,74 @@ async fn list_departments(api_client: &Client) -> Result<Vec<String>, Box<dyn st
 }
 
 
-async fn send_request(api_client: &Client, department: Option<&str>, page: usize, size: usize) -> Result<ApiResponseRoot, Box<dyn std::error::Error + Send + Sync>> {
+/// A product's details, or `None` if Countdown doesn't know the SKU any more
+pub async fn fetch_product_detail(api: &CountdownApi, sku: &str) -> Result<Option<ApiProductDetail>, ScraperError> {
+    match send_request(api, Listing::Detail(sku), 1, 1).await {
+        Ok(detail) => Ok(Some(detail)),
+        Err(ScraperError::Http { status: Some(StatusCode::NOT_FOUND), .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+
+async fn send_request<T: DeserializeOwned>(api: &CountdownApi, listing: Listing<'_>, page: usize, size: usize) -> Result<T, ScraperError> {
+    let department = listing.department();
+    let attempts = AtomicUsize::new(0);
     let get_data = || async {
-        let page_num = page.to_string();
-        let page_size = (size).to_string();
-
-        let mut query_params = vec![
-            ("target", "browse"),
-            ("inStockProductsOnly", "false"),
-            ("page", &page_num),
-            ("size", &page_size),
-        ];
-
-        let filter: String;
-        if let Some(department) = department {
-            filter = format!("Department;;{department};false");
-            query_params.push(("dasFilter", &filter));
+        if attempts.fetch_add(1, Ordering::Relaxed) > 0 {
+            metrics::HTTP_RETRIES.with_label_values(&[SUPERMARKET, "transient"]).inc();
         }
 
-        let response = api_client.execute(
-            api_client
-                .get(PRODUCT_API_URL)
-                .query(&query_params)
-                .build().unwrap()
-        ).await.map_err(|err| {
-            Error::new(ErrorKind::Other, err.to_string())
-        })?;
+        let url = listing.url(api);
+        let query_params = listing.query_params(page, size);
+
+        let request = || api.client.get(&url).query(&query_params).build();
+        let context = match request() {
+            Ok(built) => ErrorContext::request(department, page, built.url().as_str()),
+            Err(e) => return Err(ScraperError::http(e, ErrorContext::request(department, page, &url))),
+        };
+
+        // Being told to back off isn't a failure, the limiter waits as long as the server
+        // asked before trying again. The permit is held until the body has been read.
+        let mut rate_limited_attempts = 0;
+        let (response, _permit) = loop {
+            let permit = api.limiter.acquire().await;
+
+            let started = Instant::now();
+            let response = api.client
+                .execute(request().map_err(|e| ScraperError::http(e, context.clone()))?)
+                .await;
+            let status = response.as_ref().map_or(String::from("error"), |x| x.status().as_u16().to_string());
+            metrics::HTTP_REQUEST_DURATION.with_label_values(&[SUPERMARKET, &status]).observe(started.elapsed().as_secs_f64());
+            let response = response.map_err(|e| ScraperError::http(e, context.clone()))?;
+
+            if !api.limiter.record_response(response.status(), response.headers()) {
+                break (response, permit);
+            }
+
+            rate_limited_attempts += 1;
+            if rate_limited_attempts >= api.settings.retry.max_rate_limited_attempts {
+                return Err(ScraperError::RateLimitExhausted { attempts: rate_limited_attempts, context });
+            }
+            metrics::HTTP_RETRIES.with_label_values(&[SUPERMARKET, "rate_limited"]).inc();
+        };
+
+        if !response.status().is_success() {
+            info!("Countdown API returned {}{}", response.status(), context);
+            return Err(ScraperError::status(response.status(), context));
+        }
 
 
-        let contents = response.text().await.map_err(|err| {
-            Error::new(ErrorKind::Other, err.to_string())
-        })?;
+        let contents = response.text().await.map_err(|e| ScraperError::http(e, context.clone()))?;
 
 
         let pretty_printed_json = jsonxf::pretty_print(&contents).unwrap_or(contents.to_owned());
         fs::write(
-            format!("{}/countdown_{}_{}.json", config::DATA_OUT_DIR, department.unwrap_or("root"), page),
+            config::get().output_dir.join(format!("countdown_{}_{}.json", listing.label(), page)),
             pretty_printed_json.clone()
         ).await?;
 
-        match serde_json::from_str::<ApiResponseRoot>(&pretty_printed_json) {
+        match serde_json::from_str::<T>(&pretty_printed_json) {
             Ok(api_response) => {
                 Ok(api_response)
             },
@@ -159,16 +405,162 @@ async fn send_request(api_client: &Client, department: Option<&str>, page: usize
                 if contents.len() < 1000 {
                     info!("Response: {}", contents);
                 }
-                Err(Box::new(Error::new(ErrorKind::Other, "Error parsing response from Countdown API")))
+                let what = match listing {
+                    Listing::Detail(_) => "detail",
+                    _ => "page",
+                };
+                metrics::PARSE_FAILURES.with_label_values(&[SUPERMARKET, what]).inc();
+                Err(ScraperError::Decode { source: e, context })
             }
         }
     };
 
 
-    let exp_backoff = exponential(Duration::from_secs(1), 2, Some(Duration::from_secs(20)))
-        .map(jitter);
-    let policy = cond(backoff(exp_backoff), |result| !matches!(result, Some(Ok(_))));
+    let retry = &api.settings.retry;
+    let exp_backoff = exponential(Duration::from_secs(retry.initial_delay_secs), retry.factor, Some(Duration::from_secs(retry.max_delay_secs)))
+        .map(jitter)
+        .take(retry.max_retries);
+    // Only retry what might work next time, a bad request or unreadable data won't change
+    let policy = cond(backoff(exp_backoff), |result: Option<Result<&T, &ScraperError>>| match result {
+        Some(Ok(_)) => false,
+        Some(Err(e)) => e.is_transient(),
+        None => true,
+    });
 
     // Getting the data
-    Ok(fure::retry(get_data, policy).await?)
-}
\ No newline at end of file
+    fure::retry(get_data, policy).await
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::{collections::BTreeSet, net::SocketAddr, path::PathBuf, sync::atomic::Ordering};
+
+    use crate::{config::{self, CountdownConfig, RetryPolicy}, mock_countdown::{self, MockState, Options}, rate_limit::RateLimits};
+
+    use super::*;
+
+    /// Serves the fixtures on a port of its own, so each test gets its own rate limiter
+    pub(in crate::supermarkets::countdown) async fn start_mock(options: Options) -> (Arc<MockState>, String) {
+        let state = MockState::new(Options {
+            fixtures: PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/countdown")),
+            ..options
+        });
+
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(mock_countdown::router(state.clone()).into_make_service());
+        let base_url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+
+        (state, base_url)
+    }
+
+    /// Settings for talking to the mock quickly, retrying straight away
+    pub(in crate::supermarkets::countdown) fn settings(base_url: String) -> &'static CountdownConfig {
+        config::for_tests();
+
+        Box::leak(Box::new(CountdownConfig {
+            base_url,
+            page_size: 10,
+            rate_limits: RateLimits {
+                requests_per_second: 100.0,
+                burst: 10,
+                max_concurrent: 4,
+                ..RateLimits::default()
+            },
+            retry: RetryPolicy {
+                initial_delay_secs: 0,
+                max_retries: 3,
+                ..RetryPolicy::default()
+            },
+            ..CountdownConfig::default()
+        }))
+    }
+
+    fn skus(data: &CountdownData) -> BTreeSet<String> {
+        data.products.iter().filter_map(|x| x.sku.clone()).collect()
+    }
+
+    fn fixture_skus() -> BTreeSet<String> {
+        ["133211", "282758", "135344", "282767", "135306", "38570", "701425", "280145"]
+            .into_iter()
+            .map(|x| x.to_owned())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn merges_every_page_of_every_department() {
+        let (state, base_url) = start_mock(Options { page_size: Some(2), ..Options::default() }).await;
+        let api = CountdownApi::with_client(Client::new(), settings(base_url)).unwrap();
+
+        let data = fetch_countdown_data(&api, false, usize::MAX).await.unwrap();
+
+        assert!(data.failures.is_empty());
+        assert_eq!(data.products.len(), 8, "No product is repeated or skipped");
+        assert_eq!(skus(&data), fixture_skus());
+        // The department list, three pages of fruit & veg and two of pantry
+        assert_eq!(state.requests.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_same_products_when_capped() {
+        let (_, base_url) = start_mock(Options { page_size: Some(2), ..Options::default() }).await;
+        let api = CountdownApi::with_client(Client::new(), settings(base_url)).unwrap();
+        let all = fetch_countdown_data(&api, false, usize::MAX).await.unwrap();
+        let first = all.products.iter().take(3).map(|x| x.sku.clone()).collect::<Vec<_>>();
+
+        // Departments race each other, but the first three in department order are
+        // kept whichever finishes first
+        for _ in 0..5 {
+            let capped = fetch_countdown_data(&api, false, 3).await.unwrap();
+            assert_eq!(capped.products.iter().map(|x| x.sku.clone()).collect::<Vec<_>>(), first);
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let (state, base_url) = start_mock(Options { page_size: Some(2), fail_every: Some(3), ..Options::default() }).await;
+        let api = CountdownApi::with_client(Client::new(), settings(base_url)).unwrap();
+
+        let data = fetch_countdown_data(&api, false, usize::MAX).await.unwrap();
+
+        assert!(data.failures.is_empty());
+        assert_eq!(skus(&data), fixture_skus());
+        // Every third request failed and was sent again
+        assert!(state.requests.load(Ordering::SeqCst) > 6);
+    }
+
+    #[tokio::test]
+    async fn backs_off_when_rate_limited() {
+        let (state, base_url) = start_mock(Options { rate_limit: Some(2), ..Options::default() }).await;
+        let api = CountdownApi::with_client(Client::new(), settings(base_url)).unwrap();
+
+        let started = Instant::now();
+        let data = fetch_countdown_data(&api, false, usize::MAX).await.unwrap();
+
+        assert!(data.failures.is_empty());
+        assert_eq!(skus(&data), fixture_skus());
+        assert!(state.rate_limited.load(Ordering::SeqCst) > 0);
+        // Nothing more is sent until the second the mock asked for has passed
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_rate_limited_for_good() {
+        let (state, base_url) = start_mock(Options { rate_limit: Some(0), ..Options::default() }).await;
+        let settings = settings(base_url);
+        let settings = Box::leak(Box::new(CountdownConfig {
+            rate_limits: RateLimits { max_retry_after_secs: 0, ..settings.rate_limits.clone() },
+            retry: RetryPolicy { max_rate_limited_attempts: 3, ..RetryPolicy::default() },
+            base_url: settings.base_url.clone(),
+            ..CountdownConfig::default()
+        }));
+        let api = CountdownApi::with_client(Client::new(), settings).unwrap();
+
+        let error = fetch_countdown_data(&api, false, usize::MAX).await.err().unwrap();
+
+        assert!(matches!(error, ScraperError::RateLimitExhausted { attempts: 3, .. }));
+        assert!(!error.is_transient());
+        // The limiter's attempts aren't retried all over again
+        assert_eq!(state.requests.load(Ordering::SeqCst), 3);
+    }
+}
@@ -1,28 +1,89 @@
-use std::cmp;
+use std::{cmp, time::Instant};
 
-use log::{warn, info};
+use log::{error, warn, info};
 use sea_orm::{DatabaseConnection, Set, ActiveModelTrait};
 
-use crate::db::{check_add_supermarket_info, entities::{product_db, supermarket_price}};
+use crate::{config::{self, ScrapeMode}, db::{finish_scrape_run, get_watched_products, record_drift, record_price_quality, start_scrape_run, entities::supermarket_price}, error::ScraperError, metrics, price_validation::{load_price_history, QualityReport}, product_index::ProductIndex, schema_drift::DriftReport, supermarket_registry::SupermarketRegistry};
 
-use self::product_matcher::get_price;
+use self::{fetch::{CountdownApi, CountdownData}, product_matcher::{get_price, get_multibuy}};
 
+mod details;
 mod fetch;
 mod product_matcher;
 mod api_response;
 
+/// Label for this supermarket's metrics
+const SUPERMARKET: &str = "countdown";
+
+/// What a scrape found, besides the prices themselves
+struct ScrapeOutcome {
+    products_fetched: usize,
+    /// Departments that couldn't be fetched
+    failures: Vec<ScraperError>,
+    drift: DriftReport,
+    quality: QualityReport,
+}
+
 pub async fn fetch(
     db: &mut DatabaseConnection,
-    products: &mut Vec<product_db::ActiveModel>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    products: &mut ProductIndex,
+    supermarkets: &mut SupermarketRegistry,
+) -> Result<(), ScraperError> {
+    let api = CountdownApi::new(&config::get().countdown)?;
+    return fetch_from(&api, db, products, supermarkets).await;
+}
+
+/// Scrapes `api`, recording how the scrape went
+async fn fetch_from(
+    api: &CountdownApi,
+    db: &mut DatabaseConnection,
+    products: &mut ProductIndex,
+    supermarkets: &mut SupermarketRegistry,
+) -> Result<(), ScraperError> {
     info!("COUNTDOWN STARTING");
 
 
-    let supermarket_id = check_add_supermarket_info(db, "Countdown Online", "Countdown", "Online", "online").await?;
+    let supermarket_id = supermarkets.register(db, "Countdown Online", "Countdown", "Online", "online").await?;
+    let run_id = start_scrape_run(db, supermarket_id).await?;
+    let started = Instant::now();
+
+    let (status, result) = match scrape(api, db, products, supermarket_id).await {
+        Ok(outcome) => {
+            record_drift(db, run_id, &outcome.drift).await?;
+            record_price_quality(db, run_id, &outcome.quality).await?;
+            let status = finish_scrape_run(db, run_id, outcome.products_fetched, &outcome.failures, None).await?;
+            info!("COUNTDOWN COMPLETE");
+            (status, Ok(()))
+        }
+        Err(e) => (finish_scrape_run(db, run_id, 0, &[], Some(&e)).await?, Err(e)),
+    };
+
+    metrics::SCRAPE_DURATION.with_label_values(&[SUPERMARKET, status]).observe(started.elapsed().as_secs_f64());
 
-    let store_prices = fetch::fetch_countdown_data().await?;
+    return result;
+}
 
-    let product_ids = product_matcher::match_products(&store_prices, products, db).await?;
+/// Fetches everything and stores the prices that look right
+async fn scrape(
+    api: &CountdownApi,
+    db: &mut DatabaseConnection,
+    products: &mut ProductIndex,
+    supermarket_id: i32,
+) -> Result<ScrapeOutcome, ScraperError> {
+    let mode = config::get().scrape_mode;
+    let max_products = config::get().max_products_scrape;
+    info!("Scraping Countdown ({:?})", mode);
+    let data = match mode {
+        ScrapeMode::Full => fetch::fetch_countdown_data(api, false, max_products).await?,
+        ScrapeMode::Specials => fetch::fetch_countdown_data(api, true, max_products).await?,
+        ScrapeMode::Watched => fetch::fetch_watched_products(api, get_watched_products(db).await?).await?,
+    };
+    let CountdownData { products: store_prices, failures, mut drift } = data;
+    metrics::PRODUCTS_SCRAPED.with_label_values(&[SUPERMARKET]).inc_by(store_prices.len() as u64);
+
+    let history = load_price_history(db, supermarket_id).await?;
+    let product_ids = product_matcher::match_products(&store_prices, products, &history, db, &mut drift).await?;
+    let mut quality = QualityReport::default();
 
     info!("Uploading price data...");
     let matched_prices = store_prices.iter().zip(product_ids.iter()).map(|(x, y)| {
@@ -33,21 +94,86 @@ pub async fn fetch(
             return None
         }
 
+        let price = get_price(x);
+        let anomaly = quality.check(SUPERMARKET, *y, price, history.get(y));
+        if let Some(anomaly) = &anomaly {
+            warn!("Price of {:?} looks wrong ({}): {} vs usual {:?}", x.name, anomaly.reason, price, anomaly.reference_price);
+            if anomaly.quarantined {
+                return None;
+            }
+        }
+
+        let multibuy = get_multibuy(x);
+
         Some(supermarket_price::ActiveModel {
             product_id: Set(y.clone()),
             supermarket_id: Set(supermarket_id.clone()),
-            price: Set(get_price(x)),
+            price: Set(price),
             on_special: Set(Some(x.price.isSpecial)),
             original_price: Set(x.price.originalPrice),
+            multibuy_quantity: Set(multibuy.map(|(quantity, _)| quantity)),
+            multibuy_price: Set(multibuy.map(|(_, price)| price)),
+            flagged: Set(anomaly.is_some()),
             ..Default::default()
         })
     }).filter_map(|f| f).collect::<Vec<supermarket_price::ActiveModel>>();
 
     for price in matched_prices {
+        let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["save_price"]).start_timer();
         price.save(db).await?;
+        metrics::PRICE_ROWS_WRITTEN.with_label_values(&[SUPERMARKET]).inc();
     }
 
-    info!("COUNTDOWN COMPLETE");
+    info!("Prices: {} accepted, {} flagged, {} quarantined", quality.accepted, quality.flagged, quality.quarantined);
+
+    if config::get().countdown.details.enabled {
+        let scraped = store_prices.iter().zip(product_ids.iter())
+            .filter(|(_, product_id)| **product_id >= 0)
+            .filter_map(|(x, product_id)| Some((*product_id, x.sku.clone()?)))
+            .collect::<Vec<_>>();
+        // The prices are already stored, so the scrape still counts if this goes wrong
+        if let Err(e) = details::fetch_details(db, api, supermarket_id, &scraped).await {
+            error!("Fetching product details failed, trying again next run: {}", e);
+            metrics::DETAIL_RUN_FAILURES.with_label_values(&[SUPERMARKET]).inc();
+        }
+    }
+
+    return Ok(ScrapeOutcome {
+        products_fetched: store_prices.len(),
+        failures,
+        drift,
+        quality,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Client;
+    use sea_orm::{EntityTrait, PaginatorTrait};
 
-    Ok(())
+    use crate::{db::{connect_for_tests, entities::prelude::*}, mock_countdown::Options};
+
+    use super::{fetch::tests::{settings, start_mock}, *};
+
+    #[tokio::test]
+    async fn scrapes_the_mock_into_a_fresh_database() {
+        let (_, base_url) = start_mock(Options::default()).await;
+        let api = CountdownApi::with_client(Client::new(), settings(base_url)).unwrap();
+        let mut db = connect_for_tests().await;
+        let mut products = ProductIndex::load(&mut db).await.unwrap();
+        let mut supermarkets = SupermarketRegistry::load(&db).await.unwrap();
+
+        fetch_from(&api, &mut db, &mut products, &mut supermarkets).await.unwrap();
+        assert_eq!(ProductDb::find().count(&db).await.unwrap(), 8);
+        assert_eq!(SupermarketPrice::find().count(&db).await.unwrap(), 8);
+
+        // The second scrape finds the products it stored the first time
+        fetch_from(&api, &mut db, &mut products, &mut supermarkets).await.unwrap();
+        assert_eq!(ProductDb::find().count(&db).await.unwrap(), 8);
+        assert_eq!(SupermarketPrice::find().count(&db).await.unwrap(), 16);
+
+        let runs = ScrapeRun::find().all(&db).await.unwrap();
+        assert_eq!(runs.len(), 2);
+        assert!(runs.iter().all(|x| x.status == "succeeded" && x.products_fetched == 8));
+    }
 }
@@ -1,21 +1,25 @@
 use std::{collections::HashMap, cmp};
 
-use log::info;
+use chrono::Utc;
+use log::{info, warn};
+use money::Money;
 use sea_orm::{Set,NotSet, ActiveModelTrait};
 use tokio::time::Instant;
 use url::Url;
 
-use crate::db::entities::product_db;
+use crate::{db::{entities::product_db, record_revision}, error::ScraperError, metrics, price_validation::PriceHistory, product_index::ProductIndex, product_revisions::{is_shrinkflation, ProductDetails}, schema_drift::{DriftReport, FieldChange}};
 
-use super::api_response::ApiProduct;
+use super::{api_response::ApiProduct, SUPERMARKET};
 
 pub async fn match_products(
     store_products: &Vec<ApiProduct>,
-    db_products: &mut Vec<product_db::ActiveModel>,
+    products: &mut ProductIndex,
+    history: &HashMap<i32, PriceHistory>,
     db: &mut sea_orm::DatabaseConnection,
-) -> Result<Vec<i32>, Box<dyn std::error::Error + Send + Sync>> {
+    drift: &mut DriftReport,
+) -> Result<Vec<i32>, ScraperError> {
 
-    info!("Matching Products! {}/{}", store_products.len(), db_products.len(),);
+    info!("Matching Products! {}/{}", store_products.len(), products.len(),);
     let start_time = Instant::now();
 
     let mut novel_products: usize = 0;
@@ -26,19 +30,63 @@ pub async fn match_products(
 
         // Check valid price
         let store_price = get_price(&store_product);
-        if store_price == 0.0 {
-            info!("Price is 0.0 for product: {:?}, skipping", store_product.name);
+        if store_price.is_zero() {
+            info!("Price is 0 for product: {:?}, skipping", store_product.name);
             matched_product_ids.push(-1);
             continue;
         }
 
 
         // Check for a perfect ID match
-        let matched_product = db_products.iter_mut().find(|db_product| {
-            db_product.barcode.clone().unwrap().is_some_and(|x| x == store_product.barcode)
-        });
+        let matched_product = products.find(&store_product.barcode, Some(&store_product.brand), &store_product.name, store_product.variety.as_deref());
         if let Some(matched_product) = matched_product {
-            matched_product_ids.push(matched_product.product_id.clone().unwrap());
+            let mut matched_product = matched_product.clone();
+
+            // Products indexed before departments were tracked get backfilled
+            if matched_product.department.is_none() {
+                if let Some(department) = get_department(&store_product) {
+                    let mut backfilled: product_db::ActiveModel = matched_product.into();
+                    backfilled.department = Set(Some(department));
+                    backfilled.updated_at = Set(Utc::now().into());
+                    matched_product = backfilled.update(db).await?;
+                    products.insert(matched_product.clone());
+                }
+            }
+
+            // Keep up with renames, new photos and resizes
+            let product_id = matched_product.product_id;
+            let previous = ProductDetails::from_product(&matched_product);
+            let (size, quantity, unit) = parse_size_unit(&store_product, store_price);
+            let current = ProductDetails {
+                title: store_product.name.clone(),
+                // A photo we can't read is no reason to forget the one we had
+                image_url: large_image(&store_product, drift).or_else(|| previous.image_url.clone()),
+                size,
+                unit,
+                quantity,
+            };
+
+            let changes = previous.changes(&current);
+            if !changes.is_empty() {
+                let old_price = history.get(&product_id).map(|x| x.median);
+                let shrinkflation = is_shrinkflation(&previous, &current, old_price, store_price);
+                if shrinkflation {
+                    warn!("Shrinkflation: {:?} went from {:?}{} to {:?}{} at {}", current.title, previous.size, previous.unit.clone().unwrap_or_default(), current.size, current.unit.clone().unwrap_or_default(), store_price);
+                }
+
+                let mut revised: product_db::ActiveModel = matched_product.into();
+                revised.product_title = Set(current.title.clone());
+                revised.image_url = Set(current.image_url.clone());
+                revised.size = Set(current.size);
+                revised.unit = Set(current.unit.clone());
+                revised.quantity = Set(current.quantity);
+                revised.updated_at = Set(Utc::now().into());
+                products.insert(revised.update(db).await?);
+
+                record_revision(db, product_id, &current, Some(store_price), Some(&changes), shrinkflation).await?;
+            }
+
+            matched_product_ids.push(product_id);
             continue;
         }
 
@@ -48,24 +96,35 @@ pub async fn match_products(
 
         // Backup, create the product
         let (size, quantity, unit) = parse_size_unit(&store_product, store_price);
+        let department = get_department(&store_product);
+        let image_url = large_image(&store_product, drift);
         
         let new_product = product_db::ActiveModel {
             product_title: Set(store_product.name),
             product_brand: Set(Some(store_product.brand)),
-            barcode: Set(Some(store_product.barcode)),
-            image_url: Set(Some(get_large_image(&store_product.images.big))),
+            normalized_barcode: Set(barcode::normalize(&store_product.barcode)),
+            barcode: Set(Some(store_product.barcode).filter(|x| !x.trim().is_empty())),
+            image_url: Set(image_url),
             product_variety: Set(store_product.variety),
             quantity: Set(quantity),
             size: Set(size),
             unit: Set(unit),
+            department: Set(department),
+            updated_at: Set(Utc::now().into()),
             ..Default::default()
         };
         novel_products += 1;
-        let db_entry = new_product.save(db).await?;
+        let db_entry = {
+            let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["save_product"]).start_timer();
+            new_product.insert(db).await?
+        };
+        metrics::NEW_PRODUCTS.with_label_values(&[SUPERMARKET]).inc();
+        let details = ProductDetails::from_product(&db_entry);
+        record_revision(db, db_entry.product_id, &details, Some(store_price), None, false).await?;
 
         // info!("Created new product: {:?}", db_entry);
-        matched_product_ids.push(db_entry.product_id.clone().unwrap());
-        db_products.push(db_entry);
+        matched_product_ids.push(db_entry.product_id);
+        products.insert(db_entry);
     }
 
     info!("Matched {} products, in {}s!", matched_product_ids.len(), start_time.elapsed().as_millis() as f64 / 1000.0);
@@ -74,8 +133,24 @@ pub async fn match_products(
     Ok(matched_product_ids)
 }
 
-fn get_large_image(src: &str) -> String {
-    let mut url = Url::parse(src).unwrap();
+/// The product's large image, or `None` if its URL can't be read, which is noted as drift
+fn large_image(store_product: &ApiProduct, drift: &mut DriftReport) -> Option<String> {
+    match get_large_image(&store_product.images.big) {
+        Ok(url) => Some(url),
+        Err(e) => {
+            warn!("Image of {:?} can't be used: {}", store_product.name, e);
+            drift.record("product.images.big", FieldChange::Invalid, Some(store_product.images.big.clone()));
+            None
+        }
+    }
+}
+
+fn get_large_image(src: &str) -> Result<String, ScraperError> {
+    let mut url = Url::parse(src).map_err(|e| ScraperError::Parse {
+        what: "image URL",
+        value: src.to_owned(),
+        reason: e.to_string(),
+    })?;
     
     // Remove all queries except impolicy
     let mut query_pairs: HashMap<_, _> = url.query_pairs().into_owned().collect();
@@ -83,12 +158,16 @@ fn get_large_image(src: &str) -> String {
     let new_query: String = query_pairs.into_iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&");
     url.set_query(Some(&new_query));
 
-    return url.into()
+    return Ok(url.into())
 }
 
 
 
-fn parse_size_unit(store_product: &ApiProduct, store_price: f32) -> (Option<f32>, i32, Option<String>) {
+fn get_department(store_product: &ApiProduct) -> Option<String> {
+    store_product.departments.first().map(|department| department.name.clone())
+}
+
+fn parse_size_unit(store_product: &ApiProduct, store_price: Money) -> (Option<f32>, i32, Option<String>) {
     let mut size= None;
     let mut unit = None;
     let mut quantity = 1;
@@ -127,7 +206,7 @@ fn parse_size_unit(store_product: &ApiProduct, store_price: f32) -> (Option<f32>
         if let (Some(cup_measure), Some(cup_price)) = (&store_product.size.cupMeasure, &store_product.size.cupPrice) {
             let (parsed_size, parsed_unit) = parse_unit(&cup_measure);
 
-            let real_size = (parsed_size * (store_price / cup_price)) / quantity as f32;
+            let real_size = (parsed_size * (store_price.dollars() as f32 / cup_price)) / quantity as f32;
 
             if parsed_unit == "ea" {
                 // If we already have a quantity, then we don't need to set it again
@@ -165,6 +244,90 @@ fn parse_unit(str: &str) -> (f32, String) {
     (size, parsed_unit)
 }
 
-pub fn get_price(store_product: &ApiProduct) -> f32 {
-    store_product.price.salePrice.unwrap_or(store_product.price.originalPrice.unwrap_or(0.0))
-}
\ No newline at end of file
+pub fn get_price(store_product: &ApiProduct) -> Money {
+    store_product.price.salePrice.unwrap_or(store_product.price.originalPrice.unwrap_or(Money::ZERO))
+}
+
+/// The (quantity, total price) of a multibuy deal, e.g. "2 for $5"
+pub fn get_multibuy(store_product: &ApiProduct) -> Option<(i32, Money)> {
+    store_product.productTag.as_ref()
+        .and_then(|tag| tag.multiBuy.as_ref())
+        .filter(|multibuy| multibuy.quantity > 1 && multibuy.value > Money::ZERO)
+        .map(|multibuy| (multibuy.quantity as i32, multibuy.value))
+}
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use sea_orm::EntityTrait;
+    use serde_json::Value;
+
+    use crate::{db::connect_for_tests, price_validation::PriceHistory, supermarkets::countdown::api_response::ApiResponseImages};
+
+    use super::*;
+
+    fn fixture_product() -> ApiProduct {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/countdown/fruit-veg.json");
+        let root: Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        serde_json::from_value(root["products"]["items"][0].clone()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn keeps_the_stored_image_when_the_new_url_is_broken() {
+        let mut db = connect_for_tests().await;
+        let mut products = ProductIndex::load(&mut db).await.unwrap();
+        let history = HashMap::<i32, PriceHistory>::new();
+
+        let product = fixture_product();
+        let mut drift = DriftReport::default();
+        let ids = match_products(&vec![product.clone()], &mut products, &history, &mut db, &mut drift).await.unwrap();
+        assert!(drift.is_empty());
+        let stored_image = products.find(&product.barcode, Some(&product.brand), &product.name, product.variety.as_deref())
+            .and_then(|x| x.image_url.clone());
+        assert!(stored_image.is_some());
+
+        // Renamed at the same time, so the product is still saved
+        let broken = ApiProduct {
+            name: format!("{} (new look)", product.name),
+            images: ApiResponseImages { big: String::from("not a url"), ..product.images.clone() },
+            ..product.clone()
+        };
+        let mut drift = DriftReport::default();
+        let matched = match_products(&vec![broken], &mut products, &history, &mut db, &mut drift).await.unwrap();
+
+        assert_eq!(matched, ids);
+        let (field, change, found) = drift.fields().next().unwrap();
+        assert_eq!((field, change, found.example.as_deref()), ("product.images.big", FieldChange::Invalid, Some("not a url")));
+        let saved = product_db::Entity::find_by_id(ids[0]).one(&db).await.unwrap().unwrap();
+        assert_eq!(saved.image_url, stored_image);
+        assert!(saved.product_title.ends_with("(new look)"));
+    }
+
+    #[tokio::test]
+    async fn one_gtin_spelled_two_ways_is_one_product() {
+        let mut db = connect_for_tests().await;
+        let mut products = ProductIndex::load(&mut db).await.unwrap();
+        let history = HashMap::<i32, PriceHistory>::new();
+
+        let product = fixture_product();
+        let mut drift = DriftReport::default();
+        let ids = match_products(&vec![product.clone()], &mut products, &history, &mut db, &mut drift).await.unwrap();
+        let saved = product_db::Entity::find_by_id(ids[0]).one(&db).await.unwrap().unwrap();
+        assert_eq!(saved.normalized_barcode, barcode::normalize(&product.barcode));
+
+        // The database won't take a second copy, whichever way it's spelled
+        let padded = format!("0{}", product.barcode);
+        let copy = product_db::ActiveModel {
+            product_title: Set(product.name.clone()),
+            barcode: Set(Some(padded.clone())),
+            normalized_barcode: Set(barcode::normalize(&padded)),
+            quantity: Set(1),
+            ..Default::default()
+        };
+        assert!(copy.insert(&db).await.is_err());
+
+        let respelled = ApiProduct { barcode: padded, ..product };
+        let matched = match_products(&vec![respelled], &mut products, &history, &mut db, &mut drift).await.unwrap();
+        assert_eq!(matched, ids);
+    }
+}
@@ -1,14 +1,41 @@
+use std::future::Future;
+
+use futures::{stream, StreamExt};
 use sea_orm::DatabaseConnection;
 
-use crate::db::get_products;
+use crate::{error::ScraperError, product_index::ProductIndex, supermarket_registry::SupermarketRegistry};
 
 pub mod countdown;
 
 
-pub async fn super_fetch(db: &mut DatabaseConnection) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let mut products = get_products(db).await?;
+pub async fn super_fetch(
+    db: &mut DatabaseConnection,
+    products: &mut ProductIndex,
+    supermarkets: &mut SupermarketRegistry,
+) -> Result<(), ScraperError> {
+    products.refresh(db).await?;
 
-    countdown::fetch(db,&mut products).await?;
+    countdown::fetch(db, products, supermarkets).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+
+/// Runs `fetch` for every key with at most `workers` running at once. Results come
+/// back in the same order as the keys, whatever order they finish in, and one
+/// failing doesn't stop the rest.
+pub async fn fetch_concurrently<K, T, E, F, Fut>(keys: Vec<K>, workers: usize, fetch: F) -> Vec<(K, Result<T, E>)>
+where
+    K: Clone,
+    F: Fn(K) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    stream::iter(keys)
+        .map(|key| {
+            let result = fetch(key.clone());
+            async move { (key, result.await) }
+        })
+        .buffered(workers.max(1))
+        .collect()
+        .await
+}
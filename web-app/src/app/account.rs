@@ -0,0 +1,169 @@
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuthMethods {
+    pub password: bool,
+    pub email_link: bool,
+}
+
+#[server(GetAuthMethods, "/api")]
+pub async fn get_auth_methods() -> Result<AuthMethods, ServerFnError> {
+    let config = crate::auth::auth_config()?;
+
+    Ok(AuthMethods {
+        password: config.password,
+        email_link: config.email_link,
+    })
+}
+
+/// The logged in user's email, if anyone is logged in
+#[server(GetCurrentUser, "/api")]
+pub async fn get_current_user() -> Result<Option<String>, ServerFnError> {
+    Ok(crate::auth::current_user().await?.map(|user| user.email))
+}
+
+#[server(Login, "/api")]
+pub async fn login(email: String, password: String) -> Result<(), ServerFnError> {
+    if !crate::auth::auth_config()?.password {
+        return Err(ServerFnError::ServerError("Password login is disabled".to_owned()));
+    }
+
+    let db = crate::db::db()?;
+    let user = crate::auth::password::login(&db, email.trim(), &password)
+        .await?
+        .ok_or_else(|| ServerFnError::Args("Incorrect email or password".to_owned()))?;
+
+    crate::auth::start_session(&db, &user).await?;
+    leptos_actix::redirect("/dashboard");
+    Ok(())
+}
+
+#[server(Register, "/api")]
+pub async fn register(email: String, password: String) -> Result<(), ServerFnError> {
+    if !crate::auth::auth_config()?.password {
+        return Err(ServerFnError::ServerError("Password login is disabled".to_owned()));
+    }
+
+    let email = email.trim();
+    if !email.contains('@') {
+        return Err(ServerFnError::Args("That doesn't look like an email address".to_owned()));
+    }
+
+    let db = crate::db::db()?;
+    let user = crate::auth::password::register(&db, email, &password).await?;
+
+    crate::auth::start_session(&db, &user).await?;
+    leptos_actix::redirect("/dashboard");
+    Ok(())
+}
+
+#[server(RequestLoginLink, "/api")]
+pub async fn request_login_link(email: String) -> Result<(), ServerFnError> {
+    let config = crate::auth::auth_config()?;
+    if !config.email_link {
+        return Err(ServerFnError::ServerError("Email link login is disabled".to_owned()));
+    }
+
+    let email = email.trim();
+    if !email.contains('@') {
+        return Err(ServerFnError::Args("That doesn't look like an email address".to_owned()));
+    }
+
+    let db = crate::db::db()?;
+    crate::auth::email_link::issue(&db, &config, email).await
+}
+
+#[server(Logout, "/api")]
+pub async fn logout() -> Result<(), ServerFnError> {
+    let db = crate::db::db()?;
+    crate::auth::end_session(&db).await?;
+
+    leptos_actix::redirect("/");
+    Ok(())
+}
+
+/// Shows the error from the last submission of a form, if it failed
+fn action_error<I: 'static, T: Clone + 'static>(action: Action<I, Result<T, ServerFnError>>) -> impl IntoView {
+    move || {
+        action.value().get().and_then(|result| result.err()).map(|e| view! {
+            <p class="error">{e.to_string()}</p>
+        })
+    }
+}
+
+#[component]
+pub fn LoginPage() -> impl IntoView {
+    let query = use_query_map();
+    let link_expired = move || query.with(|q| q.get("error").is_some_and(|x| x == "link-expired"));
+
+    let methods = create_resource(|| (), |_| get_auth_methods());
+    let login = create_server_action::<Login>();
+    let register = create_server_action::<Register>();
+    let request_link = create_server_action::<RequestLoginLink>();
+    let link_sent = move || matches!(request_link.value().get(), Some(Ok(())));
+
+    view! {
+        <h1>"Log in"</h1>
+        <Show when=link_expired>
+            <p class="error">"That login link has expired or was already used"</p>
+        </Show>
+        <Transition fallback=move || view! { <p>"Loading..."</p> }>
+            {move || methods.get().map(|methods| match methods {
+                Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                Ok(methods) => view! {
+                    <Show when=move || methods.password>
+                        <h2>"With a password"</h2>
+                        <ActionForm action=login class="account-form">
+                            <input type="email" name="email" placeholder="Email" required=true/>
+                            <input type="password" name="password" placeholder="Password" required=true/>
+                            <input type="submit" value="Log in"/>
+                        </ActionForm>
+                        {action_error(login)}
+                        <h2>"Create an account"</h2>
+                        <ActionForm action=register class="account-form">
+                            <input type="email" name="email" placeholder="Email" required=true/>
+                            <input type="password" name="password" placeholder="Password" required=true/>
+                            <input type="submit" value="Sign up"/>
+                        </ActionForm>
+                        {action_error(register)}
+                    </Show>
+                    <Show when=move || methods.email_link>
+                        <h2>"With an email link"</h2>
+                        <ActionForm action=request_link class="account-form">
+                            <input type="email" name="email" placeholder="Email" required=true/>
+                            <input type="submit" value="Email me a link"/>
+                        </ActionForm>
+                        {action_error(request_link)}
+                        <Show when=link_sent>
+                            <p>"Check your inbox for a login link"</p>
+                        </Show>
+                    </Show>
+                }.into_view(),
+            })}
+        </Transition>
+    }
+}
+
+/// Links to the account pages, depending on whether someone is logged in
+#[component]
+pub fn AccountNav() -> impl IntoView {
+    let logout = create_server_action::<Logout>();
+    let user = create_resource(move || logout.version().get(), |_| get_current_user());
+
+    view! {
+        <Transition fallback=move || ()>
+            {move || match user.get() {
+                Some(Ok(Some(email))) => view! {
+                    <A href="/dashboard">"Dashboard"</A>
+                    <A href="/watchlist">"Watchlist"</A>
+                    <ActionForm action=logout class="logout">
+                        <input type="submit" value=format!("Log out {email}")/>
+                    </ActionForm>
+                }.into_view(),
+                _ => view! { <A href="/login">"Log in"</A> }.into_view(),
+            }}
+        </Transition>
+    }
+}
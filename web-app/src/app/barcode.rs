@@ -0,0 +1,196 @@
+use leptos::*;
+use leptos_router::*;
+use money::Money;
+use serde::{Deserialize, Serialize};
+
+use barcode::{has_valid_check_digit, normalize};
+use crate::components::barcode_scanner::BarcodeScanner;
+
+use super::price_history::PriceHistory;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoreOffer {
+    pub supermarket_name: String,
+    pub price: Money,
+    pub on_special: bool,
+    pub original_price: Option<Money>,
+    /// (quantity, total price)
+    pub multibuy: Option<(i32, Money)>,
+    pub seen_at: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BarcodeProduct {
+    pub product_id: i32,
+    pub product_title: String,
+    pub product_brand: Option<String>,
+    pub image_url: Option<String>,
+    pub size: Option<String>,
+    /// Cheapest first
+    pub offers: Vec<StoreOffer>,
+}
+
+#[server(LookupBarcode, "/api")]
+pub async fn lookup_barcode(code: String) -> Result<Vec<BarcodeProduct>, ServerFnError> {
+    use std::collections::HashMap;
+
+    use barcode::is_gtin;
+    use entity::{prelude::*, product_db};
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+    let code = normalize(&code)
+        .filter(|x| is_gtin(x))
+        .ok_or_else(|| ServerFnError::Args("That isn't a barcode number".to_owned()))?;
+
+    let db = crate::db::db()?;
+
+    // Stored barcodes keep whatever leading zeros the store sent, so look them up by
+    // the normalized copy, which is indexed
+    let products = ProductDb::find()
+        .filter(product_db::Column::NormalizedBarcode.eq(code))
+        .all(&db)
+        .await?;
+
+    let stores = Supermarkets::find()
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|x| (x.supermarket_id, x.name))
+        .collect::<HashMap<i32, String>>();
+
+    let product_ids = products.iter().map(|x| x.product_id).collect();
+    let prices = crate::db::latest_prices(&db, product_ids).await?;
+    let images = crate::db::mirrored_images(&db, products.iter().filter_map(|x| x.image_url.clone()).collect()).await?;
+
+    let products = products
+        .into_iter()
+        .map(|product| {
+            let mut offers = prices
+                .iter()
+                .filter(|x| x.product_id == product.product_id)
+                .map(|x| StoreOffer {
+                    supermarket_name: stores.get(&x.supermarket_id).cloned().unwrap_or_default(),
+                    price: x.price,
+                    on_special: x.on_special.unwrap_or(false),
+                    original_price: x.original_price,
+                    multibuy: x.multibuy_quantity.zip(x.multibuy_price),
+                    seen_at: x.timestamp.format("%Y-%m-%d").to_string(),
+                })
+                .collect::<Vec<StoreOffer>>();
+            offers.sort_by_key(|x| x.price);
+
+            let size = product.size.map(|size| {
+                let unit = product.unit.clone().unwrap_or_default();
+                match product.quantity {
+                    quantity if quantity > 1 => format!("{quantity} x {size}{unit}"),
+                    _ => format!("{size}{unit}"),
+                }
+            });
+
+            BarcodeProduct {
+                product_id: product.product_id,
+                product_title: product.product_title,
+                product_brand: product.product_brand,
+                image_url: crate::images::product_image_url(
+                    product.image_url.as_ref().and_then(|x| images.get(x).cloned()),
+                    product.image_url,
+                    crate::images::SMALL,
+                ),
+                size,
+                offers,
+            }
+        })
+        .collect();
+
+    Ok(products)
+}
+
+/// Price comparison for every product with the barcode
+#[component]
+fn BarcodeResults(code: String) -> impl IntoView {
+    let typo = !normalize(&code).is_some_and(|x| has_valid_check_digit(&x));
+    let products = create_resource(move || code.clone(), lookup_barcode);
+
+    view! {
+        <Transition fallback=move || view! { <p>"Looking up barcode..."</p> }>
+            {move || products.get().map(|products| match products {
+                Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                Ok(products) if products.is_empty() => view! {
+                    <p>"We don't know any products with that barcode"</p>
+                    {typo.then(|| view! {
+                        <p class="error">"The check digit doesn't match, it may have been typed wrong"</p>
+                    })}
+                }.into_view(),
+                Ok(products) => products.into_iter().map(|product| view! {
+                    <section class="barcode-product">
+                        {product.image_url.map(|src| view! { <img src=src alt="" width=120/> })}
+                        <h2>{product.product_brand.map(|brand| format!("{brand} "))}{product.product_title}</h2>
+                        {product.size.map(|size| view! { <p>{size}</p> })}
+                        <table>
+                            <thead>
+                                <tr><th>"Store"</th><th>"Price"</th><th>"Deal"</th><th>"Seen"</th></tr>
+                            </thead>
+                            <tbody>
+                                {product.offers.into_iter().enumerate().map(|(i, offer)| view! {
+                                    <tr class:cheapest={i == 0}>
+                                        <td>{offer.supermarket_name}</td>
+                                        <td>{offer.price.to_string()}</td>
+                                        <td>
+                                            {offer.on_special.then(|| match offer.original_price {
+                                                Some(was) => format!("Special, was {}", was),
+                                                None => "Special".to_owned(),
+                                            })}
+                                            {offer.multibuy.map(|(quantity, price)| format!(" {quantity} for {}", price))}
+                                        </td>
+                                        <td>{offer.seen_at}</td>
+                                    </tr>
+                                }).collect_view()}
+                            </tbody>
+                        </table>
+                        <PriceHistory product_id=product.product_id/>
+                    </section>
+                }).collect_view(),
+            })}
+        </Transition>
+    }
+}
+
+/// Scan with the camera or type in a barcode
+#[component]
+pub fn BarcodeScanPage() -> impl IntoView {
+    let query = use_query_map();
+    let code = move || query.with(|q| q.get("code").cloned().filter(|x| !x.trim().is_empty()));
+
+    let navigate = use_navigate();
+    let on_scan = Callback::new(move |code: String| navigate(&format!("/barcode/{code}"), Default::default()));
+
+    // Without a camera to scan with, typing the number is the only way in
+    let manual = create_node_ref::<html::Input>();
+    let on_unavailable = Callback::new(move |_| {
+        if let Some(manual) = manual.get() {
+            let _ = manual.focus();
+        }
+    });
+
+    view! {
+        <h1>"Scan a barcode"</h1>
+        <BarcodeScanner on_scan=on_scan on_unavailable=on_unavailable/>
+        <Form method="GET" action="" class="filters">
+            <input node_ref=manual type="text" name="code" inputmode="numeric" placeholder="Barcode number" value=code/>
+            <input type="submit" value="Look up"/>
+        </Form>
+        {move || code().map(|code| view! { <BarcodeResults code=code/> })}
+    }
+}
+
+#[component]
+pub fn BarcodePage() -> impl IntoView {
+    let params = use_params_map();
+    let code = move || params.with(|p| p.get("code").cloned().unwrap_or_default());
+
+    view! {
+        <h1>"Barcode " {code}</h1>
+        {move || view! { <BarcodeResults code=code()/> }}
+        <A href="/barcode">"Scan another"</A>
+    }
+}
@@ -0,0 +1,53 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+/// Data older than this is flagged as stale
+const STALE_AFTER_MINUTES: i64 = 24 * 60;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DataFreshness {
+    pub last_updated: Option<String>,
+    pub age_minutes: Option<i64>,
+}
+
+#[server(GetDataFreshness, "/api")]
+pub async fn get_data_freshness() -> Result<DataFreshness, ServerFnError> {
+    use chrono::Utc;
+
+    let db = crate::db::db()?;
+    let last_updated = crate::db::data_last_updated(&db).await?;
+
+    Ok(DataFreshness {
+        last_updated: last_updated.map(|x| x.format("%Y-%m-%d %H:%M").to_string()),
+        age_minutes: last_updated.map(|x| (Utc::now().naive_utc() - x).num_minutes()),
+    })
+}
+
+fn describe_age(minutes: i64) -> String {
+    match minutes {
+        x if x < 1 => "just now".to_owned(),
+        x if x < 60 => format!("{x} minutes ago"),
+        x if x < 48 * 60 => format!("{} hours ago", x / 60),
+        x => format!("{} days ago", x / (24 * 60)),
+    }
+}
+
+/// Says how old the prices on the site are, and warns when the scraper has fallen behind
+#[component]
+pub fn DataFreshnessBanner() -> impl IntoView {
+    let freshness = create_resource(|| (), |_| get_data_freshness());
+
+    view! {
+        <Transition fallback=move || ()>
+            {move || freshness.get().map(|freshness| match freshness {
+                Ok(DataFreshness { last_updated: Some(last_updated), age_minutes: Some(age) }) => view! {
+                    <p class="data-freshness" class:stale={age >= STALE_AFTER_MINUTES}>
+                        "Prices last updated " {last_updated} " (" {describe_age(age)} ")"
+                    </p>
+                }.into_view(),
+                Ok(_) => view! { <p class="data-freshness stale">"No prices have been scraped yet"</p> }.into_view(),
+                Err(_) => ().into_view(),
+            })}
+        </Transition>
+    }
+}
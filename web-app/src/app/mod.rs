@@ -2,6 +2,25 @@ use leptos::*;
 use leptos_meta::*;
 use leptos_router::*;
 
+mod account;
+mod barcode;
+mod freshness;
+mod price_history;
+mod price_index;
+mod shopping_list;
+mod shrinkflation;
+mod specials;
+mod watchlist;
+
+use account::{AccountNav, LoginPage};
+use barcode::{BarcodePage, BarcodeScanPage};
+use freshness::DataFreshnessBanner;
+use price_index::PriceIndexPage;
+use shopping_list::{ShoppingListPage, ShoppingListsPage};
+use shrinkflation::ShrinkflationPage;
+use specials::SpecialsPage;
+use watchlist::{DashboardPage, WatchlistPage};
+
 #[component]
 pub fn App() -> impl IntoView {
     // Provides context that manages stylesheets, titles, meta tags, etc.
@@ -17,9 +36,29 @@ pub fn App() -> impl IntoView {
 
         // content for this welcome page
         <Router>
+            <nav>
+                <A href="/">"Home"</A>
+                <A href="/specials">"Specials"</A>
+                <A href="/price-index">"Price index"</A>
+                <A href="/shrinkflation">"Shrinkflation"</A>
+                <A href="/lists">"Shopping lists"</A>
+                <A href="/barcode">"Scan"</A>
+                <AccountNav/>
+            </nav>
+            <DataFreshnessBanner/>
             <main>
                 <Routes>
                     <Route path="" view=HomePage/>
+                    <Route path="/specials" view=SpecialsPage/>
+                    <Route path="/price-index" view=PriceIndexPage/>
+                    <Route path="/shrinkflation" view=ShrinkflationPage/>
+                    <Route path="/lists" view=ShoppingListsPage/>
+                    <Route path="/lists/:id" view=ShoppingListPage/>
+                    <Route path="/barcode" view=BarcodeScanPage/>
+                    <Route path="/barcode/:code" view=BarcodePage/>
+                    <Route path="/login" view=LoginPage/>
+                    <Route path="/dashboard" view=DashboardPage/>
+                    <Route path="/watchlist" view=WatchlistPage/>
                     <Route path="/*any" view=NotFound/>
                 </Routes>
             </main>
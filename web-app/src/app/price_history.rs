@@ -0,0 +1,78 @@
+use leptos::*;
+use money::Money;
+use serde::{Deserialize, Serialize};
+
+use crate::components::line_chart::LineChart;
+
+/// How far back the history charts go
+#[cfg(feature = "ssr")]
+const HISTORY_DAYS: i64 = 90;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoreHistory {
+    pub supermarket_name: String,
+    /// (day, closing price), oldest first
+    pub points: Vec<(String, Money)>,
+    pub lowest: Money,
+    pub highest: Money,
+}
+
+#[server(GetPriceHistory, "/api")]
+pub async fn get_price_history(product_id: i32) -> Result<Vec<StoreHistory>, ServerFnError> {
+    use std::collections::BTreeMap;
+
+    use chrono::{Duration, Utc};
+    use entity::prelude::*;
+    use sea_orm::EntityTrait;
+
+    let db = crate::db::db()?;
+
+    let since = Utc::now() - Duration::days(HISTORY_DAYS);
+    let days = crate::db::price_history(&db, product_id, since.into()).await?;
+
+    let stores = Supermarkets::find()
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|x| (x.supermarket_id, x.name))
+        .collect::<BTreeMap<i32, String>>();
+
+    let mut by_store = BTreeMap::<i32, Vec<_>>::new();
+    for day in days {
+        by_store.entry(day.supermarket_id).or_default().push(day);
+    }
+
+    let history = by_store
+        .into_iter()
+        .map(|(supermarket_id, days)| StoreHistory {
+            supermarket_name: stores.get(&supermarket_id).cloned().unwrap_or_default(),
+            lowest: days.iter().map(|x| x.min_price).min().unwrap_or_default(),
+            highest: days.iter().map(|x| x.max_price).max().unwrap_or_default(),
+            points: days
+                .into_iter()
+                .map(|x| (x.bucket.format("%Y-%m-%d").to_string(), x.close_price))
+                .collect(),
+        })
+        .collect();
+
+    Ok(history)
+}
+
+/// Charts a product's daily closing price at each store
+#[component]
+pub fn PriceHistory(product_id: i32) -> impl IntoView {
+    let history = create_resource(move || product_id, get_price_history);
+
+    view! {
+        <Transition fallback=move || view! { <p>"Loading price history..."</p> }>
+            {move || history.get().map(|history| match history {
+                Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                Ok(history) => history.into_iter().map(|store| view! {
+                    <h3>{store.supermarket_name}</h3>
+                    <p>{format!("Between {} and {}", store.lowest, store.highest)}</p>
+                    <LineChart points=store.points.into_iter().map(|(day, price)| (day, price.dollars())).collect()/>
+                }).collect_view(),
+            })}
+        </Transition>
+    }
+}
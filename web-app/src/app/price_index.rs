@@ -0,0 +1,170 @@
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+use crate::components::line_chart::LineChart;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PriceIndexPoint {
+    pub period_start: String,
+    pub laspeyres: f64,
+    pub jevons: f64,
+    pub fisher: f64,
+    pub matched_items: i32,
+    pub basket_size: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PriceIndexScopes {
+    pub departments: Vec<String>,
+    pub brands: Vec<String>,
+}
+
+#[server(GetPriceIndex, "/api")]
+pub async fn get_price_index(
+    period: String,
+    department: Option<String>,
+    brand_name: Option<String>,
+) -> Result<Vec<PriceIndexPoint>, ServerFnError> {
+    use entity::{prelude::*, price_index};
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+
+    let db = crate::db::db()?;
+
+    let department_filter = match department {
+        Some(department) => price_index::Column::Department.eq(department),
+        None => price_index::Column::Department.is_null(),
+    };
+    let brand_filter = match brand_name {
+        Some(brand_name) => price_index::Column::BrandName.eq(brand_name),
+        None => price_index::Column::BrandName.is_null(),
+    };
+
+    let points = PriceIndex::find()
+        .filter(price_index::Column::PeriodType.eq(period))
+        .filter(department_filter)
+        .filter(brand_filter)
+        .order_by_asc(price_index::Column::PeriodStart)
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|x| PriceIndexPoint {
+            period_start: x.period_start.to_string(),
+            laspeyres: x.laspeyres,
+            jevons: x.jevons,
+            fisher: x.fisher,
+            matched_items: x.matched_items,
+            basket_size: x.basket_size,
+        })
+        .collect();
+
+    Ok(points)
+}
+
+#[server(GetPriceIndexScopes, "/api")]
+pub async fn get_price_index_scopes() -> Result<PriceIndexScopes, ServerFnError> {
+    use entity::{prelude::*, price_index};
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+
+    let db = crate::db::db()?;
+
+    let departments = PriceIndex::find()
+        .select_only()
+        .column(price_index::Column::Department)
+        .distinct()
+        .filter(price_index::Column::Department.is_not_null())
+        .order_by_asc(price_index::Column::Department)
+        .into_tuple::<String>()
+        .all(&db)
+        .await?;
+
+    let brands = PriceIndex::find()
+        .select_only()
+        .column(price_index::Column::BrandName)
+        .distinct()
+        .filter(price_index::Column::BrandName.is_not_null())
+        .order_by_asc(price_index::Column::BrandName)
+        .into_tuple::<String>()
+        .all(&db)
+        .await?;
+
+    Ok(PriceIndexScopes { departments, brands })
+}
+
+/// Charts the tracked basket's price index, overall or for one category or chain
+#[component]
+pub fn PriceIndexPage() -> impl IntoView {
+    let query = use_query_map();
+    let period = move || {
+        query.with(|q| q.get("period").cloned().unwrap_or("weekly".to_owned()))
+    };
+    let department = move || {
+        query.with(|q| q.get("department").cloned().filter(|x| !x.is_empty()))
+    };
+    let brand_name = move || {
+        query.with(|q| q.get("brand").cloned().filter(|x| !x.is_empty()))
+    };
+
+    let scopes = create_resource(|| (), |_| get_price_index_scopes());
+    let points = create_resource(
+        move || (period(), department(), brand_name()),
+        |(period, department, brand_name)| get_price_index(period, department, brand_name),
+    );
+
+    view! {
+        <h1>"Food price index"</h1>
+        <p>"Cost of a fixed basket of tracked products, relative to the first period of data (= 100)"</p>
+        <Transition fallback=move || view! { <p>"Loading filters..."</p> }>
+            {move || scopes.get().map(|scopes| match scopes {
+                Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                Ok(scopes) => view! {
+                    <Form method="GET" action="" class="filters">
+                        <select name="period">
+                            <option value="weekly" selected={period() == "weekly"}>"Weekly"</option>
+                            <option value="daily" selected={period() == "daily"}>"Daily"</option>
+                        </select>
+                        <select name="department">
+                            <option value="">"All categories"</option>
+                            {scopes.departments.into_iter().map(|x| {
+                                let selected = department() == Some(x.clone());
+                                view! { <option value=x.clone() selected=selected>{x}</option> }
+                            }).collect_view()}
+                        </select>
+                        <select name="brand">
+                            <option value="">"All chains"</option>
+                            {scopes.brands.into_iter().map(|x| {
+                                let selected = brand_name() == Some(x.clone());
+                                view! { <option value=x.clone() selected=selected>{x}</option> }
+                            }).collect_view()}
+                        </select>
+                        <input type="submit" value="Show"/>
+                    </Form>
+                }.into_view(),
+            })}
+        </Transition>
+        <Transition fallback=move || view! { <p>"Loading index..."</p> }>
+            {move || points.get().map(|points| match points {
+                Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                Ok(points) => {
+                    let latest = points.last().cloned();
+                    let series = points
+                        .into_iter()
+                        .map(|x| (x.period_start, x.laspeyres))
+                        .collect::<Vec<(String, f64)>>();
+
+                    view! {
+                        <LineChart points=series/>
+                        {latest.map(|x| view! {
+                            <p>
+                                {format!(
+                                    "{}: Laspeyres {:.1}, Jevons {:.1}, Fisher {:.1} ({} of {} basket items priced)",
+                                    x.period_start, x.laspeyres, x.jevons, x.fisher, x.matched_items, x.basket_size
+                                )}
+                            </p>
+                        })}
+                    }.into_view()
+                }
+            })}
+        </Transition>
+    }
+}
@@ -0,0 +1,518 @@
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+use crate::basket::BasketComparison;
+
+/// How many substitutes to consider for each item that allows them
+#[cfg(feature = "ssr")]
+const MAX_SUBSTITUTES: usize = 10;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShoppingListSummary {
+    pub id: i32,
+    pub name: String,
+    pub item_count: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShoppingListEntry {
+    pub item_id: i32,
+    pub product_id: i32,
+    pub product_title: String,
+    pub quantity: i32,
+    pub allow_substitute: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShoppingListDetail {
+    pub id: i32,
+    pub name: String,
+    pub items: Vec<ShoppingListEntry>,
+    pub comparison: BasketComparison,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProductSummary {
+    pub product_id: i32,
+    pub product_title: String,
+    pub product_brand: Option<String>,
+}
+
+/// Lists belong to the logged in user, or to the browser if nobody is logged in
+#[cfg(feature = "ssr")]
+async fn list_owner() -> Result<sea_orm::Condition, ServerFnError> {
+    use entity::shopping_list;
+    use sea_orm::{ColumnTrait, Condition};
+
+    let condition = match crate::auth::current_user().await? {
+        Some(user) => Condition::all().add(shopping_list::Column::UserId.eq(user.id)),
+        None => Condition::all()
+            .add(shopping_list::Column::Owner.eq(crate::shopper::shopper_id().await?))
+            .add(shopping_list::Column::UserId.is_null()),
+    };
+
+    Ok(condition)
+}
+
+/// Finds the shopping list, making sure it belongs to whoever is asking for it
+#[cfg(feature = "ssr")]
+async fn owned_list(db: &sea_orm::DatabaseConnection, list_id: i32) -> Result<entity::shopping_list::Model, ServerFnError> {
+    use entity::prelude::*;
+    use sea_orm::{EntityTrait, QueryFilter};
+
+    ShoppingList::find_by_id(list_id)
+        .filter(list_owner().await?)
+        .one(db)
+        .await?
+        .ok_or_else(|| ServerFnError::ServerError("Shopping list not found".to_owned()))
+}
+
+/// Every product in these departments, by department, to pick substitutes from
+#[cfg(feature = "ssr")]
+async fn load_departments(db: &sea_orm::DatabaseConnection, departments: Vec<String>) -> Result<std::collections::HashMap<String, Vec<entity::product_db::Model>>, sea_orm::DbErr> {
+    use std::collections::HashMap;
+
+    use entity::{prelude::*, product_db};
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+    if departments.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut by_department = HashMap::<String, Vec<product_db::Model>>::new();
+    for product in ProductDb::find()
+        .filter(product_db::Column::Department.is_in(departments))
+        .all(db)
+        .await?
+    {
+        if let Some(department) = product.department.clone() {
+            by_department.entry(department).or_default().push(product);
+        }
+    }
+
+    Ok(by_department)
+}
+
+/// Similar products that could replace this one, out of the products in its
+/// department: same unit and pack count, a size within 25%, and at least one word of
+/// the title in common.
+#[cfg(feature = "ssr")]
+fn find_substitutes(product: &entity::product_db::Model, department: &[entity::product_db::Model]) -> Vec<(i32, String)> {
+    let words = |text: &str| {
+        text.to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.len() > 2)
+            .map(|word| word.to_owned())
+            .collect::<Vec<String>>()
+    };
+    let brand_words = words(product.product_brand.as_deref().unwrap_or_default());
+    let title_words = words(&product.product_title)
+        .into_iter()
+        .filter(|word| !brand_words.contains(word))
+        .collect::<Vec<String>>();
+
+    let mut candidates = department
+        .iter()
+        .filter(|candidate| candidate.product_id != product.product_id)
+        .filter(|candidate| candidate.quantity == product.quantity && candidate.unit == product.unit)
+        .filter(|candidate| match (product.size, candidate.size) {
+            (Some(size), Some(candidate_size)) => candidate_size >= size * 0.8 && candidate_size <= size * 1.25,
+            (Some(_), None) => false,
+            (None, _) => true,
+        })
+        .map(|candidate| {
+            let shared = words(&candidate.product_title)
+                .iter()
+                .filter(|word| title_words.contains(word))
+                .count();
+            (shared, candidate)
+        })
+        .filter(|(shared, _)| *shared > 0)
+        .collect::<Vec<_>>();
+
+    candidates.sort_by(|a, b| b.0.cmp(&a.0));
+
+    candidates
+        .into_iter()
+        .take(MAX_SUBSTITUTES)
+        .map(|(_, candidate)| (candidate.product_id, candidate.product_title.clone()))
+        .collect()
+}
+
+#[server(GetShoppingLists, "/api")]
+pub async fn get_shopping_lists() -> Result<Vec<ShoppingListSummary>, ServerFnError> {
+    use entity::{prelude::*, shopping_list};
+    use sea_orm::{EntityTrait, QueryFilter, QueryOrder};
+
+    let db = crate::db::db()?;
+
+    let lists = ShoppingList::find()
+        .filter(list_owner().await?)
+        .order_by_desc(shopping_list::Column::CreatedAt)
+        .find_with_related(ShoppingListItem)
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|(list, items)| ShoppingListSummary {
+            id: list.id,
+            name: list.name,
+            item_count: items.len(),
+        })
+        .collect();
+
+    Ok(lists)
+}
+
+#[server(CreateShoppingList, "/api")]
+pub async fn create_shopping_list(name: String) -> Result<(), ServerFnError> {
+    use entity::shopping_list;
+    use sea_orm::{ActiveModelTrait, Set};
+
+    let db = crate::db::db()?;
+    let owner = crate::shopper::shopper_id().await?;
+    let user = crate::auth::current_user().await?;
+
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ServerFnError::Args("A shopping list needs a name".to_owned()));
+    }
+
+    let list = shopping_list::ActiveModel {
+        owner: Set(owner),
+        name: Set(name.to_owned()),
+        user_id: Set(user.map(|x| x.id)),
+        ..Default::default()
+    }.insert(&db).await?;
+
+    leptos_actix::redirect(&format!("/lists/{}", list.id));
+    Ok(())
+}
+
+#[server(GetShoppingList, "/api")]
+pub async fn get_shopping_list(list_id: i32) -> Result<ShoppingListDetail, ServerFnError> {
+    use std::collections::HashSet;
+
+    use entity::{prelude::*, shopping_list_item};
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+
+    use crate::basket::{compare_basket, BasketItem, Store, StorePrice};
+
+    let db = crate::db::db()?;
+    let list = owned_list(&db, list_id).await?;
+
+    let rows = ShoppingListItem::find()
+        .filter(shopping_list_item::Column::ListId.eq(list.id))
+        .order_by_asc(shopping_list_item::Column::Id)
+        .find_also_related(ProductDb)
+        .all(&db)
+        .await?;
+
+    // Substitutes for every item come out of one load of their departments
+    let departments = rows
+        .iter()
+        .filter(|(item, _)| item.allow_substitute)
+        .filter_map(|(_, product)| product.as_ref()?.department.clone())
+        .collect::<HashSet<String>>();
+    let departments = load_departments(&db, departments.into_iter().collect()).await?;
+
+    let mut items = Vec::new();
+    let mut basket = Vec::new();
+    let mut product_ids = HashSet::new();
+    for (item, product) in rows {
+        let Some(product) = product else {
+            continue;
+        };
+
+        let department = product.department.as_ref().and_then(|x| departments.get(x));
+        let substitutes = match department {
+            Some(department) if item.allow_substitute => find_substitutes(&product, department),
+            _ => Vec::new(),
+        };
+
+        product_ids.insert(product.product_id);
+        product_ids.extend(substitutes.iter().map(|(product_id, _)| *product_id));
+
+        items.push(ShoppingListEntry {
+            item_id: item.id,
+            product_id: product.product_id,
+            product_title: product.product_title.clone(),
+            quantity: item.quantity,
+            allow_substitute: item.allow_substitute,
+        });
+        basket.push(BasketItem {
+            item_id: item.id,
+            product_id: product.product_id,
+            title: product.product_title,
+            quantity: item.quantity,
+            substitutes,
+        });
+    }
+
+    let prices = crate::db::latest_prices(&db, product_ids.into_iter().collect())
+        .await?
+        .into_iter()
+        .map(|x| StorePrice {
+            product_id: x.product_id,
+            supermarket_id: x.supermarket_id,
+            price: x.price,
+            multibuy: x.multibuy_quantity.zip(x.multibuy_price),
+        })
+        .collect::<Vec<StorePrice>>();
+
+    let stores = Supermarkets::find()
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|x| Store { supermarket_id: x.supermarket_id, name: x.name })
+        .collect::<Vec<Store>>();
+
+    Ok(ShoppingListDetail {
+        id: list.id,
+        name: list.name,
+        items,
+        comparison: compare_basket(&basket, &prices, &stores),
+    })
+}
+
+#[server(AddListItem, "/api")]
+pub async fn add_list_item(
+    list_id: i32,
+    product_id: i32,
+    quantity: i32,
+    allow_substitute: Option<String>,
+) -> Result<(), ServerFnError> {
+    use entity::shopping_list_item;
+    use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+
+    let db = crate::db::db()?;
+    let list = owned_list(&db, list_id).await?;
+
+    if quantity < 1 {
+        return Err(ServerFnError::Args("Quantity must be at least 1".to_owned()));
+    }
+
+    let existing = shopping_list_item::Entity::find()
+        .filter(shopping_list_item::Column::ListId.eq(list.id))
+        .filter(shopping_list_item::Column::ProductId.eq(product_id))
+        .one(&db)
+        .await?;
+
+    // Adding something already on the list just buys more of it
+    match existing {
+        Some(existing) => {
+            let new_quantity = existing.quantity + quantity;
+            let mut item: shopping_list_item::ActiveModel = existing.into();
+            item.quantity = Set(new_quantity);
+            item.update(&db).await?;
+        }
+        None => {
+            shopping_list_item::ActiveModel {
+                list_id: Set(list.id),
+                product_id: Set(product_id),
+                quantity: Set(quantity),
+                allow_substitute: Set(allow_substitute.is_some()),
+                ..Default::default()
+            }.insert(&db).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[server(RemoveListItem, "/api")]
+pub async fn remove_list_item(list_id: i32, item_id: i32) -> Result<(), ServerFnError> {
+    use entity::shopping_list_item;
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+    let db = crate::db::db()?;
+    let list = owned_list(&db, list_id).await?;
+
+    shopping_list_item::Entity::delete_many()
+        .filter(shopping_list_item::Column::ListId.eq(list.id))
+        .filter(shopping_list_item::Column::Id.eq(item_id))
+        .exec(&db)
+        .await?;
+
+    Ok(())
+}
+
+#[server(SearchProducts, "/api")]
+pub async fn search_products(query: String) -> Result<Vec<ProductSummary>, ServerFnError> {
+    use entity::{prelude::*, product_db};
+    use sea_orm::{sea_query::{extension::postgres::PgExpr, Expr}, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let db = crate::db::db()?;
+
+    let products = ProductDb::find()
+        .filter(Expr::col(product_db::Column::ProductTitle).ilike(format!("%{query}%")))
+        .order_by_asc(product_db::Column::ProductTitle)
+        .limit(25)
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|x| ProductSummary {
+            product_id: x.product_id,
+            product_title: x.product_title,
+            product_brand: x.product_brand,
+        })
+        .collect();
+
+    Ok(products)
+}
+
+/// The visitor's shopping lists
+#[component]
+pub fn ShoppingListsPage() -> impl IntoView {
+    let create_list = create_server_action::<CreateShoppingList>();
+    let lists = create_resource(move || create_list.version().get(), |_| get_shopping_lists());
+
+    view! {
+        <h1>"Shopping lists"</h1>
+        <ActionForm action=create_list>
+            <input type="text" name="name" placeholder="List name" required=true/>
+            <input type="submit" value="Create list"/>
+        </ActionForm>
+        <Transition fallback=move || view! { <p>"Loading lists..."</p> }>
+            {move || lists.get().map(|lists| match lists {
+                Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                Ok(lists) if lists.is_empty() => view! { <p>"No shopping lists yet"</p> }.into_view(),
+                Ok(lists) => view! {
+                    <ul>
+                        {lists.into_iter().map(|list| view! {
+                            <li>
+                                <A href=format!("/lists/{}", list.id)>{list.name}</A>
+                                {format!(" ({} items)", list.item_count)}
+                            </li>
+                        }).collect_view()}
+                    </ul>
+                }.into_view(),
+            })}
+        </Transition>
+    }
+}
+
+/// One shopping list, with what it would cost at each store
+#[component]
+pub fn ShoppingListPage() -> impl IntoView {
+    let params = use_params_map();
+    let query = use_query_map();
+    let list_id = move || params.with(|p| p.get("id").and_then(|x| x.parse::<i32>().ok()).unwrap_or_default());
+    let search = move || query.with(|q| q.get("q").cloned().unwrap_or_default());
+
+    let add_item = create_server_action::<AddListItem>();
+    let remove_item = create_server_action::<RemoveListItem>();
+
+    let list = create_resource(
+        move || (list_id(), add_item.version().get(), remove_item.version().get()),
+        |(list_id, _, _)| get_shopping_list(list_id),
+    );
+    let results = create_resource(search, search_products);
+
+    view! {
+        <Transition fallback=move || view! { <p>"Loading list..."</p> }>
+            {move || list.get().map(|list| match list {
+                Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                Ok(list) => view! {
+                    <h1>{list.name}</h1>
+                    <table>
+                        <thead>
+                            <tr><th>"Product"</th><th>"Quantity"</th><th>"Substitutes"</th><th></th></tr>
+                        </thead>
+                        <tbody>
+                            {list.items.into_iter().map(|item| view! {
+                                <tr>
+                                    <td>{item.product_title}</td>
+                                    <td>{item.quantity}</td>
+                                    <td>{if item.allow_substitute { "Allowed" } else { "No" }}</td>
+                                    <td>
+                                        <ActionForm action=remove_item>
+                                            <input type="hidden" name="list_id" value=list.id/>
+                                            <input type="hidden" name="item_id" value=item.item_id/>
+                                            <input type="submit" value="Remove"/>
+                                        </ActionForm>
+                                    </td>
+                                </tr>
+                            }).collect_view()}
+                        </tbody>
+                    </table>
+                    <BasketComparisonView comparison=list.comparison/>
+                }.into_view(),
+            })}
+        </Transition>
+
+        <h2>"Add products"</h2>
+        <Form method="GET" action="" class="filters">
+            <input type="search" name="q" placeholder="Search products" value=search/>
+            <input type="submit" value="Search"/>
+        </Form>
+        <Transition fallback=move || view! { <p>"Searching..."</p> }>
+            {move || results.get().map(|results| match results {
+                Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                Ok(results) => view! {
+                    <ul class="search-results">
+                        {results.into_iter().map(|product| view! {
+                            <li>
+                                {product.product_brand.map(|brand| format!("{brand} "))}
+                                {product.product_title}
+                                <ActionForm action=add_item>
+                                    <input type="hidden" name="list_id" value=list_id/>
+                                    <input type="hidden" name="product_id" value=product.product_id/>
+                                    <input type="number" name="quantity" value="1" min="1"/>
+                                    <label>
+                                        <input type="checkbox" name="allow_substitute" checked=true/>
+                                        "Allow substitutes"
+                                    </label>
+                                    <input type="submit" value="Add"/>
+                                </ActionForm>
+                            </li>
+                        }).collect_view()}
+                    </ul>
+                }.into_view(),
+            })}
+        </Transition>
+    }
+}
+
+#[component]
+fn BasketComparisonView(comparison: BasketComparison) -> impl IntoView {
+    let cheapest_store = comparison.quotes.first().map(|x| x.total);
+
+    view! {
+        <h2>"Cost by store"</h2>
+        <table>
+            <thead>
+                <tr><th>"Store"</th><th>"Total"</th><th>"Substituted"</th><th>"Missing"</th></tr>
+            </thead>
+            <tbody>
+                {comparison.quotes.into_iter().map(|quote| view! {
+                    <tr>
+                        <td>{quote.supermarket_name}</td>
+                        <td>{quote.total.to_string()}</td>
+                        <td>{quote.lines.iter().filter(|x| x.substituted).count()}</td>
+                        <td title=quote.missing.join(", ")>{quote.missing.len()}</td>
+                    </tr>
+                }).collect_view()}
+            </tbody>
+        </table>
+        {comparison.best_split.map(|split| view! {
+            <h2>{format!("Cheapest split: {} + {}", split.supermarket_names.0, split.supermarket_names.1)}</h2>
+            <p>
+                {split.total.to_string()}
+                {cheapest_store
+                    .filter(|x| *x > split.total)
+                    .map(|x| format!(", saving {} on one store", x - split.total))}
+            </p>
+            <ul>
+                {split.lines.into_iter().map(|(store, line)| view! {
+                    <li>{format!("{} x{} at {}: {}", line.product_title, line.quantity, store, line.cost)}</li>
+                }).collect_view()}
+            </ul>
+        })}
+    }
+}
@@ -0,0 +1,115 @@
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+use crate::shrinkflation::Shrinkflation;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShrinkflationFilters {
+    pub brands: Vec<String>,
+    pub departments: Vec<String>,
+}
+
+#[server(GetShrinkflation, "/api")]
+pub async fn get_shrinkflation(
+    brand: Option<String>,
+    department: Option<String>,
+) -> Result<Vec<Shrinkflation>, ServerFnError> {
+    let db = crate::db::db()?;
+
+    Ok(crate::shrinkflation::report(&db, brand, department).await?)
+}
+
+/// Only the brands and categories that have shrunk something
+#[server(GetShrinkflationFilters, "/api")]
+pub async fn get_shrinkflation_filters() -> Result<ShrinkflationFilters, ServerFnError> {
+    let db = crate::db::db()?;
+    let items = crate::shrinkflation::report(&db, None, None).await?;
+
+    let mut brands = items.iter().filter_map(|x| x.product_brand.clone()).collect::<Vec<_>>();
+    brands.sort();
+    brands.dedup();
+    let mut departments = items.iter().filter_map(|x| x.department.clone()).collect::<Vec<_>>();
+    departments.sort();
+    departments.dedup();
+
+    Ok(ShrinkflationFilters { brands, departments })
+}
+
+/// Products that got smaller without getting cheaper, worst per-unit price rise first
+#[component]
+pub fn ShrinkflationPage() -> impl IntoView {
+    let query = use_query_map();
+    let brand = move || {
+        query.with(|q| q.get("brand").cloned().filter(|x| !x.is_empty()))
+    };
+    let department = move || {
+        query.with(|q| q.get("department").cloned().filter(|x| !x.is_empty()))
+    };
+
+    let filters = create_resource(|| (), |_| get_shrinkflation_filters());
+    let items = create_resource(
+        move || (brand(), department()),
+        |(brand, department)| get_shrinkflation(brand, department),
+    );
+
+    view! {
+        <h1>"Shrinkflation"</h1>
+        <Transition fallback=move || view! { <p>"Loading filters..."</p> }>
+            {move || filters.get().map(|filters| match filters {
+                Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                Ok(filters) => view! {
+                    <Form method="GET" action="" class="filters">
+                        <select name="brand">
+                            <option value="">"All brands"</option>
+                            {filters.brands.into_iter().map(|x| {
+                                let selected = brand() == Some(x.clone());
+                                view! { <option value=x.clone() selected=selected>{x}</option> }
+                            }).collect_view()}
+                        </select>
+                        <select name="department">
+                            <option value="">"All categories"</option>
+                            {filters.departments.into_iter().map(|x| {
+                                let selected = department() == Some(x.clone());
+                                view! { <option value=x.clone() selected=selected>{x}</option> }
+                            }).collect_view()}
+                        </select>
+                        <input type="submit" value="Filter"/>
+                    </Form>
+                }.into_view(),
+            })}
+        </Transition>
+        <Transition fallback=move || view! { <p>"Loading shrinkflation..."</p> }>
+            {move || items.get().map(|items| match items {
+                Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                Ok(items) if items.is_empty() => view! { <p>"No shrinkflation found"</p> }.into_view(),
+                Ok(items) => view! {
+                    <table class="shrinkflation">
+                        <thead>
+                            <tr>
+                                <th>"Product"</th>
+                                <th>"Brand"</th>
+                                <th>"Size"</th>
+                                <th>"Price"</th>
+                                <th>"Per unit"</th>
+                                <th>"Changed"</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {items.into_iter().map(|item| view! {
+                                <tr>
+                                    <td>{item.product_title}</td>
+                                    <td>{item.product_brand.unwrap_or_default()}</td>
+                                    <td>{item.old_size} " → " {item.new_size}</td>
+                                    <td>{item.old_price.to_string()} " → " {item.new_price.to_string()}</td>
+                                    <td class="price-up">{format!("+{:.0}%", item.per_unit_increase * 100.0)}</td>
+                                    <td>{item.changed_on}</td>
+                                </tr>
+                            }).collect_view()}
+                        </tbody>
+                    </table>
+                }.into_view(),
+            })}
+        </Transition>
+    }
+}
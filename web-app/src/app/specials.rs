@@ -0,0 +1,273 @@
+use leptos::*;
+use leptos_router::*;
+use money::Money;
+use serde::{Deserialize, Serialize};
+
+/// How far back to look when working out a product's regular shelf price
+#[cfg(feature = "ssr")]
+const HISTORY_WINDOW_DAYS: i32 = 90;
+
+/// How recently a price has to have been seen to count as what the store charges now
+#[cfg(feature = "ssr")]
+const CURRENT_WINDOW_DAYS: i32 = 7;
+
+/// Non-special prices needed in the history window before a "was" price that was
+/// never charged is called fake. A product that's been on special the whole time, or
+/// was first seen on special, hasn't been caught out.
+#[cfg(feature = "ssr")]
+const MIN_REGULAR_SAMPLES: i64 = 3;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Special {
+    pub product_id: i32,
+    pub product_title: String,
+    pub product_brand: Option<String>,
+    pub image_url: Option<String>,
+    pub department: Option<String>,
+    pub supermarket_id: i32,
+    pub supermarket_name: String,
+    pub price: Money,
+    /// The "was" price the store advertises
+    pub claimed_price: Option<Money>,
+    /// Median non-special price charged over the history window
+    pub regular_price: Option<Money>,
+    pub claimed_discount: Option<f32>,
+    pub real_discount: Option<f32>,
+    /// The advertised "was" price was never actually charged
+    pub fake_special: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpecialFilters {
+    pub departments: Vec<String>,
+    pub supermarkets: Vec<(i32, String)>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, sea_orm::FromQueryResult)]
+struct SpecialRow {
+    product_id: i32,
+    product_title: String,
+    product_brand: Option<String>,
+    image_url: Option<String>,
+    image_hash: Option<String>,
+    department: Option<String>,
+    supermarket_id: i32,
+    supermarket_name: String,
+    price: Money,
+    original_price: Option<Money>,
+    regular_price: Option<Money>,
+    regular_samples: i64,
+    charged_original: bool,
+}
+
+#[server(GetSpecials, "/api")]
+pub async fn get_specials(
+    department: Option<String>,
+    supermarket_id: Option<i32>,
+) -> Result<Vec<Special>, ServerFnError> {
+    use sea_orm::{DbBackend, FromQueryResult, Statement};
+
+    let db = crate::db::db()?;
+
+    // Take the latest recent price for each product at each store, then compare
+    // specials against what that store charged when it wasn't on special. Bounding
+    // the latest prices by time lets only the recent partitions be read.
+    let rows = SpecialRow::find_by_statement(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        WITH latest AS (
+            SELECT DISTINCT ON (product_id, supermarket_id)
+                product_id, supermarket_id, price, original_price, on_special, timestamp
+            FROM supermarket_price
+            WHERE NOT flagged
+                AND timestamp >= now() - make_interval(days => $4)
+            ORDER BY product_id, supermarket_id, timestamp DESC
+        )
+        SELECT
+            l.product_id, p.product_title, p.product_brand, p.image_url, i.content_hash AS image_hash, p.department,
+            l.supermarket_id, s.name AS supermarket_name, l.price, l.original_price,
+            h.regular_price, h.regular_samples, h.charged_original
+        FROM latest l
+        JOIN product_db p ON p.product_id = l.product_id
+        JOIN supermarkets s ON s.supermarket_id = l.supermarket_id
+        LEFT JOIN product_image_source pis ON pis.url = p.image_url
+        LEFT JOIN product_image i ON i.id = pis.image_id
+        LEFT JOIN LATERAL (
+            SELECT
+                percentile_cont(0.5) WITHIN GROUP (ORDER BY h.price)::numeric(12, 2) AS regular_price,
+                count(h.price) AS regular_samples,
+                COALESCE(bool_or(h.price = l.original_price), false) AS charged_original
+            FROM supermarket_price h
+            WHERE h.product_id = l.product_id
+                AND h.supermarket_id = l.supermarket_id
+                AND h.timestamp >= l.timestamp - make_interval(days => $1)
+                AND NOT COALESCE(h.on_special, false)
+                AND NOT h.flagged
+        ) h ON true
+        WHERE COALESCE(l.on_special, false)
+            AND ($2::text IS NULL OR p.department = $2)
+            AND ($3::int IS NULL OR l.supermarket_id = $3)
+        "#,
+        [
+            HISTORY_WINDOW_DAYS.into(),
+            department.into(),
+            supermarket_id.into(),
+            CURRENT_WINDOW_DAYS.into(),
+        ],
+    ))
+    .all(&db)
+    .await?;
+
+    let mut specials = rows
+        .into_iter()
+        .map(|row| {
+            let discount = |reference: Money| row.price.ratio(reference).map(|x| (1.0 - x) as f32);
+
+            Special {
+                product_id: row.product_id,
+                product_title: row.product_title,
+                product_brand: row.product_brand,
+                image_url: crate::images::product_image_url(row.image_hash, row.image_url, crate::images::LARGE),
+                department: row.department,
+                supermarket_id: row.supermarket_id,
+                supermarket_name: row.supermarket_name,
+                price: row.price,
+                claimed_price: row.original_price,
+                regular_price: row.regular_price,
+                claimed_discount: row.original_price.and_then(discount),
+                real_discount: row.regular_price.and_then(discount),
+                fake_special: row.original_price.is_some()
+                    && row.regular_price.is_some()
+                    && row.regular_samples >= MIN_REGULAR_SAMPLES
+                    && !row.charged_original,
+            }
+        })
+        .collect::<Vec<Special>>();
+
+    // Biggest real discount first, specials with no price history go last
+    specials.sort_by(|a, b| {
+        b.real_discount
+            .unwrap_or(f32::MIN)
+            .total_cmp(&a.real_discount.unwrap_or(f32::MIN))
+    });
+
+    Ok(specials)
+}
+
+#[server(GetSpecialFilters, "/api")]
+pub async fn get_special_filters() -> Result<SpecialFilters, ServerFnError> {
+    use entity::{prelude::*, product_db, supermarkets};
+    use sea_orm::{EntityTrait, QueryFilter, QueryOrder, QuerySelect, ColumnTrait};
+
+    let db = crate::db::db()?;
+
+    let departments = ProductDb::find()
+        .select_only()
+        .column(product_db::Column::Department)
+        .distinct()
+        .filter(product_db::Column::Department.is_not_null())
+        .order_by_asc(product_db::Column::Department)
+        .into_tuple::<String>()
+        .all(&db)
+        .await?;
+
+    let supermarkets = Supermarkets::find()
+        .order_by_asc(supermarkets::Column::Name)
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|x| (x.supermarket_id, x.name))
+        .collect();
+
+    Ok(SpecialFilters { departments, supermarkets })
+}
+
+fn format_discount(discount: Option<f32>) -> String {
+    discount.map_or("-".to_owned(), |x| format!("{:.0}%", x * 100.0))
+}
+
+/// Lists current specials, ranked by the discount against each product's own price history
+#[component]
+pub fn SpecialsPage() -> impl IntoView {
+    let query = use_query_map();
+    let department = move || {
+        query.with(|q| q.get("department").cloned().filter(|x| !x.is_empty()))
+    };
+    let supermarket_id = move || {
+        query.with(|q| q.get("store").and_then(|x| x.parse::<i32>().ok()))
+    };
+
+    let filters = create_resource(|| (), |_| get_special_filters());
+    let specials = create_resource(
+        move || (department(), supermarket_id()),
+        |(department, supermarket_id)| get_specials(department, supermarket_id),
+    );
+
+    view! {
+        <h1>"Specials"</h1>
+        <Transition fallback=move || view! { <p>"Loading filters..."</p> }>
+            {move || filters.get().map(|filters| match filters {
+                Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                Ok(filters) => view! {
+                    <Form method="GET" action="" class="filters">
+                        <select name="department">
+                            <option value="">"All categories"</option>
+                            {filters.departments.into_iter().map(|x| {
+                                let selected = department() == Some(x.clone());
+                                view! { <option value=x.clone() selected=selected>{x}</option> }
+                            }).collect_view()}
+                        </select>
+                        <select name="store">
+                            <option value="">"All stores"</option>
+                            {filters.supermarkets.into_iter().map(|(id, name)| {
+                                let selected = supermarket_id() == Some(id);
+                                view! { <option value=id selected=selected>{name}</option> }
+                            }).collect_view()}
+                        </select>
+                        <input type="submit" value="Filter"/>
+                    </Form>
+                }.into_view(),
+            })}
+        </Transition>
+        <Transition fallback=move || view! { <p>"Loading specials..."</p> }>
+            {move || specials.get().map(|specials| match specials {
+                Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                Ok(specials) if specials.is_empty() => view! { <p>"No specials found"</p> }.into_view(),
+                Ok(specials) => view! {
+                    <table class="specials">
+                        <thead>
+                            <tr>
+                                <th>"Product"</th>
+                                <th>"Store"</th>
+                                <th>"Price"</th>
+                                <th>"Was (claimed)"</th>
+                                <th>"Usually"</th>
+                                <th>"Real discount"</th>
+                                <th>"Claimed discount"</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {specials.into_iter().map(|special| view! {
+                                <tr class:fake=special.fake_special>
+                                    <td>
+                                        {special.product_title}
+                                        {special.fake_special.then(|| view! {
+                                            <span class="badge" title="The was price was never charged">"Fake special"</span>
+                                        })}
+                                    </td>
+                                    <td>{special.supermarket_name}</td>
+                                    <td>{special.price.to_string()}</td>
+                                    <td>{special.claimed_price.map_or("-".to_owned(), |x| x.to_string())}</td>
+                                    <td>{special.regular_price.map_or("-".to_owned(), |x| x.to_string())}</td>
+                                    <td>{format_discount(special.real_discount)}</td>
+                                    <td>{format_discount(special.claimed_discount)}</td>
+                                </tr>
+                            }).collect_view()}
+                        </tbody>
+                    </table>
+                }.into_view(),
+            })}
+        </Transition>
+    }
+}
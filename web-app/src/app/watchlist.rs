@@ -0,0 +1,318 @@
+use leptos::*;
+use leptos_router::*;
+use money::Money;
+use serde::{Deserialize, Serialize};
+
+use super::shopping_list::{search_products, ProductSummary};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WatchedProduct {
+    pub watch_id: i32,
+    pub product_id: i32,
+    pub product_title: String,
+    pub product_brand: Option<String>,
+    /// (store name, latest price)
+    pub prices: Vec<(String, Money)>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PriceChange {
+    pub product_id: i32,
+    pub product_title: String,
+    pub supermarket_name: String,
+    pub old_price: Money,
+    pub new_price: Money,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Dashboard {
+    pub email: String,
+    /// When the user last looked at their dashboard
+    pub last_visit: Option<String>,
+    pub changes: Vec<PriceChange>,
+    pub watched_count: usize,
+}
+
+#[server(GetWatchlist, "/api")]
+pub async fn get_watchlist() -> Result<Vec<WatchedProduct>, ServerFnError> {
+    use std::collections::HashMap;
+
+    use entity::{prelude::*, watchlist};
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+
+    let db = crate::db::db()?;
+    let user = crate::auth::require_user().await?;
+
+    let watched = Watchlist::find()
+        .filter(watchlist::Column::UserId.eq(user.id))
+        .order_by_asc(watchlist::Column::CreatedAt)
+        .find_also_related(ProductDb)
+        .all(&db)
+        .await?;
+
+    let stores = Supermarkets::find()
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|x| (x.supermarket_id, x.name))
+        .collect::<HashMap<i32, String>>();
+
+    let product_ids = watched.iter().map(|(x, _)| x.product_id).collect();
+    let prices = crate::db::latest_prices(&db, product_ids).await?;
+
+    let products = watched
+        .into_iter()
+        .filter_map(|(watch, product)| product.map(|product| (watch, product)))
+        .map(|(watch, product)| WatchedProduct {
+            watch_id: watch.id,
+            product_id: product.product_id,
+            product_title: product.product_title,
+            product_brand: product.product_brand,
+            prices: prices
+                .iter()
+                .filter(|x| x.product_id == product.product_id)
+                .map(|x| (stores.get(&x.supermarket_id).cloned().unwrap_or_default(), x.price))
+                .collect(),
+        })
+        .collect();
+
+    Ok(products)
+}
+
+#[server(AddToWatchlist, "/api")]
+pub async fn add_to_watchlist(product_id: i32) -> Result<(), ServerFnError> {
+    use entity::watchlist;
+    use sea_orm::{sea_query::OnConflict, EntityTrait, Set};
+
+    let db = crate::db::db()?;
+    let user = crate::auth::require_user().await?;
+
+    // Watching something twice is a no-op
+    watchlist::Entity::insert(watchlist::ActiveModel {
+        user_id: Set(user.id),
+        product_id: Set(product_id),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::columns([watchlist::Column::UserId, watchlist::Column::ProductId])
+            .do_nothing()
+            .to_owned()
+    )
+    .exec_without_returning(&db)
+    .await?;
+
+    Ok(())
+}
+
+#[server(RemoveFromWatchlist, "/api")]
+pub async fn remove_from_watchlist(watch_id: i32) -> Result<(), ServerFnError> {
+    use entity::watchlist;
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+    let db = crate::db::db()?;
+    let user = crate::auth::require_user().await?;
+
+    watchlist::Entity::delete_many()
+        .filter(watchlist::Column::Id.eq(watch_id))
+        .filter(watchlist::Column::UserId.eq(user.id))
+        .exec(&db)
+        .await?;
+
+    Ok(())
+}
+
+/// Price changes to watched products since the last visit, which this then updates
+#[server(GetDashboard, "/api")]
+pub async fn get_dashboard() -> Result<Dashboard, ServerFnError> {
+    use std::collections::HashMap;
+
+    use chrono::Utc;
+    use entity::{prelude::*, users, watchlist};
+    use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+
+    let db = crate::db::db()?;
+    let user = crate::auth::require_user().await?;
+
+    let watched = Watchlist::find()
+        .filter(watchlist::Column::UserId.eq(user.id))
+        .find_also_related(ProductDb)
+        .all(&db)
+        .await?;
+    let watched_count = watched.len();
+
+    let titles = watched
+        .into_iter()
+        .filter_map(|(_, product)| product)
+        .map(|x| (x.product_id, x.product_title))
+        .collect::<HashMap<i32, String>>();
+    let stores = Supermarkets::find()
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|x| (x.supermarket_id, x.name))
+        .collect::<HashMap<i32, String>>();
+
+    let mut changes = Vec::new();
+    if let Some(last_visit) = user.last_visit_at {
+        let product_ids = titles.keys().copied().collect::<Vec<i32>>();
+        let old_prices = crate::db::prices_as_of(&db, product_ids.clone(), last_visit)
+            .await?
+            .into_iter()
+            .map(|x| ((x.product_id, x.supermarket_id), x.price))
+            .collect::<HashMap<(i32, i32), Money>>();
+
+        for price in crate::db::latest_prices(&db, product_ids).await? {
+            let Some(old_price) = old_prices.get(&(price.product_id, price.supermarket_id)) else {
+                continue;
+            };
+            if *old_price == price.price {
+                continue;
+            }
+
+            changes.push(PriceChange {
+                product_id: price.product_id,
+                product_title: titles.get(&price.product_id).cloned().unwrap_or_default(),
+                supermarket_name: stores.get(&price.supermarket_id).cloned().unwrap_or_default(),
+                old_price: *old_price,
+                new_price: price.price,
+            });
+        }
+    }
+
+    // Biggest rises first
+    changes.sort_by_key(|x| -(x.new_price - x.old_price));
+
+    let dashboard = Dashboard {
+        email: user.email.clone(),
+        last_visit: user.last_visit_at.map(|x| x.format("%Y-%m-%d %H:%M").to_string()),
+        changes,
+        watched_count,
+    };
+
+    let mut user: users::ActiveModel = user.into();
+    user.last_visit_at = Set(Some(Utc::now().into()));
+    user.update(&db).await?;
+
+    Ok(dashboard)
+}
+
+/// e.g. "+$0.50" or "-$0.50"
+fn format_change(change: Money) -> String {
+    match change > Money::ZERO {
+        true => format!("+{change}"),
+        false => change.to_string(),
+    }
+}
+
+#[component]
+pub fn DashboardPage() -> impl IntoView {
+    let dashboard = create_resource(|| (), |_| get_dashboard());
+
+    view! {
+        <Transition fallback=move || view! { <p>"Loading dashboard..."</p> }>
+            {move || dashboard.get().map(|dashboard| match dashboard {
+                Err(e) => view! {
+                    <p class="error">{e.to_string()}</p>
+                    <A href="/login">"Log in"</A>
+                }.into_view(),
+                Ok(dashboard) => view! {
+                    <h1>"Hi " {dashboard.email}</h1>
+                    <p>
+                        {match dashboard.last_visit {
+                            Some(last_visit) => format!("Price changes to your {} watched products since {}", dashboard.watched_count, last_visit),
+                            None => "Welcome! Changes to your watched products will show up here next time".to_owned(),
+                        }}
+                    </p>
+                    <table>
+                        <thead>
+                            <tr><th>"Product"</th><th>"Store"</th><th>"Was"</th><th>"Now"</th><th>"Change"</th></tr>
+                        </thead>
+                        <tbody>
+                            {dashboard.changes.into_iter().map(|change| view! {
+                                <tr class:price-up={change.new_price > change.old_price}>
+                                    <td>{change.product_title}</td>
+                                    <td>{change.supermarket_name}</td>
+                                    <td>{change.old_price.to_string()}</td>
+                                    <td>{change.new_price.to_string()}</td>
+                                    <td>{format_change(change.new_price - change.old_price)}</td>
+                                </tr>
+                            }).collect_view()}
+                        </tbody>
+                    </table>
+                    <A href="/watchlist">"Manage watchlist"</A>
+                }.into_view(),
+            })}
+        </Transition>
+    }
+}
+
+#[component]
+pub fn WatchlistPage() -> impl IntoView {
+    let query = use_query_map();
+    let search = move || query.with(|q| q.get("q").cloned().unwrap_or_default());
+
+    let add = create_server_action::<AddToWatchlist>();
+    let remove = create_server_action::<RemoveFromWatchlist>();
+    let watchlist = create_resource(
+        move || (add.version().get(), remove.version().get()),
+        |_| get_watchlist(),
+    );
+    let results = create_resource(search, search_products);
+
+    view! {
+        <h1>"Watchlist"</h1>
+        <Transition fallback=move || view! { <p>"Loading watchlist..."</p> }>
+            {move || watchlist.get().map(|watchlist| match watchlist {
+                Err(e) => view! {
+                    <p class="error">{e.to_string()}</p>
+                    <A href="/login">"Log in"</A>
+                }.into_view(),
+                Ok(watchlist) => view! {
+                    <table>
+                        <tbody>
+                            {watchlist.into_iter().map(|product| view! {
+                                <tr>
+                                    <td>{product.product_brand.map(|brand| format!("{brand} "))}{product.product_title}</td>
+                                    <td>
+                                        {product.prices.into_iter().map(|(store, price)| format!("{store}: {price}")).collect::<Vec<String>>().join(", ")}
+                                    </td>
+                                    <td>
+                                        <ActionForm action=remove>
+                                            <input type="hidden" name="watch_id" value=product.watch_id/>
+                                            <input type="submit" value="Stop watching"/>
+                                        </ActionForm>
+                                    </td>
+                                </tr>
+                            }).collect_view()}
+                        </tbody>
+                    </table>
+                }.into_view(),
+            })}
+        </Transition>
+
+        <h2>"Watch more products"</h2>
+        <Form method="GET" action="" class="filters">
+            <input type="search" name="q" placeholder="Search products" value=search/>
+            <input type="submit" value="Search"/>
+        </Form>
+        <Transition fallback=move || view! { <p>"Searching..."</p> }>
+            {move || results.get().map(|results| match results {
+                Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                Ok(results) => view! {
+                    <ul class="search-results">
+                        {results.into_iter().map(|product: ProductSummary| view! {
+                            <li>
+                                {product.product_brand.map(|brand| format!("{brand} "))}
+                                {product.product_title}
+                                <ActionForm action=add>
+                                    <input type="hidden" name="product_id" value=product.product_id/>
+                                    <input type="submit" value="Watch"/>
+                                </ActionForm>
+                            </li>
+                        }).collect_view()}
+                    </ul>
+                }.into_view(),
+            })}
+        </Transition>
+    }
+}
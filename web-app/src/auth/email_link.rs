@@ -0,0 +1,62 @@
+use chrono::{Duration, Utc};
+use entity::{login_token, prelude::*, users};
+use leptos::ServerFnError;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set};
+
+use super::{normalize_email, AuthConfig};
+
+const LINK_LIFETIME_MINUTES: i64 = 15;
+
+/// Emails a one-time login link. Nothing is stored about the address until the link
+/// is followed.
+pub async fn issue(db: &DatabaseConnection, config: &AuthConfig, email: &str) -> Result<(), ServerFnError> {
+    let email = normalize_email(email);
+
+    let token = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+    login_token::ActiveModel {
+        token: Set(token.clone()),
+        email: Set(email.clone()),
+        expires_at: Set((Utc::now() + Duration::minutes(LINK_LIFETIME_MINUTES)).into()),
+    }.insert(db).await?;
+
+    let link = format!("{}/auth/link/{}", config.public_url.trim_end_matches('/'), token);
+    config.mailer
+        .send_login_link(&email, &link)
+        .map_err(ServerFnError::ServerError)
+}
+
+/// Uses up a login link, returning who it was for if it's still valid. Following
+/// the link proves the email is theirs, so this is when their account is created.
+pub async fn redeem(db: &DatabaseConnection, token: &str) -> Result<Option<users::Model>, DbErr> {
+    let Some(login_token) = LoginToken::find_by_id(token.to_owned()).one(db).await? else {
+        return Ok(None);
+    };
+
+    // Whoever deletes the token gets to use it, so two requests racing with the same
+    // link can't both log in
+    let deleted = LoginToken::delete_by_id(login_token.token).exec(db).await?;
+    if deleted.rows_affected != 1 {
+        return Ok(None);
+    }
+
+    if login_token.expires_at < Utc::now() {
+        return Ok(None);
+    }
+
+    let existing = Users::find()
+        .filter(users::Column::Email.eq(&login_token.email))
+        .one(db)
+        .await?;
+
+    let user = match existing {
+        Some(user) => user,
+        None => {
+            users::ActiveModel {
+                email: Set(login_token.email),
+                ..Default::default()
+            }.insert(db).await?
+        }
+    };
+
+    Ok(Some(user))
+}
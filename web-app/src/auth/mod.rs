@@ -0,0 +1,254 @@
+use std::{env, io::Write, process::{Command, Stdio}, sync::Arc};
+
+use actix_web::{
+    cookie::{time, Cookie, SameSite},
+    http::header,
+    web, HttpRequest, HttpResponse,
+};
+use chrono::{Duration, Utc};
+use entity::{prelude::*, shopping_list, user_session, users};
+use leptos::{expect_context, use_context, ServerFnError};
+use leptos_actix::{extract, ResponseOptions};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter, Set,
+};
+
+use crate::shopper::SHOPPER_COOKIE;
+
+pub mod email_link;
+pub mod password;
+
+pub const SESSION_COOKIE: &str = "session";
+const SESSION_LENGTH_DAYS: i64 = 30;
+
+/// Emails are stored and looked up like this, so the same address typed differently
+/// is still the same account
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Delivers login links
+pub trait Mailer: Send + Sync {
+    fn send_login_link(&self, email: &str, link: &str) -> Result<(), String>;
+}
+
+/// Writes login links to the server log. Anyone who can read the log can use them,
+/// so this is only allowed with `AUTH_DEV=true`.
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+    fn send_login_link(&self, email: &str, link: &str) -> Result<(), String> {
+        log::info!("Login link for {email}: {link}");
+        Ok(())
+    }
+}
+
+/// Sends login links through the local `sendmail` command, which most mail servers
+/// and relays like msmtp provide
+pub struct SendmailMailer {
+    pub command: String,
+    pub from: String,
+}
+
+impl Mailer for SendmailMailer {
+    fn send_login_link(&self, email: &str, link: &str) -> Result<(), String> {
+        // The address goes into a header, so it mustn't be able to add headers of its own
+        if email.chars().any(|x| x.is_control() || x.is_whitespace()) {
+            return Err(String::from("That doesn't look like an email address"));
+        }
+
+        let message = format!(
+            "From: {}\r\nTo: {email}\r\nSubject: Your login link\r\n\r\nFollow this link to log in, it works once in the next few minutes:\r\n\r\n{link}\r\n",
+            self.from,
+        );
+
+        let mut child = Command::new(&self.command)
+            .arg("-t")
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Couldn't run {}: {e}", self.command))?;
+        child.stdin
+            .take()
+            .ok_or_else(|| String::from("Couldn't write to sendmail"))?
+            .write_all(message.as_bytes())
+            .map_err(|e| format!("Couldn't write to sendmail: {e}"))?;
+
+        match child.wait() {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(format!("sendmail failed with {status}")),
+            Err(e) => Err(format!("sendmail failed: {e}")),
+        }
+    }
+}
+
+/// Which ways of logging in are turned on, set with `AUTH_METHODS`
+#[derive(Clone)]
+pub struct AuthConfig {
+    pub password: bool,
+    pub email_link: bool,
+    /// Base URL that login links point back to
+    pub public_url: String,
+    pub mailer: Arc<dyn Mailer>,
+}
+
+impl AuthConfig {
+    /// `AUTH_MAILER` picks how login links are sent: `sendmail` (the default, from
+    /// `MAIL_FROM` through `SENDMAIL_COMMAND`) or `log`, which also needs `AUTH_DEV=true`
+    pub fn from_env() -> Result<Self, String> {
+        let methods = env::var("AUTH_METHODS").unwrap_or(String::from("password,email_link"));
+        let methods = methods.split(',').map(|x| x.trim()).collect::<Vec<&str>>();
+
+        let dev = env::var("AUTH_DEV").is_ok_and(|x| x == "true");
+        let mailer: Arc<dyn Mailer> = match env::var("AUTH_MAILER").as_deref().unwrap_or("sendmail") {
+            "sendmail" => Arc::new(SendmailMailer {
+                command: env::var("SENDMAIL_COMMAND").unwrap_or(String::from("/usr/sbin/sendmail")),
+                from: env::var("MAIL_FROM").unwrap_or(String::from("noreply@localhost")),
+            }),
+            "log" if dev => Arc::new(LogMailer),
+            "log" => return Err(String::from("AUTH_MAILER=log writes login links to the log, set AUTH_DEV=true if this is a development server")),
+            other => return Err(format!("AUTH_MAILER={other:?} isn't a mailer, use sendmail or log")),
+        };
+
+        Ok(AuthConfig {
+            password: methods.contains(&"password"),
+            email_link: methods.contains(&"email_link"),
+            public_url: env::var("PUBLIC_URL").unwrap_or(String::from("http://localhost:3000")),
+            mailer,
+        })
+    }
+
+    /// Cookies are only sent over HTTPS when the site is served over it
+    pub fn secure_cookies(&self) -> bool {
+        self.public_url.starts_with("https://")
+    }
+}
+
+pub fn auth_config() -> Result<AuthConfig, ServerFnError> {
+    use_context::<AuthConfig>()
+        .ok_or_else(|| ServerFnError::ServerError("Auth config missing".to_owned()))
+}
+
+/// Looks up the logged in user from the session cookie
+pub async fn current_user() -> Result<Option<users::Model>, ServerFnError> {
+    let db = crate::db::db()?;
+    let token = extract(|req: HttpRequest| async move {
+        req.cookie(SESSION_COOKIE).map(|cookie| cookie.value().to_owned())
+    }).await?;
+
+    match token {
+        Some(token) => Ok(user_for_session(&db, &token).await?),
+        None => Ok(None),
+    }
+}
+
+pub async fn require_user() -> Result<users::Model, ServerFnError> {
+    current_user()
+        .await?
+        .ok_or_else(|| ServerFnError::ServerError("You need to log in first".to_owned()))
+}
+
+async fn user_for_session(db: &DatabaseConnection, token: &str) -> Result<Option<users::Model>, DbErr> {
+    let session = UserSession::find_by_id(token.to_owned())
+        .filter(user_session::Column::ExpiresAt.gt(Utc::now()))
+        .find_also_related(Users)
+        .one(db)
+        .await?;
+
+    Ok(session.and_then(|(_, user)| user))
+}
+
+/// Creates a session for the user, moving any shopping lists they made
+/// before logging in over to their account
+async fn create_session(db: &DatabaseConnection, config: &AuthConfig, user: &users::Model, shopper_id: Option<String>) -> Result<Cookie<'static>, DbErr> {
+    let token = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+    let now = Utc::now();
+
+    user_session::ActiveModel {
+        token: Set(token.clone()),
+        user_id: Set(user.id),
+        created_at: Set(now.into()),
+        expires_at: Set((now + Duration::days(SESSION_LENGTH_DAYS)).into()),
+    }.insert(db).await?;
+
+    if let Some(shopper_id) = shopper_id {
+        ShoppingList::update_many()
+            .col_expr(shopping_list::Column::UserId, Expr::value(user.id))
+            .filter(shopping_list::Column::Owner.eq(shopper_id))
+            .filter(shopping_list::Column::UserId.is_null())
+            .exec(db)
+            .await?;
+    }
+
+    Ok(Cookie::build(SESSION_COOKIE, token)
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .secure(config.secure_cookies())
+        .max_age(time::Duration::days(SESSION_LENGTH_DAYS))
+        .finish())
+}
+
+/// Logs the user in for the rest of this server function's response
+pub async fn start_session(db: &DatabaseConnection, user: &users::Model) -> Result<(), ServerFnError> {
+    let shopper_id = extract(|req: HttpRequest| async move {
+        req.cookie(SHOPPER_COOKIE).map(|cookie| cookie.value().to_owned())
+    }).await?;
+
+    let cookie = create_session(db, &auth_config()?, user, shopper_id).await?;
+
+    let response = expect_context::<ResponseOptions>();
+    response.append_header(header::SET_COOKIE, header::HeaderValue::from_str(&cookie.to_string())?);
+
+    Ok(())
+}
+
+pub async fn end_session(db: &DatabaseConnection) -> Result<(), ServerFnError> {
+    let token = extract(|req: HttpRequest| async move {
+        req.cookie(SESSION_COOKIE).map(|cookie| cookie.value().to_owned())
+    }).await?;
+
+    if let Some(token) = token {
+        UserSession::delete_by_id(token).exec(db).await?;
+    }
+
+    let mut cookie = Cookie::build(SESSION_COOKIE, "").path("/").finish();
+    cookie.make_removal();
+
+    let response = expect_context::<ResponseOptions>();
+    response.append_header(header::SET_COOKIE, header::HeaderValue::from_str(&cookie.to_string())?);
+
+    Ok(())
+}
+
+/// `GET /auth/link/{token}`, the target of emailed login links
+pub async fn redeem_login_link(
+    req: HttpRequest,
+    token: web::Path<String>,
+    db: web::Data<DatabaseConnection>,
+    config: web::Data<AuthConfig>,
+) -> HttpResponse {
+    let redirect = |location: &str| {
+        HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, location.to_owned()))
+            .finish()
+    };
+
+    let user = match email_link::redeem(&db, &token).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return redirect("/login?error=link-expired"),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let shopper_id = req.cookie(SHOPPER_COOKIE).map(|cookie| cookie.value().to_owned());
+    match create_session(&db, &config, &user, shopper_id).await {
+        Ok(cookie) => {
+            let mut response = redirect("/dashboard");
+            match response.add_cookie(&cookie) {
+                Ok(()) => response,
+                Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+            }
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,64 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use entity::{prelude::*, users};
+use leptos::ServerFnError;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+
+use super::normalize_email;
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default().hash_password(password.as_bytes(), &salt)?.to_string())
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
+/// Creates an account. An email that already has one, even one that has only used
+/// login links, can't be registered again, or anyone could take it over by picking
+/// a password for it.
+pub async fn register(db: &DatabaseConnection, email: &str, password: &str) -> Result<users::Model, ServerFnError> {
+    let email = normalize_email(email);
+    if password.len() < MIN_PASSWORD_LENGTH {
+        return Err(ServerFnError::Args(format!("Passwords need at least {MIN_PASSWORD_LENGTH} characters")));
+    }
+
+    let password_hash = hash_password(password)
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    let existing = Users::find()
+        .filter(users::Column::Email.eq(&email))
+        .one(db)
+        .await?;
+    if existing.is_some() {
+        return Err(ServerFnError::Args("An account with that email already exists, log in to it instead".to_owned()));
+    }
+
+    let user = users::ActiveModel {
+        email: Set(email),
+        password_hash: Set(Some(password_hash)),
+        ..Default::default()
+    }.insert(db).await?;
+
+    Ok(user)
+}
+
+/// Checks an email and password, returning the user if they match
+pub async fn login(db: &DatabaseConnection, email: &str, password: &str) -> Result<Option<users::Model>, ServerFnError> {
+    let user = Users::find()
+        .filter(users::Column::Email.eq(normalize_email(email)))
+        .one(db)
+        .await?;
+
+    Ok(user.filter(|user| {
+        user.password_hash
+            .as_deref()
+            .is_some_and(|hash| verify_password(password, hash))
+    }))
+}
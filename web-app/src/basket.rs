@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use money::Money;
+use serde::{Deserialize, Serialize};
+
+/// The latest price of a product at one store
+#[derive(Clone, Debug)]
+pub struct StorePrice {
+    pub product_id: i32,
+    pub supermarket_id: i32,
+    pub price: Money,
+    /// (quantity, total price), e.g. "2 for $5"
+    pub multibuy: Option<(i32, Money)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Store {
+    pub supermarket_id: i32,
+    pub name: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct BasketItem {
+    pub item_id: i32,
+    pub product_id: i32,
+    pub title: String,
+    pub quantity: i32,
+    /// Acceptable replacements as (product_id, title), best match first
+    pub substitutes: Vec<(i32, String)>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuoteLine {
+    pub item_id: i32,
+    /// The product actually bought, which may be a substitute
+    pub product_title: String,
+    pub substituted: bool,
+    pub quantity: i32,
+    pub cost: Money,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoreQuote {
+    pub supermarket_id: i32,
+    pub supermarket_name: String,
+    pub total: Money,
+    pub lines: Vec<QuoteLine>,
+    /// Titles of items this store can't supply
+    pub missing: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SplitQuote {
+    pub supermarket_names: (String, String),
+    pub total: Money,
+    /// (store name, line) for every item in the basket
+    pub lines: Vec<(String, QuoteLine)>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BasketComparison {
+    /// Cheapest complete basket first
+    pub quotes: Vec<StoreQuote>,
+    pub best_split: Option<SplitQuote>,
+}
+
+/// Cost of buying `quantity` of a product, applying a multibuy deal if it's cheaper
+pub fn line_cost(price: &StorePrice, quantity: i32) -> Money {
+    match price.multibuy {
+        Some((deal_quantity, deal_price)) if deal_quantity > 0 && deal_price < price.price * deal_quantity => {
+            deal_price * (quantity / deal_quantity) + price.price * (quantity % deal_quantity)
+        }
+        _ => price.price * quantity,
+    }
+}
+
+fn quote_store(items: &[BasketItem], prices: &HashMap<(i32, i32), StorePrice>, store: &Store) -> StoreQuote {
+    let mut lines = Vec::new();
+    let mut missing = Vec::new();
+
+    for item in items {
+        if let Some(price) = prices.get(&(item.product_id, store.supermarket_id)) {
+            lines.push(QuoteLine {
+                item_id: item.item_id,
+                product_title: item.title.clone(),
+                substituted: false,
+                quantity: item.quantity,
+                cost: line_cost(price, item.quantity),
+            });
+            continue;
+        }
+
+        // Fall back to the cheapest substitute this store stocks
+        let substitute = item.substitutes
+            .iter()
+            .filter_map(|(product_id, title)| {
+                prices
+                    .get(&(*product_id, store.supermarket_id))
+                    .map(|price| (title, line_cost(price, item.quantity)))
+            })
+            .min_by_key(|x| x.1);
+
+        match substitute {
+            Some((title, cost)) => lines.push(QuoteLine {
+                item_id: item.item_id,
+                product_title: title.clone(),
+                substituted: true,
+                quantity: item.quantity,
+                cost,
+            }),
+            None => missing.push(item.title.clone()),
+        }
+    }
+
+    StoreQuote {
+        supermarket_id: store.supermarket_id,
+        supermarket_name: store.name.clone(),
+        total: lines.iter().map(|x| x.cost).sum(),
+        lines,
+        missing,
+    }
+}
+
+/// Cheapest way to buy the basket across any two stores, only considering
+/// pairs that between them stock everything that is available anywhere
+fn best_split(items: &[BasketItem], quotes: &[StoreQuote]) -> Option<SplitQuote> {
+    let available_anywhere = items
+        .iter()
+        .filter(|item| quotes.iter().any(|quote| quote.lines.iter().any(|line| line.item_id == item.item_id)))
+        .collect::<Vec<&BasketItem>>();
+
+    let mut best: Option<SplitQuote> = None;
+    for (i, first) in quotes.iter().enumerate() {
+        for second in quotes.iter().skip(i + 1) {
+            let mut lines = Vec::new();
+
+            for item in &available_anywhere {
+                let first_line = first.lines.iter().find(|line| line.item_id == item.item_id);
+                let second_line = second.lines.iter().find(|line| line.item_id == item.item_id);
+
+                let choice = match (first_line, second_line) {
+                    (Some(a), Some(b)) if b.cost < a.cost => Some((&second.supermarket_name, b)),
+                    (Some(a), _) => Some((&first.supermarket_name, a)),
+                    (None, Some(b)) => Some((&second.supermarket_name, b)),
+                    (None, None) => None,
+                };
+
+                match choice {
+                    Some((name, line)) => lines.push((name.clone(), line.clone())),
+                    None => break,
+                }
+            }
+
+            if lines.len() != available_anywhere.len() {
+                continue;
+            }
+
+            let total = lines.iter().map(|(_, line)| line.cost).sum();
+            if best.as_ref().is_none_or(|x| total < x.total) {
+                best = Some(SplitQuote {
+                    supermarket_names: (first.supermarket_name.clone(), second.supermarket_name.clone()),
+                    total,
+                    lines,
+                });
+            }
+        }
+    }
+
+    best
+}
+
+/// Prices the basket at every store, plus the cheapest two-store split
+pub fn compare_basket(items: &[BasketItem], prices: &[StorePrice], stores: &[Store]) -> BasketComparison {
+    let prices = prices
+        .iter()
+        .map(|x| ((x.product_id, x.supermarket_id), x.clone()))
+        .collect::<HashMap<(i32, i32), StorePrice>>();
+
+    let mut quotes = stores
+        .iter()
+        .map(|store| quote_store(items, &prices, store))
+        .filter(|quote| !quote.lines.is_empty())
+        .collect::<Vec<StoreQuote>>();
+
+    quotes.sort_by(|a, b| {
+        a.missing.len().cmp(&b.missing.len()).then(a.total.cmp(&b.total))
+    });
+
+    let best_split = best_split(items, &quotes);
+
+    BasketComparison { quotes, best_split }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(product_id: i32, supermarket_id: i32, cents: i64) -> StorePrice {
+        StorePrice { product_id, supermarket_id, price: Money::from_cents(cents), multibuy: None }
+    }
+
+    fn deal(cents: i64, quantity: i32, deal_cents: i64) -> StorePrice {
+        StorePrice { multibuy: Some((quantity, Money::from_cents(deal_cents))), ..price(1, 1, cents) }
+    }
+
+    fn item(product_id: i32, substitutes: &[i32]) -> BasketItem {
+        BasketItem {
+            item_id: product_id,
+            product_id,
+            title: format!("product {product_id}"),
+            quantity: 1,
+            substitutes: substitutes.iter().map(|x| (*x, format!("product {x}"))).collect(),
+        }
+    }
+
+    fn store(supermarket_id: i32, name: &str) -> Store {
+        Store { supermarket_id, name: name.to_owned() }
+    }
+
+    #[test]
+    fn takes_a_multibuy_that_is_cheaper() {
+        // 2 for $5 beats $3 each
+        assert_eq!(line_cost(&deal(300, 2, 500), 2), Money::from_cents(500));
+        assert_eq!(line_cost(&deal(300, 2, 500), 4), Money::from_cents(1000));
+    }
+
+    #[test]
+    fn ignores_a_multibuy_that_isnt_cheaper() {
+        // 2 for $4.50 is worse than $2 each
+        assert_eq!(line_cost(&deal(200, 2, 450), 2), Money::from_cents(400));
+    }
+
+    #[test]
+    fn pays_full_price_beyond_the_deal() {
+        assert_eq!(line_cost(&deal(300, 2, 500), 3), Money::from_cents(800));
+        assert_eq!(line_cost(&deal(300, 2, 500), 1), Money::from_cents(300));
+        assert_eq!(line_cost(&deal(300, 3, 600), 7), Money::from_cents(1500));
+    }
+
+    #[test]
+    fn substitutes_the_cheapest_replacement() {
+        let items = [item(1, &[2, 3])];
+        let prices = [price(2, 1, 400), price(3, 1, 350), price(1, 2, 500)];
+        let stores = [store(1, "Substitutes"), store(2, "Original")];
+
+        let comparison = compare_basket(&items, &prices, &stores);
+
+        let substitutes = comparison.quotes.iter().find(|x| x.supermarket_id == 1).unwrap();
+        assert_eq!(substitutes.lines[0].product_title, "product 3");
+        assert!(substitutes.lines[0].substituted);
+        assert_eq!(substitutes.total, Money::from_cents(350));
+    }
+
+    #[test]
+    fn complete_baskets_come_before_cheaper_incomplete_ones() {
+        let items = [item(1, &[]), item(2, &[])];
+        let prices = [price(1, 1, 100), price(1, 2, 500), price(2, 2, 500)];
+        let stores = [store(1, "Cheap"), store(2, "Everything")];
+
+        let comparison = compare_basket(&items, &prices, &stores);
+
+        let order = comparison.quotes.iter().map(|x| x.supermarket_name.as_str()).collect::<Vec<_>>();
+        assert_eq!(order, ["Everything", "Cheap"]);
+        assert_eq!(comparison.quotes[1].missing, ["product 2"]);
+    }
+
+    #[test]
+    fn splits_only_between_stores_that_cover_the_basket() {
+        // Product 4 isn't sold anywhere, so it can't rule a split out
+        let items = [item(1, &[]), item(2, &[]), item(3, &[]), item(4, &[])];
+        let prices = [
+            price(1, 1, 100), price(2, 1, 400),
+            price(1, 2, 500), price(2, 2, 100),
+            price(1, 3, 600), price(2, 3, 600), price(3, 3, 100),
+        ];
+        let stores = [store(1, "A"), store(2, "B"), store(3, "C")];
+
+        let split = compare_basket(&items, &prices, &stores).best_split.unwrap();
+
+        // A and B together are cheapest but neither has product 3
+        let (first, second) = &split.supermarket_names;
+        let mut names = [first.as_str(), second.as_str()];
+        names.sort();
+        assert_eq!(names, ["A", "C"]);
+        assert_eq!(split.total, Money::from_cents(600));
+        assert_eq!(split.lines.len(), 3);
+    }
+}
@@ -0,0 +1,192 @@
+use leptos::*;
+
+/// How often to look for a barcode in the camera feed
+#[cfg(feature = "hydrate")]
+const SCAN_INTERVAL_MS: i32 = 250;
+
+/// Reads a barcode from the device camera with the browser's BarcodeDetector and
+/// passes the raw code to `on_scan`. Only does anything once hydrated, and browsers
+/// without BarcodeDetector (Firefox, Safari) or a camera can't scan at all, so pages
+/// using it should also offer a way to type the code in.
+#[component]
+pub fn BarcodeScanner(
+    /// Called once with the first barcode read
+    #[prop(into)]
+    on_scan: Callback<String>,
+    /// Called if this browser can't scan, after saying so
+    #[prop(into, optional)]
+    on_unavailable: Option<Callback<()>>,
+) -> impl IntoView {
+    let video = create_node_ref::<html::Video>();
+    let (status, set_status) = create_signal("Starting camera...".to_owned());
+    let (available, set_available) = create_signal(true);
+
+    #[cfg(feature = "hydrate")]
+    {
+        use std::{cell::Cell, rc::Rc};
+
+        let stopped = Rc::new(Cell::new(false));
+        on_cleanup({
+            let stopped = stopped.clone();
+            move || stopped.set(true)
+        });
+
+        video.on_load(move |video| {
+            spawn_local(async move {
+                let result = match camera::supported().await {
+                    true => camera::scan(&video, &stopped, |message| set_status.set(message.to_owned())).await,
+                    false => Err(camera::UNSUPPORTED.into()),
+                };
+                match result {
+                    Ok(Some(code)) => on_scan.call(code),
+                    Ok(None) => {}
+                    Err(e) => {
+                        set_status.set(format!("{}. Type the number under the barcode in instead.", camera::error_message(e).trim_end_matches('.')));
+                        set_available.set(false);
+                        if let Some(on_unavailable) = on_unavailable {
+                            on_unavailable.call(());
+                        }
+                    }
+                }
+            });
+        });
+    }
+
+    #[cfg(not(feature = "hydrate"))]
+    let _ = (video, set_status, set_available, on_scan, on_unavailable);
+
+    view! {
+        <div class="barcode-scanner">
+            <video node_ref=video muted=true playsinline=true hidden=move || !available.get()></video>
+            <p class:error=move || !available.get()>{status}</p>
+        </div>
+    }
+}
+
+#[cfg(feature = "hydrate")]
+mod camera {
+    use std::cell::Cell;
+
+    use js_sys::{Array, Function, Object, Promise, Reflect};
+    use wasm_bindgen::{JsCast, JsValue};
+    use wasm_bindgen_futures::JsFuture;
+    use web_sys::{HtmlVideoElement, MediaStream, MediaStreamConstraints, MediaStreamTrack};
+
+    use super::SCAN_INTERVAL_MS;
+
+    /// What the scanner looks for, the barcodes on retail products
+    const FORMATS: [&str; 3] = ["ean_13", "ean_8", "upc_a"];
+
+    pub const UNSUPPORTED: &str = "This browser can't scan barcodes";
+
+    /// Whether this browser has a BarcodeDetector that reads retail barcodes. Some
+    /// have the class but no formats to go with it.
+    pub async fn supported() -> bool {
+        match supported_formats().await {
+            Ok(formats) => FORMATS.iter().any(|format| formats.includes(&(*format).into(), 0)),
+            Err(_) => false,
+        }
+    }
+
+    async fn supported_formats() -> Result<Array, JsValue> {
+        let class = detector_class()?;
+        let get_supported: Function = Reflect::get(&class, &"getSupportedFormats".into())?.dyn_into()?;
+        let promise: Promise = get_supported.call0(&class)?.dyn_into()?;
+
+        JsFuture::from(promise).await?.dyn_into()
+    }
+
+    fn detector_class() -> Result<JsValue, JsValue> {
+        let window = web_sys::window().ok_or("No window")?;
+        let class = Reflect::get(&window, &"BarcodeDetector".into())?;
+        if class.is_undefined() {
+            return Err(UNSUPPORTED.into());
+        }
+
+        Ok(class)
+    }
+
+    /// Streams the camera into `video` until a barcode is read or `stopped` is set
+    pub async fn scan(video: &HtmlVideoElement, stopped: &Cell<bool>, set_status: impl Fn(&str)) -> Result<Option<String>, JsValue> {
+        let detector = detector()?;
+        let stream = start_camera(video).await?;
+        set_status("Point the camera at a barcode");
+
+        let mut code = None;
+        while !stopped.get() {
+            // Frames before the video has data fail to read, so errors just mean try again
+            if let Ok(Some(raw)) = detect(&detector, video).await {
+                code = Some(raw);
+                break;
+            }
+            sleep(SCAN_INTERVAL_MS).await;
+        }
+
+        for track in stream.get_tracks().iter() {
+            if let Ok(track) = track.dyn_into::<MediaStreamTrack>() {
+                track.stop();
+            }
+        }
+
+        Ok(code)
+    }
+
+    /// Creates a BarcodeDetector for the retail barcode formats
+    fn detector() -> Result<Object, JsValue> {
+        let class = detector_class()?;
+
+        let options = Object::new();
+        let formats = FORMATS.iter().map(|format| JsValue::from(*format)).collect::<Array>();
+        Reflect::set(&options, &"formats".into(), &formats)?;
+
+        Ok(Reflect::construct(class.unchecked_ref(), &Array::of1(&options))?.unchecked_into())
+    }
+
+    /// Opens the rear camera, if there is one, and plays it in the video element
+    async fn start_camera(video: &HtmlVideoElement) -> Result<MediaStream, JsValue> {
+        let devices = web_sys::window().ok_or("No window")?.navigator().media_devices()?;
+
+        let video_constraints = Object::new();
+        Reflect::set(&video_constraints, &"facingMode".into(), &"environment".into())?;
+        let mut constraints = MediaStreamConstraints::new();
+        constraints.video(&video_constraints);
+
+        let stream: MediaStream = JsFuture::from(devices.get_user_media_with_constraints(&constraints)?)
+            .await?
+            .dyn_into()?;
+
+        video.set_src_object(Some(&stream));
+        JsFuture::from(video.play()?).await?;
+
+        Ok(stream)
+    }
+
+    /// The first barcode visible in the current video frame
+    async fn detect(detector: &Object, video: &HtmlVideoElement) -> Result<Option<String>, JsValue> {
+        let detect: Function = Reflect::get(detector, &"detect".into())?.dyn_into()?;
+        let promise: Promise = detect.call1(detector, video)?.dyn_into()?;
+        let barcodes: Array = JsFuture::from(promise).await?.dyn_into()?;
+
+        let first = barcodes.get(0);
+        if first.is_undefined() {
+            return Ok(None);
+        }
+
+        Ok(Reflect::get(&first, &"rawValue".into())?.as_string())
+    }
+
+    async fn sleep(ms: i32) {
+        let promise = Promise::new(&mut |resolve, _| {
+            if let Some(window) = web_sys::window() {
+                let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms);
+            }
+        });
+        let _ = JsFuture::from(promise).await;
+    }
+
+    pub fn error_message(e: JsValue) -> String {
+        e.as_string()
+            .or_else(|| Reflect::get(&e, &"message".into()).ok().and_then(|x| x.as_string()))
+            .unwrap_or_else(|| "Couldn't start the camera".to_owned())
+    }
+}
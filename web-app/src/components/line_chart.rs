@@ -0,0 +1,46 @@
+use leptos::*;
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 300.0;
+const PADDING: f64 = 40.0;
+
+/// A single series line chart rendered as SVG, so it works without hydration
+#[component]
+pub fn LineChart(
+    /// (label, value) pairs, in the order they should be plotted
+    points: Vec<(String, f64)>,
+) -> impl IntoView {
+    if points.is_empty() {
+        return view! { <p>"No data yet"</p> }.into_view();
+    }
+
+    let min = points.iter().map(|(_, y)| *y).fold(f64::INFINITY, f64::min);
+    let max = points.iter().map(|(_, y)| *y).fold(f64::NEG_INFINITY, f64::max);
+    let range = if max - min > f64::EPSILON { max - min } else { 1.0 };
+    let step = (WIDTH - 2.0 * PADDING) / (points.len().max(2) - 1) as f64;
+
+    let to_x = |i: usize| PADDING + i as f64 * step;
+    let to_y = |y: f64| HEIGHT - PADDING - (y - min) / range * (HEIGHT - 2.0 * PADDING);
+
+    let line = points
+        .iter()
+        .enumerate()
+        .map(|(i, (_, y))| format!("{:.1},{:.1}", to_x(i), to_y(*y)))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    let first_label = points.first().map(|(label, _)| label.clone());
+    let last_label = points.last().map(|(label, _)| label.clone());
+
+    view! {
+        <svg class="line-chart" viewBox=format!("0 0 {WIDTH} {HEIGHT}") width=WIDTH height=HEIGHT>
+            <line x1=PADDING y1=HEIGHT - PADDING x2=WIDTH - PADDING y2=HEIGHT - PADDING class="axis"/>
+            <line x1=PADDING y1=PADDING x2=PADDING y2=HEIGHT - PADDING class="axis"/>
+            <text x=PADDING - 4.0 y=to_y(max) text-anchor="end">{format!("{max:.1}")}</text>
+            <text x=PADDING - 4.0 y=to_y(min) text-anchor="end">{format!("{min:.1}")}</text>
+            <text x=PADDING y=HEIGHT - PADDING / 4.0>{first_label}</text>
+            <text x=WIDTH - PADDING y=HEIGHT - PADDING / 4.0 text-anchor="end">{last_label}</text>
+            <polyline points=line class="series"/>
+        </svg>
+    }.into_view()
+}
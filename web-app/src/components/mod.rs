@@ -1 +1,3 @@
+pub mod barcode_scanner;
 pub mod histogram;
+pub mod line_chart;
@@ -0,0 +1,94 @@
+use std::{collections::HashMap, env};
+
+use chrono::NaiveDateTime;
+use entity::{prelude::*, price_daily, product_image, product_image_source, supermarket_price};
+use leptos::{use_context, ServerFnError};
+use sea_orm::{prelude::DateTimeWithTimeZone, ColumnTrait, ConnectOptions, Database, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect, Select};
+
+use crate::metrics;
+
+pub async fn connect() -> DatabaseConnection {
+    if dotenv::dotenv().is_err() {
+        println!("No .env file found");
+    }
+
+    let db_connection_uri = env::var("DATABASE_URL")
+        .expect("Missing DATABASE_URL environment variable");
+
+    let mut opt = ConnectOptions::new(db_connection_uri);
+    opt.sqlx_logging(false);
+
+    Database::connect(opt).await.expect("Failed to connect to the database")
+}
+
+/// Fetches the database connection provided to server functions and routes
+pub fn db() -> Result<DatabaseConnection, ServerFnError> {
+    use_context::<DatabaseConnection>()
+        .ok_or_else(|| ServerFnError::ServerError("Database connection missing".to_owned()))
+}
+
+/// The most recent price row for each of the products at every store
+pub async fn latest_prices(db: &DatabaseConnection, product_ids: Vec<i32>) -> Result<Vec<supermarket_price::Model>, DbErr> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["latest_prices"]).start_timer();
+    latest_prices_query(product_ids)
+        .all(db)
+        .await
+}
+
+/// The price row for each of the products at every store as it stood at `as_of`
+pub async fn prices_as_of(db: &DatabaseConnection, product_ids: Vec<i32>, as_of: DateTimeWithTimeZone) -> Result<Vec<supermarket_price::Model>, DbErr> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["prices_as_of"]).start_timer();
+    latest_prices_query(product_ids)
+        .filter(supermarket_price::Column::Timestamp.lte(as_of))
+        .all(db)
+        .await
+}
+
+/// When the newest price was scraped, if there are any prices
+pub async fn data_last_updated(db: &DatabaseConnection) -> Result<Option<NaiveDateTime>, DbErr> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["data_last_updated"]).start_timer();
+    SupermarketPrice::find()
+        .select_only()
+        .column_as(supermarket_price::Column::Timestamp.max(), "last_updated")
+        .into_tuple::<Option<DateTimeWithTimeZone>>()
+        .one(db)
+        .await
+        .map(|x| x.flatten().map(|x| x.naive_utc()))
+}
+
+/// A product's daily prices at every store since `since`, oldest first
+pub async fn price_history(db: &DatabaseConnection, product_id: i32, since: DateTimeWithTimeZone) -> Result<Vec<price_daily::Model>, DbErr> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["price_history"]).start_timer();
+    PriceDaily::find()
+        .filter(price_daily::Column::ProductId.eq(product_id))
+        .filter(price_daily::Column::Bucket.gte(since))
+        .order_by_asc(price_daily::Column::Bucket)
+        .all(db)
+        .await
+}
+
+/// The content hash of the mirrored copy of each of these image URLs that has one,
+/// by URL
+pub async fn mirrored_images(db: &DatabaseConnection, image_urls: Vec<String>) -> Result<HashMap<String, String>, DbErr> {
+    let _timer = metrics::DB_QUERY_DURATION.with_label_values(&["mirrored_images"]).start_timer();
+    ProductImageSource::find()
+        .select_only()
+        .column(product_image_source::Column::Url)
+        .column(product_image::Column::ContentHash)
+        .inner_join(ProductImage)
+        .filter(product_image_source::Column::Url.is_in(image_urls))
+        .into_tuple::<(String, String)>()
+        .all(db)
+        .await
+        .map(|x| x.into_iter().collect())
+}
+
+fn latest_prices_query(product_ids: Vec<i32>) -> Select<SupermarketPrice> {
+    SupermarketPrice::find()
+        .distinct_on([supermarket_price::Column::ProductId, supermarket_price::Column::SupermarketId])
+        .filter(supermarket_price::Column::ProductId.is_in(product_ids))
+        .filter(supermarket_price::Column::Flagged.eq(false))
+        .order_by_asc(supermarket_price::Column::ProductId)
+        .order_by_asc(supermarket_price::Column::SupermarketId)
+        .order_by_desc(supermarket_price::Column::Timestamp)
+}
@@ -0,0 +1,41 @@
+use actix_web::{web, HttpResponse};
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Health {
+    database: bool,
+    data_last_updated: Option<NaiveDateTime>,
+    data_age_seconds: Option<i64>,
+}
+
+async fn check(db: &DatabaseConnection) -> Health {
+    let database = db.ping().await.is_ok();
+    let data_last_updated = match database {
+        true => crate::db::data_last_updated(db).await.ok().flatten(),
+        false => None,
+    };
+
+    Health {
+        database,
+        data_last_updated,
+        data_age_seconds: data_last_updated.map(|x| (Utc::now().naive_utc() - x).num_seconds()),
+    }
+}
+
+/// `GET /healthz`, fine as long as we can answer. Stale data or a database outage
+/// is reported but doesn't fail it, restarting the web app wouldn't fix either.
+pub async fn healthz(db: web::Data<DatabaseConnection>) -> HttpResponse {
+    HttpResponse::Ok().json(check(&db).await)
+}
+
+/// `GET /readyz`, ready for traffic once the database can be reached
+pub async fn readyz(db: web::Data<DatabaseConnection>) -> HttpResponse {
+    let health = check(&db).await;
+
+    match health.database {
+        true => HttpResponse::Ok().json(health),
+        false => HttpResponse::ServiceUnavailable().json(health),
+    }
+}
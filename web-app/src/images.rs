@@ -0,0 +1,45 @@
+use std::env;
+
+use actix_web::{http::header, web, HttpResponse};
+use image_store::{thumbnail_key, ImageStore, THUMBNAIL_SIZES};
+
+/// For small pictures beside a product, e.g. barcode results
+pub const SMALL: u32 = THUMBNAIL_SIZES[0];
+/// For product cards and the JSON API
+pub const LARGE: u32 = THUMBNAIL_SIZES[1];
+
+/// The store the scraper mirrors product images into, set with `IMAGE_STORE` to a
+/// directory or an `s3://bucket/prefix` URL
+pub fn open_store() -> Result<ImageStore, String> {
+    let location = env::var("IMAGE_STORE").unwrap_or(String::from("images"));
+
+    ImageStore::open(&location).map_err(|e| format!("IMAGE_STORE {location:?} can't be opened: {e}"))
+}
+
+/// Where a product's picture should be loaded from: our copy if the scraper has
+/// mirrored it, otherwise straight from the supermarket
+pub fn product_image_url(content_hash: Option<String>, image_url: Option<String>, size: u32) -> Option<String> {
+    match content_hash {
+        Some(hash) => Some(format!("/images/{size}/{hash}.jpg")),
+        None => image_url,
+    }
+}
+
+/// `GET /images/{size}/{hash}.jpg`. A thumbnail is named after the image it was made
+/// from, so it never changes and browsers can keep it for good.
+pub async fn thumbnail(store: web::Data<ImageStore>, path: web::Path<(u32, String)>) -> HttpResponse {
+    let (size, hash) = path.into_inner();
+    let valid_hash = hash.len() == 64 && hash.bytes().all(|x| x.is_ascii_hexdigit());
+    if !valid_hash || !THUMBNAIL_SIZES.contains(&size) {
+        return HttpResponse::NotFound().finish();
+    }
+
+    match store.get(&thumbnail_key(&hash, size)).await {
+        Ok(Some(bytes)) => HttpResponse::Ok()
+            .content_type("image/jpeg")
+            .insert_header((header::CACHE_CONTROL, "public, max-age=31536000, immutable"))
+            .body(bytes),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
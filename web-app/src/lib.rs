@@ -1,5 +1,19 @@
 pub mod app;
+#[cfg(feature = "ssr")]
+pub mod auth;
+pub mod basket;
 mod components;
+#[cfg(feature = "ssr")]
+pub mod db;
+#[cfg(feature = "ssr")]
+pub mod health;
+#[cfg(feature = "ssr")]
+pub mod images;
+#[cfg(feature = "ssr")]
+pub mod metrics;
+#[cfg(feature = "ssr")]
+pub mod shopper;
+pub mod shrinkflation;
 use cfg_if::cfg_if;
 
 cfg_if! {
@@ -2,13 +2,32 @@
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     use actix_files::Files;
-    use actix_web::*;
+    use actix_web::{dev::Service, *};
     use leptos::*;
     use leptos_actix::{generate_route_list, LeptosRoutes};
     use web_app::app::*;
 
+    if let Err(e) = pretty_flexible_env_logger::try_init_with("INFO") {
+        eprintln!("Couldn't start logging: {e}");
+    }
+
     let conf = get_configuration(None).await.unwrap();
     let addr = conf.leptos_options.site_addr;
+    let db = web_app::db::connect().await;
+    let auth_config = match web_app::auth::AuthConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("Auth config isn't valid: {e}");
+            std::process::exit(1);
+        }
+    };
+    let images = match web_app::images::open_store() {
+        Ok(store) => web::Data::new(store),
+        Err(e) => {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    };
     // Generate the list of routes in your Leptos App
     let routes = generate_route_list(App);
     println!("listening on http://{}", &addr);
@@ -16,17 +35,42 @@ async fn main() -> std::io::Result<()> {
     HttpServer::new(move || {
         let leptos_options = &conf.leptos_options;
         let site_root = &leptos_options.site_root;
+        let app_db = db.clone();
+        let app_auth_config = auth_config.clone();
+        let provide_db = move || {
+            provide_context(app_db.clone());
+            provide_context(app_auth_config.clone());
+        };
 
         App::new()
-            .route("/api/{tail:.*}", leptos_actix::handle_server_fns())
+            .route("/metrics", web::get().to(web_app::metrics::metrics))
+            .route("/healthz", web::get().to(web_app::health::healthz))
+            .route("/readyz", web::get().to(web_app::health::readyz))
+            .route("/api/shrinkflation", web::get().to(web_app::shrinkflation::shrinkflation_api))
+            .route("/api/{tail:.*}", leptos_actix::handle_server_fns_with_context(provide_db.clone()))
+            .route("/auth/link/{token}", web::get().to(web_app::auth::redeem_login_link))
+            .route("/images/{size}/{hash}.jpg", web::get().to(web_app::images::thumbnail))
             // serve JS/WASM/CSS from `pkg`
             .service(Files::new("/pkg", format!("{site_root}/pkg")))
             // serve other assets from the `assets` directory
             .service(Files::new("/assets", site_root))
             // serve the favicon from /favicon.ico
             .service(favicon)
-            .leptos_routes(leptos_options.to_owned(), routes.to_owned(), App)
+            .leptos_routes_with_context(leptos_options.to_owned(), routes.to_owned(), provide_db, App)
             .app_data(web::Data::new(leptos_options.to_owned()))
+            .app_data(web::Data::new(db.clone()))
+            .app_data(web::Data::new(auth_config.clone()))
+            .app_data(images.clone())
+            .wrap_fn(|req, srv| {
+                let started = std::time::Instant::now();
+                let method = req.method().to_string();
+                let response = srv.call(req);
+                async move {
+                    let response = response.await?;
+                    web_app::metrics::observe_request(&method, response.request().match_pattern(), response.status().as_u16(), started.elapsed());
+                    Ok(response)
+                }
+            })
         //.wrap(middleware::Compress::default())
     })
     .bind(&addr)?
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use actix_web::HttpResponse;
+use once_cell::sync::Lazy;
+use prometheus::{register_histogram_vec, Encoder, HistogramVec, TextEncoder};
+
+/// Labelled with the route that matched rather than the path, so every product page
+/// doesn't get its own series
+pub static HTTP_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "web_http_request_duration_seconds",
+        "Time taken to answer requests",
+        &["method", "route", "status"],
+        vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]
+    ).expect("Metric can be registered")
+});
+
+pub static DB_QUERY_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "web_db_query_duration_seconds",
+        "Time taken by database queries",
+        &["query"],
+        vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0]
+    ).expect("Metric can be registered")
+});
+
+pub fn observe_request(method: &str, route: Option<String>, status: u16, elapsed: Duration) {
+    let route = route.unwrap_or(String::from("unmatched"));
+
+    HTTP_REQUEST_DURATION
+        .with_label_values(&[method, &route, &status.to_string()])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// `GET /metrics`, in the Prometheus text format
+pub async fn metrics() -> HttpResponse {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+
+    match encoder.encode(&prometheus::gather(), &mut buffer) {
+        Ok(()) => HttpResponse::Ok().content_type(encoder.format_type()).body(buffer),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,28 @@
+use actix_web::{cookie::Cookie, http::header, HttpRequest};
+use leptos::{expect_context, ServerFnError};
+use leptos_actix::{extract, ResponseOptions};
+
+pub const SHOPPER_COOKIE: &str = "shopper_id";
+
+/// Identifies the browser that owns a shopping list, handing out a new id on first visit
+pub async fn shopper_id() -> Result<String, ServerFnError> {
+    let existing = extract(|req: HttpRequest| async move {
+        req.cookie(SHOPPER_COOKIE).map(|cookie| cookie.value().to_owned())
+    }).await?;
+
+    if let Some(id) = existing {
+        return Ok(id);
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let cookie = Cookie::build(SHOPPER_COOKIE, id.clone())
+        .path("/")
+        .http_only(true)
+        .permanent()
+        .finish();
+
+    let response = expect_context::<ResponseOptions>();
+    response.append_header(header::SET_COOKIE, header::HeaderValue::from_str(&cookie.to_string())?);
+
+    Ok(id)
+}
@@ -0,0 +1,203 @@
+use money::Money;
+use serde::{Deserialize, Serialize};
+
+/// A product that got smaller without getting cheaper
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Shrinkflation {
+    pub product_id: i32,
+    pub product_title: String,
+    pub product_brand: Option<String>,
+    pub department: Option<String>,
+    pub image_url: Option<String>,
+    pub old_size: String,
+    pub new_size: String,
+    pub old_price: Money,
+    pub new_price: Money,
+    /// How much more each gram, millilitre or item costs now, e.g. 0.1 for 10%
+    pub per_unit_increase: f32,
+    pub changed_on: String,
+}
+
+/// e.g. "420g", or "6 x 330ml" for multipacks
+pub fn describe_size(size: Option<f32>, unit: Option<&str>, quantity: i32) -> String {
+    let single = match size {
+        Some(size) => format!("{}{}", (size * 100.0).round() / 100.0, unit.unwrap_or("")),
+        None => unit.unwrap_or("1 ea").to_owned(),
+    };
+
+    match quantity {
+        x if x > 1 => format!("{x} x {single}"),
+        _ => single,
+    }
+}
+
+/// The change in price per unit between two pack sizes, or `None` if the packs
+/// aren't measured the same way or the new one isn't smaller
+pub fn per_unit_increase(old: (f32, &str), old_price: Money, new: (f32, &str), new_price: Money) -> Option<f32> {
+    let ((old_amount, old_unit), (new_amount, new_unit)) = (old, new);
+    if old_unit != new_unit || new_amount >= old_amount {
+        return None;
+    }
+
+    let old_unit_price = old_price.per_unit(old_amount as f64).filter(|x| *x > 0.0)?;
+    let new_unit_price = new_price.per_unit(new_amount as f64)?;
+
+    Some((new_unit_price / old_unit_price - 1.0) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use pack_size::amount;
+
+    use super::*;
+
+    fn increase(old: (f32, &str, i64), new: (f32, &str, i64)) -> Option<f32> {
+        let (old_amount, old_unit) = amount(Some(old.0), Some(old.1), 1);
+        let (new_amount, new_unit) = amount(Some(new.0), Some(new.1), 1);
+        per_unit_increase((old_amount, &old_unit), Money::from_cents(old.2), (new_amount, &new_unit), Money::from_cents(new.2))
+    }
+
+    #[test]
+    fn compares_kilograms_with_grams() {
+        // 1kg to 900g for the same $5 is 11% more a gram
+        let increase = increase((1.0, "kg", 500), (900.0, "g", 500)).unwrap();
+        assert!((increase - 1.0 / 9.0).abs() < 1e-4, "{increase}");
+    }
+
+    #[test]
+    fn compares_litres_with_millilitres() {
+        // 2L at $4 to 1.5L at $3.60 is 20% more a millilitre
+        let increase = increase((2.0, "l", 400), (1500.0, "ml", 360)).unwrap();
+        assert!((increase - 0.2).abs() < 1e-4, "{increase}");
+    }
+
+    #[test]
+    fn packs_measured_differently_dont_compare() {
+        assert_eq!(increase((500.0, "g", 500), (400.0, "ml", 500)), None);
+        assert_eq!(increase((1.0, "kg", 500), (1.0, "l", 500)), None);
+    }
+
+    #[test]
+    fn only_smaller_packs_count() {
+        assert_eq!(increase((500.0, "g", 500), (500.0, "g", 600)), None);
+        assert_eq!(increase((500.0, "g", 500), (0.6, "kg", 500)), None);
+        assert_eq!(increase((500.0, "g", 0), (400.0, "g", 500)), None);
+    }
+
+    #[test]
+    fn describes_sizes() {
+        assert_eq!(describe_size(Some(420.0), Some("g"), 1), "420g");
+        assert_eq!(describe_size(Some(330.0), Some("ml"), 6), "6 x 330ml");
+        assert_eq!(describe_size(Some(1.0 / 3.0), Some("kg"), 1), "0.33kg");
+        assert_eq!(describe_size(None, None, 1), "1 ea");
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub use report::*;
+
+#[cfg(feature = "ssr")]
+mod report {
+    use actix_web::{web, HttpResponse};
+    use chrono::{DateTime, Utc};
+    use pack_size::amount;
+    use sea_orm::{DatabaseConnection, DbBackend, DbErr, FromQueryResult, Statement};
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, FromQueryResult)]
+    struct ShrinkflationRow {
+        product_id: i32,
+        product_title: String,
+        product_brand: Option<String>,
+        department: Option<String>,
+        image_url: Option<String>,
+        image_hash: Option<String>,
+        old_size: Option<f32>,
+        old_unit: Option<String>,
+        old_quantity: i32,
+        old_price: Money,
+        new_size: Option<f32>,
+        new_unit: Option<String>,
+        new_quantity: i32,
+        new_price: Money,
+        revised_at: DateTime<Utc>,
+    }
+
+    /// Every time a product's pack shrank while its price held or rose, biggest
+    /// per-unit price increase first
+    pub async fn report(db: &DatabaseConnection, brand: Option<String>, department: Option<String>) -> Result<Vec<Shrinkflation>, DbErr> {
+        let rows = ShrinkflationRow::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            SELECT
+                r.product_id, p.product_title, p.product_brand, p.department, p.image_url, i.content_hash AS image_hash,
+                prev.size AS old_size, prev.unit AS old_unit, prev.quantity AS old_quantity, prev.price AS old_price,
+                r.size AS new_size, r.unit AS new_unit, r.quantity AS new_quantity, r.price AS new_price,
+                r.revised_at
+            FROM product_revision r
+            JOIN product_db p ON p.product_id = r.product_id
+            LEFT JOIN product_image_source pis ON pis.url = p.image_url
+            LEFT JOIN product_image i ON i.id = pis.image_id
+            JOIN LATERAL (
+                SELECT size, unit, quantity, price
+                FROM product_revision prev
+                WHERE prev.product_id = r.product_id AND prev.id < r.id
+                ORDER BY prev.id DESC
+                LIMIT 1
+            ) prev ON true
+            WHERE (r.changes LIKE '%size%' OR r.changes LIKE '%unit%' OR r.changes LIKE '%quantity%')
+                AND r.price >= prev.price
+                AND ($1::text IS NULL OR p.product_brand = $1)
+                AND ($2::text IS NULL OR p.department = $2)
+            "#,
+            [brand.into(), department.into()],
+        ))
+        .all(db)
+        .await?;
+
+        let mut items = rows
+            .into_iter()
+            .filter_map(|row| {
+                let old = amount(row.old_size, row.old_unit.as_deref(), row.old_quantity);
+                let new = amount(row.new_size, row.new_unit.as_deref(), row.new_quantity);
+                let increase = per_unit_increase((old.0, &old.1), row.old_price, (new.0, &new.1), row.new_price)?;
+
+                Some(Shrinkflation {
+                    product_id: row.product_id,
+                    product_title: row.product_title,
+                    product_brand: row.product_brand,
+                    department: row.department,
+                    image_url: crate::images::product_image_url(row.image_hash, row.image_url, crate::images::LARGE),
+                    old_size: describe_size(row.old_size, row.old_unit.as_deref(), row.old_quantity),
+                    new_size: describe_size(row.new_size, row.new_unit.as_deref(), row.new_quantity),
+                    old_price: row.old_price,
+                    new_price: row.new_price,
+                    per_unit_increase: increase,
+                    changed_on: row.revised_at.format("%Y-%m-%d").to_string(),
+                })
+            })
+            .collect::<Vec<Shrinkflation>>();
+
+        items.sort_by(|a, b| b.per_unit_increase.total_cmp(&a.per_unit_increase));
+
+        Ok(items)
+    }
+
+    #[derive(Deserialize)]
+    pub struct ShrinkflationQuery {
+        brand: Option<String>,
+        department: Option<String>,
+    }
+
+    /// `GET /api/shrinkflation?brand=..&department=..`, the report as JSON
+    pub async fn shrinkflation_api(query: web::Query<ShrinkflationQuery>, db: web::Data<DatabaseConnection>) -> HttpResponse {
+        let ShrinkflationQuery { brand, department } = query.into_inner();
+
+        match report(&db, brand.filter(|x| !x.is_empty()), department.filter(|x| !x.is_empty())).await {
+            Ok(items) => HttpResponse::Ok().json(items),
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        }
+    }
+}